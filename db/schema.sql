@@ -48,20 +48,20 @@ COMMENT ON EXTENSION pgcrypto IS 'cryptographic functions';
 
 
 --
--- Name: lab_fun_audit(uuid, text, text, jsonb, text, text); Type: FUNCTION; Schema: public; Owner: postgres
+-- Name: lab_fun_audit(uuid, text, text, jsonb, text, text, uuid); Type: FUNCTION; Schema: public; Owner: postgres
 --
 
-CREATE FUNCTION public.lab_fun_audit(p_user_id uuid, p_action text, p_target text, p_meta jsonb, p_ip text, p_user_agent text) RETURNS void
+CREATE OR REPLACE FUNCTION public.lab_fun_audit(p_user_id uuid, p_action text, p_target text, p_meta jsonb, p_ip text, p_user_agent text, p_request_id uuid) RETURNS void
     LANGUAGE plpgsql
     AS $$
 BEGIN
-  INSERT INTO lab_audit_logs(user_id, action, target, meta, ip_addr, user_agent)
-  VALUES (p_user_id, p_action, NULLIF(p_target,''), p_meta, NULLIF(p_ip,''), NULLIF(p_user_agent,''));
+  INSERT INTO lab_audit_logs(user_id, action, target, meta, ip_addr, user_agent, request_id)
+  VALUES (p_user_id, p_action, NULLIF(p_target,''), p_meta, NULLIF(p_ip,''), NULLIF(p_user_agent,''), p_request_id);
 END;
 $$;
 
 
-ALTER FUNCTION public.lab_fun_audit(p_user_id uuid, p_action text, p_target text, p_meta jsonb, p_ip text, p_user_agent text) OWNER TO postgres;
+ALTER FUNCTION public.lab_fun_audit(p_user_id uuid, p_action text, p_target text, p_meta jsonb, p_ip text, p_user_agent text, p_request_id uuid) OWNER TO postgres;
 
 --
 -- Name: lab_fun_consume_refresh_token(uuid, bytea, bytea, timestamp with time zone, text, text); Type: FUNCTION; Schema: public; Owner: postgres
@@ -102,45 +102,47 @@ $$;
 ALTER FUNCTION public.lab_fun_consume_refresh_token(p_user_id uuid, p_current_sha256 bytea, p_new_sha256 bytea, p_new_expires_at timestamp with time zone, p_user_agent text, p_ip_addr text) OWNER TO postgres;
 
 --
--- Name: lab_fun_create_refresh_token(uuid, bytea, text, text, timestamp with time zone); Type: FUNCTION; Schema: public; Owner: postgres
+-- Name: lab_fun_create_refresh_token(uuid, bytea, text, text, timestamp with time zone, uuid); Type: FUNCTION; Schema: public; Owner: postgres
 --
 
-CREATE FUNCTION public.lab_fun_create_refresh_token(p_user_id uuid, p_token_sha256 bytea, p_user_agent text, p_ip_addr text, p_expires_at timestamp with time zone) RETURNS uuid
+CREATE OR REPLACE FUNCTION public.lab_fun_create_refresh_token(p_user_id uuid, p_token_sha256 bytea, p_user_agent text, p_ip_addr text, p_expires_at timestamp with time zone, p_request_id uuid) RETURNS uuid
     LANGUAGE plpgsql
     AS $$
 DECLARE
     v_token_id uuid := gen_random_uuid();
 BEGIN
-    INSERT INTO lab_refresh_tokens(token_id, user_id, token_sha256, user_agent, ip_addr, expires_at, revoked)
-    VALUES (v_token_id, p_user_id, p_token_sha256, p_user_agent, p_ip_addr, p_expires_at, false);
+    INSERT INTO lab_refresh_tokens(token_id, user_id, token_sha256, user_agent, ip_addr, expires_at, revoked, request_id)
+    VALUES (v_token_id, p_user_id, p_token_sha256, p_user_agent, p_ip_addr, p_expires_at, false, p_request_id);
     RETURN v_token_id;
 END;
 $$;
 
 
-ALTER FUNCTION public.lab_fun_create_refresh_token(p_user_id uuid, p_token_sha256 bytea, p_user_agent text, p_ip_addr text, p_expires_at timestamp with time zone) OWNER TO postgres;
+ALTER FUNCTION public.lab_fun_create_refresh_token(p_user_id uuid, p_token_sha256 bytea, p_user_agent text, p_ip_addr text, p_expires_at timestamp with time zone, p_request_id uuid) OWNER TO postgres;
 
 --
--- Name: lab_fun_deposit(uuid, uuid, double precision, text, text); Type: FUNCTION; Schema: public; Owner: postgres
+-- Name: lab_fun_deposit(uuid, uuid, numeric, text, text); Type: FUNCTION; Schema: public; Owner: postgres
 --
 
-CREATE FUNCTION public.lab_fun_deposit(p_user_id uuid, p_account_id uuid, p_amount double precision, p_description text, p_akun text) RETURNS TABLE(journal_id uuid, account_id uuid, balance_after double precision, trx_time timestamp with time zone, description text)
+CREATE OR REPLACE FUNCTION public.lab_fun_deposit(p_user_id uuid, p_account_id uuid, p_amount numeric, p_description text, p_akun text) RETURNS TABLE(journal_id uuid, account_id uuid, balance_after numeric, trx_time timestamp with time zone, description text)
     LANGUAGE plpgsql
     AS $$
 DECLARE
   v_owner uuid;
-  v_balance double precision;
+  v_balance numeric;
+  v_status text;
   v_journal uuid := gen_random_uuid();
   v_desc text := COALESCE(p_description, 'Setor tunai');
 BEGIN
-  IF p_amount IS NULL OR p_amount <= 0 THEN
+  IF p_amount IS NULL OR p_amount <= 0 OR p_amount <> round(p_amount, 2) THEN
     RAISE EXCEPTION 'AMOUNT_INVALID';
   END IF;
 
-  SELECT user_id INTO v_owner FROM lab_accounts WHERE id = p_account_id;
+  SELECT user_id, status INTO v_owner, v_status FROM lab_accounts WHERE id = p_account_id FOR UPDATE;
   IF v_owner IS NULL OR v_owner <> p_user_id THEN
     RAISE EXCEPTION 'ACCOUNT_NOT_OWNED';
   END IF;
+  PERFORM lab_fun_account_assert_status(v_status, false);
 
   -- tambah saldo
   UPDATE lab_accounts
@@ -161,7 +163,7 @@ END;
 $$;
 
 
-ALTER FUNCTION public.lab_fun_deposit(p_user_id uuid, p_account_id uuid, p_amount double precision, p_description text, p_akun text) OWNER TO postgres;
+ALTER FUNCTION public.lab_fun_deposit(p_user_id uuid, p_account_id uuid, p_amount numeric, p_description text, p_akun text) OWNER TO postgres;
 
 --
 -- Name: lab_fun_find_refresh_token(uuid, text); Type: FUNCTION; Schema: public; Owner: postgres
@@ -253,10 +255,10 @@ ALTER FUNCTION public.lab_fun_journal_guard() OWNER TO postgres;
 -- Name: lab_fun_list_accounts_by_user(uuid); Type: FUNCTION; Schema: public; Owner: postgres
 --
 
-CREATE FUNCTION public.lab_fun_list_accounts_by_user(p_user_id uuid) RETURNS TABLE(id uuid, account_no text, saldo numeric, created_at timestamp with time zone, updated_at timestamp with time zone)
+CREATE FUNCTION public.lab_fun_list_accounts_by_user(p_user_id uuid) RETURNS TABLE(id uuid, account_no text, saldo numeric, status text, created_at timestamp with time zone, updated_at timestamp with time zone)
     LANGUAGE sql STABLE
     AS $$
-SELECT a.id, a.account_no, a.saldo, a.created_at, a.updated_at
+SELECT a.id, a.account_no, a.saldo, a.status, a.created_at, a.updated_at
   FROM lab_accounts a
   WHERE a.user_id = p_user_id
   ORDER BY a.created_at ASC;
@@ -334,36 +336,36 @@ $$;
 ALTER FUNCTION public.journals_list_all(text, date, date, integer, integer) OWNER TO postgres;
 
 --
--- Name: lab_fun_open_account(uuid, text, double precision); Type: FUNCTION; Schema: public; Owner: postgres
+-- Name: lab_fun_open_account(uuid, text, uuid, numeric); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Pembukaan rekening selalu bersaldo nol. Setoran awal (opsional) hanya berupa transfer dari
+-- rekening lain milik user yang sama lewat lab_fun_transfer (cek status, saldo, limit & jurnal),
+-- dalam transaksi yang sama dengan pembuatan rekening.
 --
 
-CREATE FUNCTION public.lab_fun_open_account(p_user_id uuid, p_pin text, p_initial double precision) RETURNS TABLE(account_id uuid, account_no text)
+CREATE OR REPLACE FUNCTION public.lab_fun_open_account(p_user_id uuid, p_pin_hash text, p_fund_from uuid, p_fund_amount numeric) RETURNS TABLE(account_id uuid, account_no text, journal_id_credit uuid, journal_id_debit uuid)
     LANGUAGE plpgsql
     AS $_$
 DECLARE
   v_id uuid;
   v_no text;
   v_retry int := 0;
+  v_fund record;
 BEGIN
-  IF p_pin IS NULL OR length(p_pin) <> 6 OR p_pin !~ '^[0-9]{6}$' THEN
+  IF p_pin_hash IS NULL OR p_pin_hash !~ '^\$argon2id\$' THEN
     RAISE EXCEPTION 'PIN_INVALID';
   END IF;
 
-  IF p_initial IS NOT NULL AND p_initial < 0 THEN
-    RAISE EXCEPTION 'INITIAL_NEGATIVE';
+  IF (p_fund_from IS NULL) <> (p_fund_amount IS NULL) THEN
+    RAISE EXCEPTION 'FUNDING_INVALID';
   END IF;
 
   -- generate nomor unik & insert akun
   LOOP
     v_no := lab_fun_generate_account_no();
     BEGIN
-      INSERT INTO lab_accounts(user_id, account_no, pin, saldo)
-      VALUES (
-        p_user_id,
-        v_no,
-        crypt(p_pin, gen_salt('bf')),  -- <<< HASH PIN DI SINI
-        0
-      )
+      INSERT INTO lab_accounts(user_id, account_no, pin_hash, saldo)
+      VALUES (p_user_id, v_no, p_pin_hash, 0)
       RETURNING id INTO v_id;
 
       EXIT; -- sukses insert
@@ -375,41 +377,36 @@ BEGIN
     END;
   END LOOP;
 
-  -- setoran awal (opsional)
-  IF p_initial IS NOT NULL AND p_initial > 0 THEN
-    UPDATE lab_accounts
-       SET saldo = saldo + p_initial
-     WHERE id = v_id;
+  account_id := v_id;
+  account_no := v_no;
 
-    INSERT INTO lab_journals(user_id, account_id, debit, credit, description, balance_after)
-    VALUES (
-      p_user_id,
-      v_id,
-      p_initial,
-      0,
-      'Setoran awal',
-      (SELECT saldo FROM lab_accounts WHERE id = v_id)
-    );
+  -- setoran awal (opsional): pindah dana dari rekening sendiri
+  IF p_fund_from IS NOT NULL THEN
+    SELECT * INTO v_fund
+      FROM lab_fun_transfer(p_user_id, p_fund_from, v_id, p_fund_amount, 'Setoran awal rekening ' || v_no);
+    journal_id_credit := v_fund.journal_id_credit;
+    journal_id_debit  := v_fund.journal_id_debit;
   END IF;
 
-  RETURN QUERY SELECT v_id, v_no;
+  RETURN NEXT;
 END;
 $_$;
 
 
-ALTER FUNCTION public.lab_fun_open_account(p_user_id uuid, p_pin text, p_initial double precision) OWNER TO postgres;
+ALTER FUNCTION public.lab_fun_open_account(p_user_id uuid, p_pin_hash text, p_fund_from uuid, p_fund_amount numeric) OWNER TO postgres;
 
 --
--- Name: lab_fun_post_journal(uuid, uuid, double precision, double precision, text); Type: FUNCTION; Schema: public; Owner: postgres
+-- Name: lab_fun_post_journal(uuid, uuid, numeric, numeric, text); Type: FUNCTION; Schema: public; Owner: postgres
 --
 
-CREATE FUNCTION public.lab_fun_post_journal(p_user_id uuid, p_account_id uuid, p_debit double precision, p_credit double precision, p_description text) RETURNS uuid
+CREATE OR REPLACE FUNCTION public.lab_fun_post_journal(p_user_id uuid, p_account_id uuid, p_debit numeric, p_credit numeric, p_description text) RETURNS uuid
     LANGUAGE plpgsql
     AS $$
 DECLARE
   v_owner   uuid;
   v_balance numeric;
   v_newbal  numeric;
+  v_status  text;
   v_id      uuid := gen_random_uuid();
 BEGIN
   -- Normalisasi nilai NULL → 0
@@ -420,13 +417,16 @@ BEGIN
   IF p_debit < 0 OR p_credit < 0 THEN
     RAISE EXCEPTION 'AMOUNT_NEGATIVE';
   END IF;
+  IF p_debit <> round(p_debit, 2) OR p_credit <> round(p_credit, 2) THEN
+    RAISE EXCEPTION 'AMOUNT_INVALID';
+  END IF;
   IF (p_debit = 0 AND p_credit = 0) OR (p_debit > 0 AND p_credit > 0) THEN
     RAISE EXCEPTION 'AMOUNT_INVALID';
   END IF;
 
   -- Pastikan akun milik user & lock baris untuk update saldo
-  SELECT user_id, saldo
-    INTO v_owner, v_balance
+  SELECT user_id, saldo, status
+    INTO v_owner, v_balance, v_status
   FROM lab_accounts
   WHERE id = p_account_id
   FOR UPDATE;
@@ -437,13 +437,17 @@ BEGIN
   IF v_owner <> p_user_id THEN
     RAISE EXCEPTION 'ACCOUNT_NOT_OWNED';
   END IF;
+  PERFORM lab_fun_account_assert_status(v_status, p_credit > 0);
 
   -- Jika penarikan (credit), cek saldo cukup
   IF p_credit > 0 AND v_balance < p_credit THEN
     RAISE EXCEPTION 'INSUFFICIENT_FUNDS';
   END IF;
+  IF p_credit > 0 THEN
+    PERFORM lab_fun_limit_consume(p_user_id, p_account_id, 'cash_withdraw', p_credit);
+  END IF;
 
-  -- Hitung saldo baru (saldo numeric, argumen double → cast)
+  -- Hitung saldo baru (saldo & argumen sama-sama numeric)
   v_newbal := v_balance + (p_debit::numeric) - (p_credit::numeric);
 
   -- Update saldo akun
@@ -468,7 +472,7 @@ END;
 $$;
 
 
-ALTER FUNCTION public.lab_fun_post_journal(p_user_id uuid, p_account_id uuid, p_debit double precision, p_credit double precision, p_description text) OWNER TO postgres;
+ALTER FUNCTION public.lab_fun_post_journal(p_user_id uuid, p_account_id uuid, p_debit numeric, p_credit numeric, p_description text) OWNER TO postgres;
 
 --
 -- Name: lab_fun_register_user(text, text, text, text, text); Type: FUNCTION; Schema: public; Owner: postgres
@@ -551,10 +555,10 @@ END; $$;
 ALTER FUNCTION public.lab_fun_touch_updated_at() OWNER TO postgres;
 
 --
--- Name: lab_fun_transfer(uuid, uuid, uuid, double precision, text); Type: FUNCTION; Schema: public; Owner: postgres
+-- Name: lab_fun_transfer(uuid, uuid, uuid, numeric, text); Type: FUNCTION; Schema: public; Owner: postgres
 --
 
-CREATE FUNCTION public.lab_fun_transfer(p_user_id uuid, p_from_account uuid, p_to_account uuid, p_amount double precision, p_description text) RETURNS TABLE(journal_id_credit uuid, journal_id_debit uuid)
+CREATE OR REPLACE FUNCTION public.lab_fun_transfer(p_user_id uuid, p_from_account uuid, p_to_account uuid, p_amount numeric, p_description text) RETURNS TABLE(journal_id_credit uuid, journal_id_debit uuid)
     LANGUAGE plpgsql
     AS $$
 DECLARE
@@ -564,11 +568,13 @@ DECLARE
   v_bal_to        numeric;
   v_new_from      numeric;
   v_new_to        numeric;
+  v_status_from   text;
+  v_status_to     text;
   v_j_credit      uuid := gen_random_uuid(); -- jurnal keluar (credit) dari rekening sumber
   v_j_debit       uuid := gen_random_uuid(); -- jurnal masuk (debit) ke rekening tujuan
 BEGIN
   -- Validasi nominal
-  IF p_amount IS NULL OR p_amount <= 0 THEN
+  IF p_amount IS NULL OR p_amount <= 0 OR p_amount <> round(p_amount, 2) THEN
     RAISE EXCEPTION 'AMOUNT_INVALID';
   END IF;
 
@@ -578,8 +584,8 @@ BEGIN
   END IF;
 
   -- Ambil & lock rekening sumber
-  SELECT user_id, saldo
-    INTO v_owner_from, v_bal_from
+  SELECT user_id, saldo, status
+    INTO v_owner_from, v_bal_from, v_status_from
   FROM lab_accounts
   WHERE id = p_from_account
   FOR UPDATE;
@@ -589,21 +595,24 @@ BEGIN
   IF v_owner_from <> p_user_id THEN
     RAISE EXCEPTION 'ACCOUNT_NOT_OWNED';
   END IF;
+  PERFORM lab_fun_account_assert_status(v_status_from, true);
 
   -- Cek saldo cukup
   IF v_bal_from < p_amount THEN
     RAISE EXCEPTION 'INSUFFICIENT_FUNDS';
   END IF;
+  PERFORM lab_fun_limit_consume(p_user_id, p_from_account, 'transfer_out', p_amount);
 
   -- Ambil & lock rekening tujuan
-  SELECT user_id, saldo
-    INTO v_owner_to, v_bal_to
+  SELECT user_id, saldo, status
+    INTO v_owner_to, v_bal_to, v_status_to
   FROM lab_accounts
   WHERE id = p_to_account
   FOR UPDATE;
   IF v_owner_to IS NULL THEN
     RAISE EXCEPTION 'ACCOUNT_TO_NOT_FOUND';
   END IF;
+  PERFORM lab_fun_account_assert_status(v_status_to, false);
 
   -- Hitung saldo baru
   v_new_from := v_bal_from - (p_amount::numeric);
@@ -643,19 +652,21 @@ END;
 $$;
 
 
-ALTER FUNCTION public.lab_fun_transfer(p_user_id uuid, p_from_account uuid, p_to_account uuid, p_amount double precision, p_description text) OWNER TO postgres;
+ALTER FUNCTION public.lab_fun_transfer(p_user_id uuid, p_from_account uuid, p_to_account uuid, p_amount numeric, p_description text) OWNER TO postgres;
 
 --
 -- Name: lab_fun_update_account_pin(uuid, uuid, text); Type: FUNCTION; Schema: public; Owner: postgres
 --
+-- Ganti hash PIN; sekaligus reset counter salah PIN & kunci.
+--
 
-CREATE FUNCTION public.lab_fun_update_account_pin(p_user_id uuid, p_account_id uuid, p_new_pin text) RETURNS boolean
+CREATE FUNCTION public.lab_fun_update_account_pin(p_user_id uuid, p_account_id uuid, p_new_pin_hash text) RETURNS boolean
     LANGUAGE plpgsql
     AS $_$
 DECLARE
   v_owner uuid;
 BEGIN
-  IF p_new_pin IS NULL OR length(p_new_pin) <> 6 OR p_new_pin !~ '^[0-9]{6}$' THEN
+  IF p_new_pin_hash IS NULL OR p_new_pin_hash !~ '^\$argon2id\$' THEN
     RETURN false;
   END IF;
 
@@ -665,14 +676,18 @@ BEGIN
   END IF;
 
   UPDATE lab_accounts
-     SET pin = p_new_pin
+     SET pin_hash = p_new_pin_hash,
+         pin_failed_attempts = 0,
+         pin_locked_until = NULL,
+         pin_changed_at = now(),
+         updated_at = now()
    WHERE id = p_account_id;
 
   RETURN FOUND;
 END; $_$;
 
 
-ALTER FUNCTION public.lab_fun_update_account_pin(p_user_id uuid, p_account_id uuid, p_new_pin text) OWNER TO postgres;
+ALTER FUNCTION public.lab_fun_update_account_pin(p_user_id uuid, p_account_id uuid, p_new_pin_hash text) OWNER TO postgres;
 
 --
 -- Name: lab_fun_upsert_profile(uuid, text, text, text, date, text, text, text, text); Type: FUNCTION; Schema: public; Owner: postgres
@@ -702,58 +717,37 @@ END; $$;
 ALTER FUNCTION public.lab_fun_upsert_profile(p_user_id uuid, p_ktp_nik text, p_nama_lengkap text, p_tempat_lahir text, p_tanggal_lahir date, p_jenis_kelamin text, p_no_telepon text, p_alamat text, p_ibu_kandung text) OWNER TO postgres;
 
 --
--- Name: lab_fun_verify_account_pin(uuid, uuid, text); Type: FUNCTION; Schema: public; Owner: postgres
---
-
-CREATE FUNCTION public.lab_fun_verify_account_pin(p_user_id uuid, p_account_id uuid, p_pin text) RETURNS boolean
-    LANGUAGE plpgsql
-    AS $$
-DECLARE
-  v_owner uuid;
-  v_hash  text;
-BEGIN
-  SELECT user_id, pin
-    INTO v_owner, v_hash
-    FROM lab_accounts
-   WHERE (id = p_account_id or account_no = p_account_id::varchar);
-
-  IF v_owner IS NULL OR v_owner <> p_user_id THEN
-    RETURN FALSE;
-  END IF;
-
-  RETURN crypt(p_pin, v_hash) = v_hash;
-END;
-$$;
-
-
-ALTER FUNCTION public.lab_fun_verify_account_pin(p_user_id uuid, p_account_id uuid, p_pin text) OWNER TO postgres;
-
---
--- Name: lab_fun_withdraw(uuid, uuid, double precision, text, text); Type: FUNCTION; Schema: public; Owner: postgres
+-- Name: lab_fun_withdraw(uuid, uuid, numeric, text, text, text); Type: FUNCTION; Schema: public; Owner: postgres
 --
 
-CREATE FUNCTION public.lab_fun_withdraw(p_user_id uuid, p_account_id uuid, p_amount double precision, p_description text, p_akun text) RETURNS TABLE(journal_id uuid, account_id uuid, balance_after double precision, trx_time timestamp with time zone, description text)
+CREATE OR REPLACE FUNCTION public.lab_fun_withdraw(p_user_id uuid, p_account_id uuid, p_amount numeric, p_description text, p_akun text, p_txn_type text) RETURNS TABLE(journal_id uuid, account_id uuid, balance_after numeric, trx_time timestamp with time zone, description text)
     LANGUAGE plpgsql
     AS $$
 DECLARE
   v_owner uuid;
-  v_balance double precision;
+  v_balance numeric;
+  v_status text;
   v_journal uuid := gen_random_uuid();
   v_desc text := COALESCE(p_description, 'Tarik tunai');
 BEGIN
-  IF p_amount IS NULL OR p_amount <= 0 THEN
+  IF p_amount IS NULL OR p_amount <= 0 OR p_amount <> round(p_amount, 2) THEN
     RAISE EXCEPTION 'AMOUNT_INVALID';
   END IF;
+  IF p_txn_type IS NULL OR p_txn_type NOT IN ('cash_withdraw', 'ppob') THEN
+    RAISE EXCEPTION 'TXN_TYPE_INVALID';
+  END IF;
 
   SELECT user_id INTO v_owner FROM lab_accounts WHERE id = p_account_id;
   IF v_owner IS NULL OR v_owner <> p_user_id THEN
     RAISE EXCEPTION 'ACCOUNT_NOT_OWNED';
   END IF;
 
-  SELECT saldo INTO v_balance FROM lab_accounts WHERE id = p_account_id FOR UPDATE;
+  SELECT saldo, status INTO v_balance, v_status FROM lab_accounts WHERE id = p_account_id FOR UPDATE;
+  PERFORM lab_fun_account_assert_status(v_status, true);
   IF v_balance < p_amount THEN
     RAISE EXCEPTION 'INSUFFICIENT_FUNDS';
   END IF;
+  PERFORM lab_fun_limit_consume(p_user_id, p_account_id, p_txn_type, p_amount);
 
   -- kurangi saldo
   UPDATE lab_accounts
@@ -774,7 +768,7 @@ END;
 $$;
 
 
-ALTER FUNCTION public.lab_fun_withdraw(p_user_id uuid, p_account_id uuid, p_amount double precision, p_description text, p_akun text) OWNER TO postgres;
+ALTER FUNCTION public.lab_fun_withdraw(p_user_id uuid, p_account_id uuid, p_amount numeric, p_description text, p_akun text, p_txn_type text) OWNER TO postgres;
 
 --
 -- Name: corp_sp_update_widhraw_journal(bigint, character varying, bigint, character varying, character varying, character varying, text, text); Type: FUNCTION; Schema: public; Owner: postgres
@@ -834,13 +828,13 @@ SET default_table_access_method = heap;
 -- =========================================================
 -- Transfer by account_no (tanpa mengubah fungsi lama)
 -- =========================================================
--- DROP FUNCTION public.lab_fun_transfer_by_no(uuid, text, text, double precision, text, text);
+-- DROP FUNCTION public.lab_fun_transfer_by_no(uuid, text, text, numeric, text, text);
 
 CREATE OR REPLACE FUNCTION public.lab_fun_transfer_by_no(
   p_user_id uuid,
   p_from_account_no text,
   p_to_account_no text,
-  p_amount double precision,
+  p_amount numeric,
   p_description text,
   p_akun text
 )
@@ -865,16 +859,19 @@ DECLARE
   v_new_from    numeric;
   v_new_to      numeric;
 
+  v_status_from text;
+  v_status_to   text;
+
   v_j_credit    uuid := gen_random_uuid();
   v_j_debit     uuid := gen_random_uuid();
 BEGIN
-  IF p_amount IS NULL OR p_amount <= 0 THEN
+  IF p_amount IS NULL OR p_amount <= 0 OR p_amount <> round(p_amount, 2) THEN
     RAISE EXCEPTION 'AMOUNT_INVALID';
   END IF;
 
   -- Resolve FROM account
-  SELECT id, user_id, saldo
-    INTO v_from_id, v_owner_from, v_bal_from
+  SELECT id, user_id, saldo, status
+    INTO v_from_id, v_owner_from, v_bal_from, v_status_from
   FROM lab_accounts
   WHERE account_no = p_from_account_no
   FOR UPDATE;
@@ -887,9 +884,11 @@ BEGIN
     RAISE EXCEPTION 'ACCOUNT_NOT_OWNED';
   END IF;
 
+  PERFORM lab_fun_account_assert_status(v_status_from, true);
+
   -- Resolve TO account
-  SELECT id, user_id, saldo
-    INTO v_to_id, v_owner_to, v_bal_to
+  SELECT id, user_id, saldo, status
+    INTO v_to_id, v_owner_to, v_bal_to, v_status_to
   FROM lab_accounts
   WHERE account_no = p_to_account_no
   FOR UPDATE;
@@ -898,6 +897,8 @@ BEGIN
     RAISE EXCEPTION 'ACCOUNT_TO_NOT_FOUND';
   END IF;
 
+  PERFORM lab_fun_account_assert_status(v_status_to, false);
+
   IF v_from_id = v_to_id THEN
     RAISE EXCEPTION 'SAME_ACCOUNT';
   END IF;
@@ -906,6 +907,9 @@ BEGIN
     RAISE EXCEPTION 'INSUFFICIENT_FUNDS';
   END IF;
 
+  PERFORM lab_fun_limit_consume(p_user_id, v_from_id, 'transfer_out', p_amount);
+  PERFORM lab_fun_payee_cooling_consume(p_user_id, v_owner_to, p_to_account_no, p_amount);
+
   -- Hitung saldo baru
   v_new_from := v_bal_from - p_amount::numeric;
   v_new_to   := v_bal_to   + p_amount::numeric;
@@ -924,6 +928,9 @@ BEGIN
   VALUES (v_j_debit, v_owner_to, v_to_id, p_amount::numeric, 0,
           COALESCE(p_description,'transfer in'), v_new_to, now());
 
+  -- Catat penerima (dalam transaksi yang sama dengan transfer)
+  PERFORM lab_fun_record_counterparty(p_user_id, p_to_account_no);
+
   -- Ambil token FROM & TO
   SELECT lu.fcm_token
     INTO token_from
@@ -942,6 +949,9 @@ BEGIN
 END;
 $function$;
 
+
+ALTER FUNCTION public.lab_fun_transfer_by_no(p_user_id uuid, p_from_account_no text, p_to_account_no text, p_amount numeric, p_description text, p_akun text) OWNER TO postgres;
+
 --
 -- Name: lab_accounts; Type: TABLE; Schema: public; Owner: postgres
 --
@@ -950,10 +960,21 @@ CREATE TABLE public.lab_accounts (
     id uuid DEFAULT gen_random_uuid() NOT NULL,
     user_id uuid NOT NULL,
     account_no character varying(14) NOT NULL,
-    pin character(1000) NOT NULL,
+    pin_hash text,
     saldo numeric(20,2) DEFAULT 0 NOT NULL,
     created_at timestamp with time zone DEFAULT now() NOT NULL,
-    updated_at timestamp with time zone DEFAULT now() NOT NULL
+    updated_at timestamp with time zone DEFAULT now() NOT NULL,
+    status text DEFAULT 'active'::text NOT NULL,
+    status_reason text,
+    status_changed_by text,
+    status_changed_at timestamp with time zone,
+    closed_at timestamp with time zone,
+    pin_failed_attempts integer DEFAULT 0 NOT NULL,
+    pin_locked_until timestamp with time zone,
+    pin_changed_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT lab_accounts_pin_hash_check CHECK ((pin_hash ~ '^\$(argon2id|2[aby])\$'::text)),
+    CONSTRAINT lab_accounts_status_changed_by_check CHECK ((status_changed_by = ANY (ARRAY['owner'::text, 'admin'::text, 'system'::text]))),
+    CONSTRAINT lab_accounts_status_check CHECK ((status = ANY (ARRAY['active'::text, 'frozen'::text, 'dormant'::text, 'closed'::text])))
 );
 
 
@@ -971,7 +992,8 @@ CREATE TABLE public.lab_audit_logs (
     target text,
     meta jsonb,
     ip_addr text,
-    user_agent text
+    user_agent text,
+    request_id uuid
 );
 
 
@@ -1032,7 +1054,10 @@ CREATE TABLE public.lab_refresh_tokens (
     rotated_from uuid,
     token_id uuid NOT NULL,
     revoked boolean DEFAULT false NOT NULL,
-    created_at timestamp with time zone DEFAULT now() NOT NULL
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    access_jti uuid,
+    access_expires_at timestamp with time zone,
+    request_id uuid
 );
 
 
@@ -1062,7 +1087,7 @@ CREATE TABLE public.lab_users (
     is_active boolean DEFAULT true NOT NULL,
     created_at timestamp with time zone DEFAULT now() NOT NULL,
     updated_at timestamp with time zone DEFAULT now() NOT NULL,
-    CONSTRAINT lab_users_role_check CHECK ((role = ANY (ARRAY['admin'::text, 'user'::text])))
+    tier text DEFAULT 'basic'::text NOT NULL
 );
 
 
@@ -1396,6 +1421,2802 @@ $$;
 ALTER FUNCTION public.lab_get_selected_bank_account(p_user_id uuid) OWNER TO postgres;
 
 
+--
+-- Name: lab_user_mfa; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.lab_user_mfa (
+    user_id uuid NOT NULL,
+    secret text NOT NULL,
+    enabled boolean DEFAULT false NOT NULL,
+    last_used_step bigint,
+    confirmed_at timestamp with time zone,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    updated_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+
+ALTER TABLE public.lab_user_mfa OWNER TO postgres;
+
+ALTER TABLE ONLY public.lab_user_mfa
+    ADD CONSTRAINT lab_user_mfa_pkey PRIMARY KEY (user_id);
+
+ALTER TABLE ONLY public.lab_user_mfa
+    ADD CONSTRAINT lab_user_mfa_user_id_fkey FOREIGN KEY (user_id) REFERENCES public.lab_users(id) ON DELETE CASCADE;
+
+CREATE TRIGGER lab_user_mfa_touch BEFORE UPDATE ON public.lab_user_mfa FOR EACH ROW EXECUTE FUNCTION public.lab_touch_updated_at();
+
+--
+-- Name: lab_mfa_recovery_codes; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.lab_mfa_recovery_codes (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    user_id uuid NOT NULL,
+    code_sha256 bytea NOT NULL,
+    used_at timestamp with time zone,
+    created_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+
+ALTER TABLE public.lab_mfa_recovery_codes OWNER TO postgres;
+
+ALTER TABLE ONLY public.lab_mfa_recovery_codes
+    ADD CONSTRAINT lab_mfa_recovery_codes_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.lab_mfa_recovery_codes
+    ADD CONSTRAINT lab_mfa_recovery_codes_user_id_fkey FOREIGN KEY (user_id) REFERENCES public.lab_users(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_lab_mfa_recovery_codes_user ON public.lab_mfa_recovery_codes USING btree (user_id) WHERE (used_at IS NULL);
+
+--
+-- Name: lab_fun_mfa_enroll(uuid, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_mfa_enroll(p_user_id uuid, p_secret text) RETURNS void
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  IF EXISTS (SELECT 1 FROM lab_user_mfa WHERE user_id = p_user_id AND enabled = true) THEN
+    RAISE EXCEPTION 'MFA_ALREADY_ENABLED';
+  END IF;
+
+  -- enrollment ulang (belum dikonfirmasi) menimpa secret lama
+  INSERT INTO lab_user_mfa(user_id, secret, enabled)
+  VALUES (p_user_id, p_secret, false)
+  ON CONFLICT (user_id) DO UPDATE SET
+    secret         = EXCLUDED.secret,
+    enabled        = false,
+    last_used_step = NULL,
+    confirmed_at   = NULL;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_mfa_enroll(p_user_id uuid, p_secret text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_mfa_get(uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_mfa_get(p_user_id uuid) RETURNS TABLE(secret text, enabled boolean, last_used_step bigint)
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT m.secret, m.enabled, m.last_used_step
+  FROM lab_user_mfa m
+  WHERE m.user_id = p_user_id;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_mfa_get(p_user_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_mfa_mark_step(uuid, bigint); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Menandai time step TOTP yang sudah dipakai; FALSE jika step sama/lebih lama (replay)
+CREATE OR REPLACE FUNCTION public.lab_fun_mfa_mark_step(p_user_id uuid, p_step bigint) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  UPDATE lab_user_mfa
+     SET last_used_step = p_step
+   WHERE user_id = p_user_id
+     AND (last_used_step IS NULL OR last_used_step < p_step);
+
+  RETURN FOUND;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_mfa_mark_step(p_user_id uuid, p_step bigint) OWNER TO postgres;
+
+--
+-- Name: lab_fun_mfa_confirm(uuid, bytea[]); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_mfa_confirm(p_user_id uuid, p_recovery_sha256 bytea[]) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  UPDATE lab_user_mfa
+     SET enabled = true,
+         confirmed_at = now()
+   WHERE user_id = p_user_id
+     AND enabled = false;
+
+  IF NOT FOUND THEN
+    RETURN false;
+  END IF;
+
+  DELETE FROM lab_mfa_recovery_codes WHERE user_id = p_user_id;
+  INSERT INTO lab_mfa_recovery_codes(user_id, code_sha256)
+  SELECT p_user_id, c FROM unnest(p_recovery_sha256) AS c;
+
+  RETURN true;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_mfa_confirm(p_user_id uuid, p_recovery_sha256 bytea[]) OWNER TO postgres;
+
+--
+-- Name: lab_fun_mfa_use_recovery_code(uuid, bytea); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_mfa_use_recovery_code(p_user_id uuid, p_code_sha256 bytea) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  UPDATE lab_mfa_recovery_codes
+     SET used_at = now()
+   WHERE id = (
+     SELECT id FROM lab_mfa_recovery_codes
+      WHERE user_id = p_user_id
+        AND code_sha256 = p_code_sha256
+        AND used_at IS NULL
+      LIMIT 1
+      FOR UPDATE
+   );
+
+  RETURN FOUND;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_mfa_use_recovery_code(p_user_id uuid, p_code_sha256 bytea) OWNER TO postgres;
+
+--
+-- Name: lab_fun_mfa_reset(uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_mfa_reset(p_user_id uuid) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  DELETE FROM lab_mfa_recovery_codes WHERE user_id = p_user_id;
+  DELETE FROM lab_user_mfa WHERE user_id = p_user_id;
+
+  RETURN FOUND;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_mfa_reset(p_user_id uuid) OWNER TO postgres;
+
+
+--
+-- Name: lab_fun_get_user_by_id(uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_get_user_by_id(p_user_id uuid) RETURNS TABLE(user_id uuid, email text, role text, is_active boolean)
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT id, email::text, role, is_active
+  FROM lab_users
+  WHERE id = p_user_id;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_get_user_by_id(p_user_id uuid) OWNER TO postgres;
+
+
+--
+-- Name: lab_password_reset_tokens; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE public.lab_password_reset_tokens (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    user_id uuid NOT NULL,
+    token_sha256 bytea NOT NULL,
+    expires_at timestamp with time zone NOT NULL,
+    used_at timestamp with time zone,
+    created_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+
+ALTER TABLE public.lab_password_reset_tokens OWNER TO postgres;
+
+ALTER TABLE ONLY public.lab_password_reset_tokens
+    ADD CONSTRAINT lab_password_reset_tokens_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.lab_password_reset_tokens
+    ADD CONSTRAINT lab_password_reset_tokens_user_id_fkey FOREIGN KEY (user_id) REFERENCES public.lab_users(id) ON DELETE CASCADE;
+
+CREATE UNIQUE INDEX idx_lab_password_reset_tokens_sha256 ON public.lab_password_reset_tokens USING btree (token_sha256);
+
+--
+-- Name: lab_fun_create_password_reset(text, bytea, timestamp with time zone); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- NULL jika email tidak terdaftar / non-aktif (caller tetap merespons seragam)
+CREATE OR REPLACE FUNCTION public.lab_fun_create_password_reset(p_email text, p_token_sha256 bytea, p_expires_at timestamp with time zone) RETURNS uuid
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_user_id uuid;
+BEGIN
+  SELECT id INTO v_user_id
+    FROM lab_users
+   WHERE email = p_email
+     AND is_active = true;
+
+  IF v_user_id IS NULL THEN
+    RETURN NULL;
+  END IF;
+
+  -- hanya satu token aktif per user
+  UPDATE lab_password_reset_tokens
+     SET used_at = now()
+   WHERE user_id = v_user_id
+     AND used_at IS NULL;
+
+  INSERT INTO lab_password_reset_tokens(user_id, token_sha256, expires_at)
+  VALUES (v_user_id, p_token_sha256, p_expires_at);
+
+  RETURN v_user_id;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_create_password_reset(p_email text, p_token_sha256 bytea, p_expires_at timestamp with time zone) OWNER TO postgres;
+
+--
+-- Name: lab_fun_consume_password_reset(bytea, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_consume_password_reset(p_token_sha256 bytea, p_new_password_hash text) RETURNS uuid
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_id      uuid;
+  v_user_id uuid;
+BEGIN
+  SELECT id, user_id INTO v_id, v_user_id
+    FROM lab_password_reset_tokens
+   WHERE token_sha256 = p_token_sha256
+     AND used_at IS NULL
+     AND now() < expires_at
+   FOR UPDATE;
+
+  IF v_id IS NULL THEN
+    RAISE EXCEPTION 'RESET_TOKEN_INVALID';
+  END IF;
+
+  UPDATE lab_password_reset_tokens SET used_at = now() WHERE id = v_id;
+
+  UPDATE lab_users SET password_hash = p_new_password_hash WHERE id = v_user_id;
+
+  -- semua sesi lama dicabut
+  UPDATE lab_refresh_tokens
+     SET revoked = true,
+         revoked_at = now()
+   WHERE user_id = v_user_id
+     AND revoked = false;
+
+  RETURN v_user_id;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_consume_password_reset(p_token_sha256 bytea, p_new_password_hash text) OWNER TO postgres;
+
+
+CREATE INDEX IF NOT EXISTS idx_lab_refresh_tokens_rotated_from ON public.lab_refresh_tokens USING btree (rotated_from);
+
+--
+-- Name: lab_fun_set_refresh_access_jti(uuid, uuid, timestamp with time zone); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_set_refresh_access_jti(p_token_id uuid, p_jti uuid, p_expires_at timestamp with time zone) RETURNS void
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  UPDATE lab_refresh_tokens
+     SET access_jti = p_jti,
+         access_expires_at = p_expires_at
+   WHERE token_id = p_token_id;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_set_refresh_access_jti(p_token_id uuid, p_jti uuid, p_expires_at timestamp with time zone) OWNER TO postgres;
+
+--
+-- Name: lab_fun_revoke_refresh_family(uuid, bytea); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Dipanggil saat refresh token ditolak. Jika token tsb sudah pernah di-rotate (reuse),
+-- seluruh rantai rotasi dicabut + access token yang masih hidup di-blacklist.
+-- Tidak mengembalikan baris jika bukan kasus reuse.
+CREATE OR REPLACE FUNCTION public.lab_fun_revoke_refresh_family(p_user_id uuid, p_token_sha256 bytea) RETURNS TABLE(family_root uuid, revoked_refresh integer, revoked_access integer)
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_token_id uuid;
+  v_root     uuid;
+  v_family   uuid[];
+  v_rt       integer;
+  v_at       integer;
+BEGIN
+  SELECT t.token_id INTO v_token_id
+    FROM lab_refresh_tokens t
+   WHERE t.user_id = p_user_id
+     AND t.token_sha256 = p_token_sha256
+     AND t.revoked = true
+     AND EXISTS (SELECT 1 FROM lab_refresh_tokens c WHERE c.rotated_from = t.token_id)
+   ORDER BY t.created_at DESC
+   LIMIT 1;
+
+  IF v_token_id IS NULL THEN
+    RETURN;
+  END IF;
+
+  -- naik ke akar rantai rotasi
+  WITH RECURSIVE up AS (
+    SELECT r.token_id, r.rotated_from, 0 AS depth
+      FROM lab_refresh_tokens r
+     WHERE r.token_id = v_token_id
+    UNION ALL
+    SELECT p.token_id, p.rotated_from, up.depth + 1
+      FROM lab_refresh_tokens p
+      JOIN up ON p.token_id = up.rotated_from
+  )
+  SELECT up.token_id INTO v_root FROM up ORDER BY up.depth DESC LIMIT 1;
+
+  -- turun ke semua keturunan
+  WITH RECURSIVE fam AS (
+    SELECT r.token_id FROM lab_refresh_tokens r WHERE r.token_id = v_root
+    UNION ALL
+    SELECT c.token_id
+      FROM lab_refresh_tokens c
+      JOIN fam ON c.rotated_from = fam.token_id
+  )
+  SELECT array_agg(fam.token_id) INTO v_family FROM fam;
+
+  UPDATE lab_refresh_tokens
+     SET revoked = true,
+         revoked_at = COALESCE(revoked_at, now())
+   WHERE token_id = ANY(v_family)
+     AND revoked = false;
+  GET DIAGNOSTICS v_rt = ROW_COUNT;
+
+  INSERT INTO lab_revoked_access_tokens(jti, expires_at)
+  SELECT r.access_jti, r.access_expires_at
+    FROM lab_refresh_tokens r
+   WHERE r.token_id = ANY(v_family)
+     AND r.access_jti IS NOT NULL
+     AND r.access_expires_at > now()
+  ON CONFLICT (jti) DO NOTHING;
+  GET DIAGNOSTICS v_at = ROW_COUNT;
+
+  family_root     := v_root;
+  revoked_refresh := v_rt;
+  revoked_access  := v_at;
+  RETURN NEXT;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_revoke_refresh_family(p_user_id uuid, p_token_sha256 bytea) OWNER TO postgres;
+
+
+--
+-- Name: lab_fun_list_sessions(uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Satu sesi = satu refresh token aktif; session_started_at diambil dari akar rantai rotasi
+CREATE OR REPLACE FUNCTION public.lab_fun_list_sessions(p_user_id uuid) RETURNS TABLE(token_id uuid, session_started_at timestamp with time zone, last_rotated_at timestamp with time zone, user_agent text, ip_addr text, expires_at timestamp with time zone, access_jti uuid)
+    LANGUAGE sql STABLE
+    AS $$
+  WITH RECURSIVE chain AS (
+    SELECT t.token_id AS leaf_id, t.token_id, t.rotated_from, t.created_at
+      FROM lab_refresh_tokens t
+     WHERE t.user_id = p_user_id
+       AND t.revoked = false
+       AND now() < t.expires_at
+    UNION ALL
+    SELECT chain.leaf_id, p.token_id, p.rotated_from, p.created_at
+      FROM lab_refresh_tokens p
+      JOIN chain ON p.token_id = chain.rotated_from
+  )
+  SELECT t.token_id,
+         (SELECT min(c.created_at) FROM chain c WHERE c.leaf_id = t.token_id),
+         CASE WHEN t.rotated_from IS NULL THEN NULL ELSE t.created_at END,
+         t.user_agent,
+         t.ip_addr,
+         t.expires_at,
+         t.access_jti
+  FROM lab_refresh_tokens t
+  WHERE t.user_id = p_user_id
+    AND t.revoked = false
+    AND now() < t.expires_at
+  ORDER BY t.created_at DESC;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_list_sessions(p_user_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_revoke_user_session(uuid, uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Cabut satu sesi milik user (FALSE jika token bukan milik user / sudah dicabut)
+CREATE OR REPLACE FUNCTION public.lab_fun_revoke_user_session(p_user_id uuid, p_token_id uuid) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_jti uuid;
+  v_exp timestamp with time zone;
+BEGIN
+  UPDATE lab_refresh_tokens
+     SET revoked = true,
+         revoked_at = now()
+   WHERE token_id = p_token_id
+     AND user_id = p_user_id
+     AND revoked = false
+  RETURNING access_jti, access_expires_at INTO v_jti, v_exp;
+
+  IF NOT FOUND THEN
+    RETURN false;
+  END IF;
+
+  IF v_jti IS NOT NULL AND v_exp > now() THEN
+    PERFORM lab_fun_revoke_access_token(v_jti, v_exp);
+  END IF;
+
+  RETURN true;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_revoke_user_session(p_user_id uuid, p_token_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_revoke_all_sessions(uuid, uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Logout di semua device; p_except_token_id (opsional) dibiarkan tetap aktif
+CREATE OR REPLACE FUNCTION public.lab_fun_revoke_all_sessions(p_user_id uuid, p_except_token_id uuid) RETURNS integer
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_count integer;
+BEGIN
+  WITH revoked AS (
+    UPDATE lab_refresh_tokens
+       SET revoked = true,
+           revoked_at = now()
+     WHERE user_id = p_user_id
+       AND revoked = false
+       AND (p_except_token_id IS NULL OR token_id <> p_except_token_id)
+    RETURNING access_jti, access_expires_at
+  ), blacklisted AS (
+    INSERT INTO lab_revoked_access_tokens(jti, expires_at)
+    SELECT access_jti, access_expires_at
+      FROM revoked
+     WHERE access_jti IS NOT NULL
+       AND access_expires_at > now()
+    ON CONFLICT (jti) DO NOTHING
+  )
+  SELECT count(*) INTO v_count FROM revoked;
+
+  RETURN v_count;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_revoke_all_sessions(p_user_id uuid, p_except_token_id uuid) OWNER TO postgres;
+
+
+CREATE INDEX IF NOT EXISTS idx_audit_request_id ON public.lab_audit_logs USING btree (request_id);
+
+--
+-- Name: lab_fun_rotate_refresh_token(bytea, bytea, timestamp with time zone, text, text, uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_rotate_refresh_token(p_current_sha256 bytea, p_new_sha256 bytea, p_new_expires_at timestamp with time zone, p_user_agent text, p_ip_addr text, p_request_id uuid) RETURNS TABLE(new_token_id uuid, user_id uuid, role text, is_active boolean)
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_old_token_id uuid;
+  v_user_id      uuid;
+  v_role         text;
+  v_active       boolean;
+  v_new_token_id uuid := gen_random_uuid();
+BEGIN
+  SELECT t.token_id, t.user_id INTO v_old_token_id, v_user_id
+    FROM lab_refresh_tokens t
+   WHERE t.token_sha256 = p_current_sha256
+     AND t.revoked = false
+     AND now() < t.expires_at
+   FOR UPDATE;
+
+  IF v_old_token_id IS NULL THEN
+    RAISE EXCEPTION 'REFRESH_INVALID_OR_EXPIRED';
+  END IF;
+
+  SELECT u.role, u.is_active INTO v_role, v_active
+    FROM lab_users u
+   WHERE u.id = v_user_id;
+
+  IF NOT COALESCE(v_active, false) THEN
+    -- user dinonaktifkan: cabut semua refresh token & blacklist access token yang masih hidup
+    PERFORM lab_fun_revoke_all_sessions(v_user_id, NULL);
+
+    new_token_id := NULL;
+    user_id      := v_user_id;
+    role         := v_role;
+    is_active    := false;
+    RETURN NEXT;
+    RETURN;
+  END IF;
+
+  UPDATE lab_refresh_tokens
+     SET revoked = true,
+         revoked_at = COALESCE(revoked_at, now())
+   WHERE token_id = v_old_token_id;
+
+  INSERT INTO lab_refresh_tokens(token_id, user_id, token_sha256, user_agent, ip_addr, expires_at, revoked, rotated_from, request_id)
+  VALUES (v_new_token_id, v_user_id, p_new_sha256, p_user_agent, p_ip_addr, p_new_expires_at, false, v_old_token_id, p_request_id);
+
+  new_token_id := v_new_token_id;
+  user_id      := v_user_id;
+  role         := v_role;
+  is_active    := true;
+  RETURN NEXT;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_rotate_refresh_token(p_current_sha256 bytea, p_new_sha256 bytea, p_new_expires_at timestamp with time zone, p_user_agent text, p_ip_addr text, p_request_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_refresh_token_owner(bytea); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Pemilik refresh token (termasuk yang sudah dicabut/expired), untuk deteksi reuse
+CREATE OR REPLACE FUNCTION public.lab_fun_refresh_token_owner(p_token_sha256 bytea) RETURNS uuid
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT t.user_id
+    FROM lab_refresh_tokens t
+   WHERE t.token_sha256 = p_token_sha256
+   ORDER BY t.created_at DESC
+   LIMIT 1;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_refresh_token_owner(p_token_sha256 bytea) OWNER TO postgres;
+
+
+--
+-- Name: lab_roles; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_roles (
+    name text NOT NULL,
+    description text,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT lab_roles_pkey PRIMARY KEY (name)
+);
+
+
+ALTER TABLE public.lab_roles OWNER TO postgres;
+
+--
+-- Name: lab_permissions; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_permissions (
+    name text NOT NULL,
+    description text,
+    CONSTRAINT lab_permissions_pkey PRIMARY KEY (name)
+);
+
+
+ALTER TABLE public.lab_permissions OWNER TO postgres;
+
+--
+-- Name: lab_role_permissions; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_role_permissions (
+    role text NOT NULL,
+    permission text NOT NULL,
+    CONSTRAINT lab_role_permissions_pkey PRIMARY KEY (role, permission),
+    CONSTRAINT lab_role_permissions_role_fkey FOREIGN KEY (role) REFERENCES public.lab_roles(name) ON DELETE CASCADE,
+    CONSTRAINT lab_role_permissions_permission_fkey FOREIGN KEY (permission) REFERENCES public.lab_permissions(name) ON DELETE CASCADE
+);
+
+
+ALTER TABLE public.lab_role_permissions OWNER TO postgres;
+
+--
+-- Data: lab_roles, lab_permissions, lab_role_permissions
+--
+
+INSERT INTO public.lab_roles(name, description) VALUES
+    ('admin', 'Administrator sistem'),
+    ('user', 'Nasabah'),
+    ('teller', 'Teller cabang (transaksi kas corp)'),
+    ('branch_supervisor', 'Supervisor cabang (approval penarikan, refund PPOB)'),
+    ('auditor', 'Auditor (read-only audit & kas)')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO public.lab_permissions(name, description) VALUES
+    ('audit:read', 'Lihat audit log'),
+    ('user:mfa_reset', 'Reset 2FA user'),
+    ('withdraw:read', 'Lihat antrian penarikan & saldo EOD corp'),
+    ('withdraw:approve', 'Posting jurnal penarikan corp'),
+    ('cash:deposit', 'Setor tunai ke rekening nasabah'),
+    ('cash:withdraw', 'Tarik tunai dari rekening nasabah'),
+    ('ppob:refund', 'Refund transaksi PPOB'),
+    ('notification:broadcast', 'Kirim push notification ke token sembarang')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO public.lab_role_permissions(role, permission) VALUES
+    ('admin', 'audit:read'),
+    ('admin', 'user:mfa_reset'),
+    ('admin', 'withdraw:read'),
+    ('admin', 'withdraw:approve'),
+    ('admin', 'cash:deposit'),
+    ('admin', 'cash:withdraw'),
+    ('admin', 'ppob:refund'),
+    ('admin', 'notification:broadcast'),
+    ('teller', 'withdraw:read'),
+    ('teller', 'cash:deposit'),
+    ('teller', 'cash:withdraw'),
+    ('branch_supervisor', 'withdraw:read'),
+    ('branch_supervisor', 'withdraw:approve'),
+    ('branch_supervisor', 'ppob:refund'),
+    ('auditor', 'audit:read'),
+    ('auditor', 'withdraw:read')
+ON CONFLICT DO NOTHING;
+
+--
+-- Name: lab_users lab_users_role_fkey; Type: FK CONSTRAINT; Schema: public; Owner: postgres
+--
+
+ALTER TABLE ONLY public.lab_users
+    ADD CONSTRAINT lab_users_role_fkey FOREIGN KEY (role) REFERENCES public.lab_roles(name);
+
+--
+-- Name: lab_fun_role_permissions(text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_role_permissions(p_role text) RETURNS text[]
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT COALESCE(array_agg(rp.permission ORDER BY rp.permission), '{}'::text[])
+    FROM lab_role_permissions rp
+   WHERE rp.role = p_role;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_role_permissions(p_role text) OWNER TO postgres;
+
+
+--
+-- Name: lab_invitations; Type: TABLE; Schema: public; Owner: postgres
+--
+
+-- Undangan akun staf/admin. Token yang dikirim ke invitee adalah JWT bertanda tangan
+-- (jti = id); baris ini menjamin sekali pakai & bisa dicabut.
+CREATE TABLE IF NOT EXISTS public.lab_invitations (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    email public.citext NOT NULL,
+    role text NOT NULL,
+    invited_by uuid NOT NULL,
+    expires_at timestamp with time zone NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    redeemed_at timestamp with time zone,
+    redeemed_user_id uuid,
+    revoked_at timestamp with time zone,
+    CONSTRAINT lab_invitations_pkey PRIMARY KEY (id),
+    CONSTRAINT lab_invitations_role_fkey FOREIGN KEY (role) REFERENCES public.lab_roles(name),
+    CONSTRAINT lab_invitations_invited_by_fkey FOREIGN KEY (invited_by) REFERENCES public.lab_users(id),
+    CONSTRAINT lab_invitations_redeemed_user_id_fkey FOREIGN KEY (redeemed_user_id) REFERENCES public.lab_users(id)
+);
+
+
+ALTER TABLE public.lab_invitations OWNER TO postgres;
+
+CREATE INDEX IF NOT EXISTS lab_invitations_email_idx ON public.lab_invitations USING btree (email);
+
+INSERT INTO public.lab_permissions(name, description) VALUES
+    ('user:invite', 'Undang akun admin/staf')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO public.lab_role_permissions(role, permission) VALUES
+    ('admin', 'user:invite')
+ON CONFLICT DO NOTHING;
+
+--
+-- Name: lab_fun_create_invitation(text, text, uuid, timestamp with time zone); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Undangan lama yang masih pending untuk email yang sama otomatis dicabut
+CREATE OR REPLACE FUNCTION public.lab_fun_create_invitation(p_email text, p_role text, p_invited_by uuid, p_expires_at timestamp with time zone) RETURNS uuid
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_id uuid;
+BEGIN
+  IF EXISTS (SELECT 1 FROM lab_users WHERE email = p_email) THEN
+    RAISE EXCEPTION 'EMAIL_EXISTS';
+  END IF;
+  IF NOT EXISTS (SELECT 1 FROM lab_roles WHERE name = p_role) THEN
+    RAISE EXCEPTION 'ROLE_UNKNOWN';
+  END IF;
+
+  UPDATE lab_invitations
+     SET revoked_at = now()
+   WHERE email = p_email
+     AND redeemed_at IS NULL
+     AND revoked_at IS NULL;
+
+  INSERT INTO lab_invitations(email, role, invited_by, expires_at)
+  VALUES (p_email, p_role, p_invited_by, p_expires_at)
+  RETURNING id INTO v_id;
+
+  RETURN v_id;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_create_invitation(p_email text, p_role text, p_invited_by uuid, p_expires_at timestamp with time zone) OWNER TO postgres;
+
+--
+-- Name: lab_fun_revoke_invitation(uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_revoke_invitation(p_id uuid) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  UPDATE lab_invitations
+     SET revoked_at = now()
+   WHERE id = p_id
+     AND redeemed_at IS NULL
+     AND revoked_at IS NULL;
+  RETURN FOUND;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_revoke_invitation(p_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_redeem_invitation(uuid, text, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Tukar undangan (sekali pakai) menjadi akun baru dengan role dari undangan
+CREATE OR REPLACE FUNCTION public.lab_fun_redeem_invitation(p_id uuid, p_email text, p_password_hash text) RETURNS TABLE(user_id uuid, role text, invited_by uuid)
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_inv     lab_invitations%ROWTYPE;
+  v_user_id uuid;
+BEGIN
+  SELECT * INTO v_inv
+    FROM lab_invitations i
+   WHERE i.id = p_id
+   FOR UPDATE;
+
+  IF v_inv.id IS NULL
+     OR v_inv.redeemed_at IS NOT NULL
+     OR v_inv.revoked_at IS NOT NULL
+     OR v_inv.expires_at <= now()
+     OR v_inv.email <> p_email::citext THEN
+    RAISE EXCEPTION 'INVITATION_INVALID';
+  END IF;
+
+  IF EXISTS (SELECT 1 FROM lab_users u WHERE u.email = v_inv.email) THEN
+    RAISE EXCEPTION 'EMAIL_EXISTS';
+  END IF;
+
+  INSERT INTO lab_users(email, password_hash, role)
+  VALUES (v_inv.email, p_password_hash, v_inv.role)
+  RETURNING id INTO v_user_id;
+
+  UPDATE lab_invitations
+     SET redeemed_at = now(),
+         redeemed_user_id = v_user_id
+   WHERE id = p_id;
+
+  user_id    := v_user_id;
+  role       := v_inv.role;
+  invited_by := v_inv.invited_by;
+  RETURN NEXT;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_redeem_invitation(p_id uuid, p_email text, p_password_hash text) OWNER TO postgres;
+
+
+--
+-- Name: lab_login_throttle; Type: TABLE; Schema: public; Owner: postgres
+--
+
+-- Hitungan gagal login per email (dicatat walau email tidak terdaftar) dan per IP.
+-- blocked_until = backoff eksponensial; locked = lockout penuh setelah N kegagalan.
+CREATE TABLE IF NOT EXISTS public.lab_login_throttle (
+    scope text NOT NULL,
+    key text NOT NULL,
+    failures integer DEFAULT 0 NOT NULL,
+    last_failure_at timestamp with time zone,
+    blocked_until timestamp with time zone,
+    locked boolean DEFAULT false NOT NULL,
+    CONSTRAINT lab_login_throttle_pkey PRIMARY KEY (scope, key),
+    CONSTRAINT lab_login_throttle_scope_check CHECK ((scope = ANY (ARRAY['email'::text, 'ip'::text, 'totp'::text])))
+);
+
+
+ALTER TABLE public.lab_login_throttle OWNER TO postgres;
+
+INSERT INTO public.lab_permissions(name, description) VALUES
+    ('auth:lock_manage', 'Lihat & lepas lockout login')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO public.lab_role_permissions(role, permission) VALUES
+    ('admin', 'auth:lock_manage')
+ON CONFLICT DO NOTHING;
+
+--
+-- Name: lab_fun_login_blocked_until(text, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_login_blocked_until(p_email text, p_ip text) RETURNS timestamp with time zone
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT max(t.blocked_until)
+    FROM lab_login_throttle t
+   WHERE ((t.scope = 'email' AND t.key = lower(trim(p_email)))
+       OR (t.scope = 'ip' AND t.key = p_ip))
+     AND t.blocked_until > now();
+$$;
+
+
+ALTER FUNCTION public.lab_fun_login_blocked_until(p_email text, p_ip text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_login_failure(text, text, integer, integer, integer, integer, integer, integer); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_login_failure(p_email text, p_ip text, p_free_email integer, p_free_ip integer, p_lock_email integer, p_lock_ip integer, p_lock_secs integer, p_backoff_cap_secs integer) RETURNS timestamp with time zone
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_email timestamp with time zone;
+  v_ip    timestamp with time zone;
+BEGIN
+  v_email := lab_fun_throttle_bump('email', lower(trim(p_email)), p_free_email, p_lock_email, p_lock_secs, p_backoff_cap_secs);
+  v_ip    := lab_fun_throttle_bump('ip', p_ip, p_free_ip, p_lock_ip, p_lock_secs, p_backoff_cap_secs);
+  RETURN GREATEST(v_email, v_ip);
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_login_failure(p_email text, p_ip text, p_free_email integer, p_free_ip integer, p_lock_email integer, p_lock_ip integer, p_lock_secs integer, p_backoff_cap_secs integer) OWNER TO postgres;
+
+--
+-- Name: lab_fun_login_success(text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Login sukses hanya me-reset counter email; counter IP tidak (satu akun valid
+-- tidak boleh dipakai untuk "mencuci" counter IP penyerang).
+CREATE OR REPLACE FUNCTION public.lab_fun_login_success(p_email text) RETURNS void
+    LANGUAGE sql
+    AS $$
+  DELETE FROM lab_login_throttle
+   WHERE scope = 'email' AND key = lower(trim(p_email));
+$$;
+
+
+ALTER FUNCTION public.lab_fun_login_success(p_email text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_list_login_locks(); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_list_login_locks() RETURNS TABLE(scope text, key text, failures integer, last_failure_at timestamp with time zone, blocked_until timestamp with time zone, locked boolean)
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT t.scope, t.key, t.failures, t.last_failure_at, t.blocked_until, t.locked
+    FROM lab_login_throttle t
+   WHERE t.blocked_until > now()
+   ORDER BY t.blocked_until DESC;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_list_login_locks() OWNER TO postgres;
+
+--
+-- Name: lab_fun_release_login_lock(text, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_release_login_lock(p_scope text, p_key text) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  DELETE FROM lab_login_throttle
+   WHERE scope = p_scope
+     AND key = CASE WHEN p_scope = 'email' THEN lower(trim(p_key)) ELSE p_key END;
+  RETURN FOUND;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_release_login_lock(p_scope text, p_key text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_login_throttle_purge(); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Hapus baris throttle yang sudah tidak berpengaruh: tidak sedang diblokir dan counternya
+-- akan mulai dari nol lagi (lockout sudah lewat, atau tidak ada kegagalan 24 jam).
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_login_throttle_purge() RETURNS integer
+    LANGUAGE sql
+    AS $$
+  WITH purged AS (
+    DELETE FROM lab_login_throttle t
+     WHERE (t.blocked_until IS NULL OR t.blocked_until <= now())
+       AND (t.locked OR t.last_failure_at IS NULL OR t.last_failure_at < now() - interval '24 hours')
+    RETURNING 1
+  )
+  SELECT count(*)::integer FROM purged;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_login_throttle_purge() OWNER TO postgres;
+
+
+--
+-- Name: lab_api_keys; Type: TABLE; Schema: public; Owner: postgres
+--
+
+-- API key untuk sistem lain (machine-to-machine). Hanya SHA-256 key yang disimpan;
+-- scopes memakai nama dari lab_permissions. Saat rotate, hash lama tetap berlaku
+-- sampai prev_valid_until supaya klien bisa berpindah tanpa downtime.
+CREATE TABLE IF NOT EXISTS public.lab_api_keys (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    name text NOT NULL,
+    key_prefix text NOT NULL,
+    key_sha256 bytea NOT NULL,
+    prev_key_sha256 bytea,
+    prev_valid_until timestamp with time zone,
+    scopes text[] DEFAULT '{}'::text[] NOT NULL,
+    ip_allowlist inet[],
+    created_by uuid NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    rotated_at timestamp with time zone,
+    expires_at timestamp with time zone,
+    revoked_at timestamp with time zone,
+    last_used_at timestamp with time zone,
+    last_used_ip text,
+    CONSTRAINT lab_api_keys_pkey PRIMARY KEY (id),
+    CONSTRAINT lab_api_keys_created_by_fkey FOREIGN KEY (created_by) REFERENCES public.lab_users(id)
+);
+
+
+ALTER TABLE public.lab_api_keys OWNER TO postgres;
+
+CREATE UNIQUE INDEX IF NOT EXISTS lab_api_keys_key_sha256_uidx ON public.lab_api_keys USING btree (key_sha256);
+CREATE INDEX IF NOT EXISTS lab_api_keys_prev_key_sha256_idx ON public.lab_api_keys USING btree (prev_key_sha256) WHERE (prev_key_sha256 IS NOT NULL);
+
+INSERT INTO public.lab_permissions(name, description) VALUES
+    ('api_key:manage', 'Kelola API key'),
+    ('notification:store', 'Kirim notifikasi dividen per store (back-office corp)')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO public.lab_role_permissions(role, permission) VALUES
+    ('admin', 'api_key:manage'),
+    ('admin', 'notification:store')
+ON CONFLICT DO NOTHING;
+
+--
+-- Name: lab_fun_api_key_create(text, text, bytea, text[], inet[], timestamp with time zone, uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_api_key_create(p_name text, p_key_prefix text, p_key_sha256 bytea, p_scopes text[], p_ip_allowlist inet[], p_expires_at timestamp with time zone, p_created_by uuid) RETURNS uuid
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_id uuid;
+BEGIN
+  IF EXISTS (
+    SELECT 1 FROM unnest(p_scopes) s(scope)
+     WHERE NOT EXISTS (SELECT 1 FROM lab_permissions p WHERE p.name = s.scope)
+  ) THEN
+    RAISE EXCEPTION 'SCOPE_UNKNOWN';
+  END IF;
+
+  INSERT INTO lab_api_keys(name, key_prefix, key_sha256, scopes, ip_allowlist, expires_at, created_by)
+  VALUES (p_name, p_key_prefix, p_key_sha256, p_scopes, p_ip_allowlist, p_expires_at, p_created_by)
+  RETURNING id INTO v_id;
+
+  RETURN v_id;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_api_key_create(p_name text, p_key_prefix text, p_key_sha256 bytea, p_scopes text[], p_ip_allowlist inet[], p_expires_at timestamp with time zone, p_created_by uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_api_key_rotate(uuid, text, bytea, integer); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_api_key_rotate(p_id uuid, p_key_prefix text, p_key_sha256 bytea, p_grace_secs integer) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  UPDATE lab_api_keys
+     SET prev_key_sha256 = CASE WHEN p_grace_secs > 0 THEN key_sha256 END,
+         prev_valid_until = CASE WHEN p_grace_secs > 0 THEN now() + make_interval(secs => p_grace_secs) END,
+         key_prefix = p_key_prefix,
+         key_sha256 = p_key_sha256,
+         rotated_at = now()
+   WHERE id = p_id
+     AND revoked_at IS NULL;
+  RETURN FOUND;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_api_key_rotate(p_id uuid, p_key_prefix text, p_key_sha256 bytea, p_grace_secs integer) OWNER TO postgres;
+
+--
+-- Name: lab_fun_api_key_revoke(uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_api_key_revoke(p_id uuid) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  UPDATE lab_api_keys
+     SET revoked_at = now(),
+         prev_key_sha256 = NULL,
+         prev_valid_until = NULL
+   WHERE id = p_id
+     AND revoked_at IS NULL;
+  RETURN FOUND;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_api_key_revoke(p_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_api_key_list(); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_api_key_list() RETURNS TABLE(id uuid, name text, key_prefix text, scopes text[], ip_allowlist text[], created_by uuid, created_at timestamp with time zone, rotated_at timestamp with time zone, expires_at timestamp with time zone, revoked_at timestamp with time zone, last_used_at timestamp with time zone, last_used_ip text)
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT k.id, k.name, k.key_prefix, k.scopes, k.ip_allowlist::text[], k.created_by, k.created_at,
+         k.rotated_at, k.expires_at, k.revoked_at, k.last_used_at, k.last_used_ip
+    FROM lab_api_keys k
+   ORDER BY k.created_at DESC;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_api_key_list() OWNER TO postgres;
+
+--
+-- Name: lab_fun_api_key_authenticate(bytea, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Cari key aktif (hash saat ini, atau hash lama dalam masa grace rotasi) dan cek allowlist IP.
+-- ip_allowed = false jika allowlist diisi dan IP caller tidak termasuk.
+CREATE OR REPLACE FUNCTION public.lab_fun_api_key_authenticate(p_key_sha256 bytea, p_ip text) RETURNS TABLE(key_id uuid, name text, scopes text[], ip_allowed boolean)
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_key lab_api_keys%ROWTYPE;
+  v_ip  inet;
+BEGIN
+  SELECT * INTO v_key
+    FROM lab_api_keys k
+   WHERE (k.key_sha256 = p_key_sha256
+          OR (k.prev_key_sha256 = p_key_sha256 AND k.prev_valid_until > now()))
+     AND k.revoked_at IS NULL
+     AND (k.expires_at IS NULL OR k.expires_at > now())
+   LIMIT 1;
+
+  IF v_key.id IS NULL THEN
+    RETURN;
+  END IF;
+
+  BEGIN
+    v_ip := p_ip::inet;
+  EXCEPTION WHEN others THEN
+    v_ip := NULL;
+  END;
+
+  key_id := v_key.id;
+  name := v_key.name;
+  scopes := v_key.scopes;
+  ip_allowed := v_key.ip_allowlist IS NULL
+             OR cardinality(v_key.ip_allowlist) = 0
+             OR (v_ip IS NOT NULL AND EXISTS (
+                   SELECT 1 FROM unnest(v_key.ip_allowlist) a(net) WHERE v_ip <<= a.net));
+
+  IF ip_allowed THEN
+    UPDATE lab_api_keys
+       SET last_used_at = now(),
+           last_used_ip = p_ip
+     WHERE id = v_key.id;
+  END IF;
+
+  RETURN NEXT;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_api_key_authenticate(p_key_sha256 bytea, p_ip text) OWNER TO postgres;
+
+
+--
+-- Name: lab_request_nonces; Type: TABLE; Schema: public; Owner: postgres
+--
+
+-- Nonce request bertanda tangan HMAC yang sudah dipakai (anti-replay), per client
+CREATE TABLE IF NOT EXISTS public.lab_request_nonces (
+    client_id text NOT NULL,
+    nonce text NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT lab_request_nonces_pkey PRIMARY KEY (client_id, nonce)
+);
+
+
+ALTER TABLE public.lab_request_nonces OWNER TO postgres;
+
+CREATE INDEX IF NOT EXISTS lab_request_nonces_created_at_idx ON public.lab_request_nonces USING btree (created_at);
+
+--
+-- Name: lab_fun_consume_request_nonce(text, text, integer); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- TRUE jika nonce baru (dan dicatat); FALSE jika sudah pernah dipakai.
+-- Nonce yang lebih tua dari 2x window dibuang karena timestamp-nya sudah pasti ditolak.
+CREATE OR REPLACE FUNCTION public.lab_fun_consume_request_nonce(p_client_id text, p_nonce text, p_window_secs integer) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_rows integer;
+BEGIN
+  DELETE FROM lab_request_nonces
+   WHERE created_at < now() - make_interval(secs => p_window_secs * 2);
+
+  INSERT INTO lab_request_nonces(client_id, nonce)
+  VALUES (p_client_id, p_nonce)
+  ON CONFLICT (client_id, nonce) DO NOTHING;
+  GET DIAGNOSTICS v_rows = ROW_COUNT;
+
+  RETURN v_rows = 1;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_consume_request_nonce(p_client_id text, p_nonce text, p_window_secs integer) OWNER TO postgres;
+
+
+--
+-- Name: lab_password_history; Type: TABLE; Schema: public; Owner: postgres
+--
+
+-- hash password lama; diisi trigger setiap kali lab_users.password_hash berubah
+CREATE TABLE public.lab_password_history (
+    id bigint GENERATED ALWAYS AS IDENTITY,
+    user_id uuid NOT NULL,
+    password_hash text NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+
+ALTER TABLE public.lab_password_history OWNER TO postgres;
+
+ALTER TABLE ONLY public.lab_password_history
+    ADD CONSTRAINT lab_password_history_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.lab_password_history
+    ADD CONSTRAINT lab_password_history_user_id_fkey FOREIGN KEY (user_id) REFERENCES public.lab_users(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_lab_password_history_user ON public.lab_password_history USING btree (user_id, id DESC);
+
+--
+-- Name: lab_fun_password_history(); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- simpan hash lama, pangkas ke 24 entri terakhir (batas PASSWORD_HISTORY di aplikasi)
+CREATE OR REPLACE FUNCTION public.lab_fun_password_history() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  INSERT INTO lab_password_history(user_id, password_hash)
+  VALUES (OLD.id, OLD.password_hash);
+
+  DELETE FROM lab_password_history
+   WHERE user_id = OLD.id
+     AND id NOT IN (SELECT id FROM lab_password_history
+                     WHERE user_id = OLD.id
+                     ORDER BY id DESC
+                     LIMIT 24);
+  RETURN NEW;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_password_history() OWNER TO postgres;
+
+--
+-- Name: lab_users lab_users_password_history; Type: TRIGGER; Schema: public; Owner: postgres
+--
+
+CREATE TRIGGER lab_users_password_history AFTER UPDATE OF password_hash ON public.lab_users FOR EACH ROW WHEN (OLD.password_hash IS DISTINCT FROM NEW.password_hash) EXECUTE FUNCTION public.lab_fun_password_history();
+
+--
+-- Name: lab_fun_recent_password_hashes(uuid, integer); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- hash saat ini + (p_limit - 1) hash sebelumnya
+CREATE OR REPLACE FUNCTION public.lab_fun_recent_password_hashes(p_user_id uuid, p_limit integer) RETURNS SETOF text
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT password_hash FROM lab_users WHERE id = p_user_id AND p_limit > 0
+  UNION ALL
+  (SELECT password_hash
+     FROM lab_password_history
+    WHERE user_id = p_user_id
+    ORDER BY id DESC
+    LIMIT GREATEST(p_limit - 1, 0));
+$$;
+
+
+ALTER FUNCTION public.lab_fun_recent_password_hashes(p_user_id uuid, p_limit integer) OWNER TO postgres;
+
+--
+-- Name: lab_fun_password_reset_owner(bytea); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- user pemilik token reset yang masih valid (tanpa memakainya); NULL jika tidak valid
+CREATE OR REPLACE FUNCTION public.lab_fun_password_reset_owner(p_token_sha256 bytea) RETURNS uuid
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT user_id
+    FROM lab_password_reset_tokens
+   WHERE token_sha256 = p_token_sha256
+     AND used_at IS NULL
+     AND now() < expires_at;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_password_reset_owner(p_token_sha256 bytea) OWNER TO postgres;
+
+
+--
+-- Name: lab_fun_change_password(uuid, text, uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- ganti password user yang sedang login; sesi lain (refresh token + access jti pasangannya)
+-- dan token reset yang belum dipakai dicabut. Mengembalikan jumlah sesi yang dicabut.
+CREATE OR REPLACE FUNCTION public.lab_fun_change_password(p_user_id uuid, p_new_password_hash text, p_current_jti uuid) RETURNS integer
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_current_token_id uuid;
+BEGIN
+  SELECT token_id INTO v_current_token_id
+    FROM lab_refresh_tokens
+   WHERE user_id = p_user_id
+     AND access_jti = p_current_jti
+     AND revoked = false;
+
+  UPDATE lab_users SET password_hash = p_new_password_hash WHERE id = p_user_id;
+  IF NOT FOUND THEN
+    RAISE EXCEPTION 'USER_NOT_FOUND';
+  END IF;
+
+  UPDATE lab_password_reset_tokens
+     SET used_at = now()
+   WHERE user_id = p_user_id
+     AND used_at IS NULL;
+
+  RETURN lab_fun_revoke_all_sessions(p_user_id, v_current_token_id);
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_change_password(p_user_id uuid, p_new_password_hash text, p_current_jti uuid) OWNER TO postgres;
+
+
+--
+-- Name: lab_fun_notify_revoked_access_token(); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- kabari instance API (cache revoked jti) setiap ada access token yang di-blacklist
+CREATE OR REPLACE FUNCTION public.lab_fun_notify_revoked_access_token() RETURNS trigger
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  PERFORM pg_notify('lab_revoked_access_tokens',
+                    NEW.jti::text || ':' || extract(epoch FROM NEW.expires_at)::bigint);
+  RETURN NEW;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_notify_revoked_access_token() OWNER TO postgres;
+
+--
+-- Name: lab_revoked_access_tokens lab_revoked_access_tokens_notify; Type: TRIGGER; Schema: public; Owner: postgres
+--
+
+CREATE TRIGGER lab_revoked_access_tokens_notify AFTER INSERT ON public.lab_revoked_access_tokens FOR EACH ROW EXECUTE FUNCTION public.lab_fun_notify_revoked_access_token();
+
+
+--
+-- Name: lab_step_up_challenges; Type: TABLE; Schema: public; Owner: postgres
+--
+
+-- challenge step-up untuk transaksi bernilai besar; terikat ke hash payload transaksi
+CREATE TABLE public.lab_step_up_challenges (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    user_id uuid NOT NULL,
+    operation text NOT NULL,
+    payload_sha256 bytea NOT NULL,
+    method text NOT NULL,
+    code_sha256 bytea,
+    attempts integer DEFAULT 0 NOT NULL,
+    expires_at timestamp with time zone NOT NULL,
+    consumed_at timestamp with time zone,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT lab_step_up_challenges_method_check CHECK ((method = ANY (ARRAY['totp'::text, 'otp'::text])))
+);
+
+
+ALTER TABLE public.lab_step_up_challenges OWNER TO postgres;
+
+ALTER TABLE ONLY public.lab_step_up_challenges
+    ADD CONSTRAINT lab_step_up_challenges_pkey PRIMARY KEY (id);
+
+ALTER TABLE ONLY public.lab_step_up_challenges
+    ADD CONSTRAINT lab_step_up_challenges_user_id_fkey FOREIGN KEY (user_id) REFERENCES public.lab_users(id) ON DELETE CASCADE;
+
+CREATE INDEX idx_lab_step_up_challenges_exp ON public.lab_step_up_challenges USING btree (expires_at);
+
+--
+-- Name: lab_transfer_counterparties; Type: TABLE; Schema: public; Owner: postgres
+--
+
+-- rekening tujuan yang pernah menerima transfer dari user (deteksi beneficiary baru)
+CREATE TABLE public.lab_transfer_counterparties (
+    user_id uuid NOT NULL,
+    account_no text NOT NULL,
+    first_transfer_at timestamp with time zone DEFAULT now() NOT NULL,
+    last_transfer_at timestamp with time zone DEFAULT now() NOT NULL
+);
+
+
+ALTER TABLE public.lab_transfer_counterparties OWNER TO postgres;
+
+ALTER TABLE ONLY public.lab_transfer_counterparties
+    ADD CONSTRAINT lab_transfer_counterparties_pkey PRIMARY KEY (user_id, account_no);
+
+ALTER TABLE ONLY public.lab_transfer_counterparties
+    ADD CONSTRAINT lab_transfer_counterparties_user_id_fkey FOREIGN KEY (user_id) REFERENCES public.lab_users(id) ON DELETE CASCADE;
+
+--
+-- Name: lab_fun_step_up_create(uuid, text, bytea, text, bytea, timestamp with time zone); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_step_up_create(p_user_id uuid, p_operation text, p_payload_sha256 bytea, p_method text, p_code_sha256 bytea, p_expires_at timestamp with time zone) RETURNS uuid
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_id uuid;
+BEGIN
+  DELETE FROM lab_step_up_challenges WHERE expires_at < now() - interval '1 day';
+
+  INSERT INTO lab_step_up_challenges(user_id, operation, payload_sha256, method, code_sha256, expires_at)
+  VALUES (p_user_id, p_operation, p_payload_sha256, p_method, p_code_sha256, p_expires_at)
+  RETURNING id INTO v_id;
+
+  RETURN v_id;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_step_up_create(p_user_id uuid, p_operation text, p_payload_sha256 bytea, p_method text, p_code_sha256 bytea, p_expires_at timestamp with time zone) OWNER TO postgres;
+
+--
+-- Name: lab_fun_step_up_attempt(uuid, uuid, text, bytea, integer); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- hitung satu percobaan untuk challenge yang cocok (user, operasi, payload) dan masih berlaku;
+-- kosong jika tidak valid / percobaan habis
+CREATE OR REPLACE FUNCTION public.lab_fun_step_up_attempt(p_challenge_id uuid, p_user_id uuid, p_operation text, p_payload_sha256 bytea, p_max_attempts integer) RETURNS TABLE(method text, code_sha256 bytea)
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  RETURN QUERY
+  UPDATE lab_step_up_challenges c
+     SET attempts = c.attempts + 1
+   WHERE c.id = p_challenge_id
+     AND c.user_id = p_user_id
+     AND c.operation = p_operation
+     AND c.payload_sha256 = p_payload_sha256
+     AND c.consumed_at IS NULL
+     AND now() < c.expires_at
+     AND c.attempts < p_max_attempts
+  RETURNING c.method, c.code_sha256;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_step_up_attempt(p_challenge_id uuid, p_user_id uuid, p_operation text, p_payload_sha256 bytea, p_max_attempts integer) OWNER TO postgres;
+
+--
+-- Name: lab_fun_step_up_consume(uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- tandai challenge terpakai; FALSE jika sudah dipakai request lain
+CREATE OR REPLACE FUNCTION public.lab_fun_step_up_consume(p_challenge_id uuid) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  UPDATE lab_step_up_challenges
+     SET consumed_at = now()
+   WHERE id = p_challenge_id
+     AND consumed_at IS NULL;
+  RETURN FOUND;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_step_up_consume(p_challenge_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_is_new_counterparty(uuid, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_is_new_counterparty(p_user_id uuid, p_account_no text) RETURNS boolean
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT NOT EXISTS (
+    SELECT 1 FROM lab_transfer_counterparties
+     WHERE user_id = p_user_id AND account_no = p_account_no
+  );
+$$;
+
+
+ALTER FUNCTION public.lab_fun_is_new_counterparty(p_user_id uuid, p_account_no text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_record_counterparty(uuid, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_record_counterparty(p_user_id uuid, p_account_no text) RETURNS void
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  INSERT INTO lab_transfer_counterparties(user_id, account_no)
+  VALUES (p_user_id, p_account_no)
+  ON CONFLICT (user_id, account_no) DO UPDATE SET last_transfer_at = now();
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_record_counterparty(p_user_id uuid, p_account_no text) OWNER TO postgres;
+
+
+--
+-- Siklus hidup rekening: active → frozen / dormant → active, dan closed (final).
+--   frozen  : dibekukan owner/admin; tidak bisa didebit, kredit masuk tetap diterima
+--   dormant : tidak ada mutasi selama periode idle (job lab_fun_mark_dormant_accounts); sama seperti frozen
+--   closed  : ditutup dengan saldo nol (atau saldo dipindah dulu); tidak bisa didebit maupun dikredit
+--
+
+--
+-- Name: lab_account_status_events; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_account_status_events (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    account_id uuid NOT NULL,
+    from_status text NOT NULL,
+    to_status text NOT NULL,
+    actor_kind text NOT NULL,
+    actor_id uuid,
+    reason text,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT lab_account_status_events_pkey PRIMARY KEY (id),
+    CONSTRAINT lab_account_status_events_account_id_fkey FOREIGN KEY (account_id) REFERENCES public.lab_accounts(id) ON DELETE CASCADE
+);
+
+
+ALTER TABLE public.lab_account_status_events OWNER TO postgres;
+
+CREATE INDEX IF NOT EXISTS lab_account_status_events_account_idx ON public.lab_account_status_events USING btree (account_id, created_at DESC);
+
+INSERT INTO public.lab_permissions(name, description) VALUES
+    ('account:manage', 'Bekukan, aktifkan kembali & tutup rekening nasabah')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO public.lab_role_permissions(role, permission) VALUES
+    ('admin', 'account:manage'),
+    ('branch_supervisor', 'account:manage')
+ON CONFLICT DO NOTHING;
+
+--
+-- Name: lab_fun_account_assert_status(text, boolean); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_assert_status(p_status text, p_debit boolean) RETURNS void
+    LANGUAGE plpgsql IMMUTABLE
+    AS $$
+BEGIN
+  IF p_status = 'closed' THEN
+    RAISE EXCEPTION 'ACCOUNT_CLOSED';
+  END IF;
+  IF p_debit AND p_status = 'frozen' THEN
+    RAISE EXCEPTION 'ACCOUNT_FROZEN';
+  END IF;
+  IF p_debit AND p_status = 'dormant' THEN
+    RAISE EXCEPTION 'ACCOUNT_DORMANT';
+  END IF;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_assert_status(p_status text, p_debit boolean) OWNER TO postgres;
+
+--
+-- Name: lab_fun_account_set_status(uuid, uuid, uuid, text, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- p_owner_id NULL = aksi admin; selain itu aksi owner (wajib pemilik rekening).
+-- Owner tidak bisa mengaktifkan kembali rekening yang dibekukan admin.
+-- Mengembalikan pemilik rekening & status sebelumnya.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_set_status(p_account_id uuid, p_owner_id uuid, p_actor_id uuid, p_to text, p_reason text) RETURNS TABLE(owner_id uuid, previous_status text)
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_owner   uuid;
+  v_status  text;
+  v_by      text;
+  v_kind    text := CASE WHEN p_owner_id IS NULL THEN 'admin' ELSE 'owner' END;
+BEGIN
+  SELECT user_id, status, status_changed_by
+    INTO v_owner, v_status, v_by
+  FROM lab_accounts
+  WHERE id = p_account_id
+  FOR UPDATE;
+
+  IF v_owner IS NULL THEN
+    RAISE EXCEPTION 'ACCOUNT_NOT_FOUND';
+  END IF;
+  IF p_owner_id IS NOT NULL AND v_owner <> p_owner_id THEN
+    RAISE EXCEPTION 'ACCOUNT_NOT_OWNED';
+  END IF;
+  IF v_status = 'closed' THEN
+    RAISE EXCEPTION 'ACCOUNT_CLOSED';
+  END IF;
+
+  IF p_to = 'frozen' THEN
+    IF v_status = 'frozen' THEN
+      RAISE EXCEPTION 'STATUS_UNCHANGED';
+    END IF;
+  ELSIF p_to = 'active' THEN
+    IF v_status = 'active' THEN
+      RAISE EXCEPTION 'STATUS_UNCHANGED';
+    END IF;
+    IF v_kind = 'owner' AND v_status = 'frozen' AND v_by = 'admin' THEN
+      RAISE EXCEPTION 'FROZEN_BY_ADMIN';
+    END IF;
+  ELSE
+    RAISE EXCEPTION 'STATUS_INVALID';
+  END IF;
+
+  UPDATE lab_accounts
+     SET status = p_to,
+         status_reason = p_reason,
+         status_changed_by = v_kind,
+         status_changed_at = now(),
+         updated_at = now()
+   WHERE id = p_account_id;
+
+  INSERT INTO lab_account_status_events(account_id, from_status, to_status, actor_kind, actor_id, reason)
+  VALUES (p_account_id, v_status, p_to, v_kind, p_actor_id, p_reason);
+
+  owner_id := v_owner;
+  previous_status := v_status;
+  RETURN NEXT;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_set_status(p_account_id uuid, p_owner_id uuid, p_actor_id uuid, p_to text, p_reason text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_account_close(uuid, uuid, uuid, text, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Tutup rekening. Saldo harus nol, atau dipindah (sweep) ke rekening aktif milik owner yang sama.
+-- p_owner_id NULL = aksi admin.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_close(p_account_id uuid, p_owner_id uuid, p_actor_id uuid, p_sweep_to_no text, p_reason text) RETURNS TABLE(owner_id uuid, previous_status text, swept numeric, journal_id_credit uuid, journal_id_debit uuid)
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_owner     uuid;
+  v_status    text;
+  v_by        text;
+  v_balance   numeric;
+  v_no        text;
+  v_to_id     uuid;
+  v_to_owner  uuid;
+  v_to_status text;
+  v_to_bal    numeric;
+  v_kind      text := CASE WHEN p_owner_id IS NULL THEN 'admin' ELSE 'owner' END;
+BEGIN
+  SELECT user_id, status, status_changed_by, saldo, account_no
+    INTO v_owner, v_status, v_by, v_balance, v_no
+  FROM lab_accounts
+  WHERE id = p_account_id
+  FOR UPDATE;
+
+  IF v_owner IS NULL THEN
+    RAISE EXCEPTION 'ACCOUNT_NOT_FOUND';
+  END IF;
+  IF p_owner_id IS NOT NULL AND v_owner <> p_owner_id THEN
+    RAISE EXCEPTION 'ACCOUNT_NOT_OWNED';
+  END IF;
+  IF v_status = 'closed' THEN
+    RAISE EXCEPTION 'ACCOUNT_CLOSED';
+  END IF;
+  IF v_kind = 'owner' AND v_status = 'frozen' AND v_by = 'admin' THEN
+    RAISE EXCEPTION 'FROZEN_BY_ADMIN';
+  END IF;
+
+  owner_id := v_owner;
+  previous_status := v_status;
+  swept := 0;
+
+  IF v_balance > 0 THEN
+    IF p_sweep_to_no IS NULL THEN
+      RAISE EXCEPTION 'BALANCE_NOT_ZERO';
+    END IF;
+
+    SELECT id, user_id, status, saldo
+      INTO v_to_id, v_to_owner, v_to_status, v_to_bal
+    FROM lab_accounts
+    WHERE account_no = p_sweep_to_no
+    FOR UPDATE;
+
+    IF v_to_id IS NULL THEN
+      RAISE EXCEPTION 'ACCOUNT_TO_NOT_FOUND';
+    END IF;
+    IF v_to_id = p_account_id THEN
+      RAISE EXCEPTION 'SAME_ACCOUNT';
+    END IF;
+    IF v_to_owner <> v_owner THEN
+      RAISE EXCEPTION 'SWEEP_TARGET_NOT_OWNED';
+    END IF;
+    PERFORM lab_fun_account_assert_status(v_to_status, false);
+
+    journal_id_credit := gen_random_uuid();
+    journal_id_debit  := gen_random_uuid();
+
+    UPDATE lab_accounts SET saldo = 0 WHERE id = p_account_id;
+    UPDATE lab_accounts SET saldo = v_to_bal + v_balance WHERE id = v_to_id;
+
+    INSERT INTO lab_journals (id, user_id, account_id, debit, credit, description, balance_after, trx_time)
+    VALUES (journal_id_credit, v_owner, p_account_id, 0, v_balance,
+            'Penutupan rekening, saldo dipindah ke ' || p_sweep_to_no, 0, now());
+
+    INSERT INTO lab_journals (id, user_id, account_id, debit, credit, description, balance_after, trx_time)
+    VALUES (journal_id_debit, v_owner, v_to_id, v_balance, 0,
+            'Pindahan saldo penutupan rekening ' || v_no, v_to_bal + v_balance, now());
+
+    swept := v_balance;
+  END IF;
+
+  UPDATE lab_accounts
+     SET status = 'closed',
+         status_reason = p_reason,
+         status_changed_by = v_kind,
+         status_changed_at = now(),
+         closed_at = now(),
+         updated_at = now()
+   WHERE id = p_account_id;
+
+  INSERT INTO lab_account_status_events(account_id, from_status, to_status, actor_kind, actor_id, reason)
+  VALUES (p_account_id, v_status, 'closed', v_kind, p_actor_id, p_reason);
+
+  RETURN NEXT;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_close(p_account_id uuid, p_owner_id uuid, p_actor_id uuid, p_sweep_to_no text, p_reason text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_mark_dormant_accounts(interval); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Rekening aktif tanpa jurnal selama p_idle → dormant. Mengembalikan jumlah rekening yang diubah.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_mark_dormant_accounts(p_idle interval) RETURNS integer
+    LANGUAGE sql
+    AS $$
+  WITH idle AS (
+    UPDATE lab_accounts a
+       SET status = 'dormant',
+           status_reason = 'no activity',
+           status_changed_by = 'system',
+           status_changed_at = now(),
+           updated_at = now()
+     WHERE a.status = 'active'
+       AND a.created_at < now() - p_idle
+       AND NOT EXISTS (
+         SELECT 1 FROM lab_journals j
+          WHERE j.account_id = a.id
+            AND j.trx_time >= now() - p_idle
+       )
+    RETURNING a.id
+  ), events AS (
+    INSERT INTO lab_account_status_events(account_id, from_status, to_status, actor_kind, reason)
+    SELECT id, 'active', 'dormant', 'system', 'no activity' FROM idle
+    RETURNING 1
+  )
+  SELECT count(*)::integer FROM events;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_mark_dormant_accounts(p_idle interval) OWNER TO postgres;
+
+--
+-- Limit transaksi per tier user: per transaksi, harian & bulanan, untuk tiap jenis transaksi
+-- (transfer_out, cash_withdraw, ppob). Scope 'user' = total semua rekening milik user,
+-- scope 'account' = per rekening. NULL = tanpa batas. Periode dihitung di zona Asia/Jakarta.
+--
+
+--
+-- Name: lab_limit_tiers; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_limit_tiers (
+    name text NOT NULL,
+    description text,
+    new_payee_cooling_hours integer DEFAULT 24 NOT NULL,
+    new_payee_cooling_max numeric(20,2),
+    CONSTRAINT lab_limit_tiers_pkey PRIMARY KEY (name)
+);
+
+
+ALTER TABLE public.lab_limit_tiers OWNER TO postgres;
+
+INSERT INTO public.lab_limit_tiers(name, description, new_payee_cooling_max) VALUES
+    ('basic', 'Nasabah baru / belum verifikasi', 1000000),
+    ('verified', 'Nasabah terverifikasi (KYC)', 5000000),
+    ('premium', 'Nasabah prioritas', 25000000)
+ON CONFLICT (name) DO NOTHING;
+
+ALTER TABLE ONLY public.lab_users
+    ADD CONSTRAINT lab_users_tier_fkey FOREIGN KEY (tier) REFERENCES public.lab_limit_tiers(name);
+
+--
+-- Name: lab_transaction_limits; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_transaction_limits (
+    tier text NOT NULL,
+    txn_type text NOT NULL,
+    scope text NOT NULL,
+    per_txn_max numeric(20,2),
+    daily_max numeric(20,2),
+    monthly_max numeric(20,2),
+    updated_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT lab_transaction_limits_pkey PRIMARY KEY (tier, txn_type, scope),
+    CONSTRAINT lab_transaction_limits_tier_fkey FOREIGN KEY (tier) REFERENCES public.lab_limit_tiers(name) ON DELETE CASCADE,
+    CONSTRAINT lab_transaction_limits_txn_type_check CHECK ((txn_type = ANY (ARRAY['transfer_out'::text, 'cash_withdraw'::text, 'ppob'::text]))),
+    CONSTRAINT lab_transaction_limits_scope_check CHECK ((scope = ANY (ARRAY['user'::text, 'account'::text])))
+);
+
+
+ALTER TABLE public.lab_transaction_limits OWNER TO postgres;
+
+INSERT INTO public.lab_transaction_limits(tier, txn_type, scope, per_txn_max, daily_max, monthly_max) VALUES
+    ('basic',    'transfer_out',  'user',     5000000,   10000000,   50000000),
+    ('basic',    'transfer_out',  'account',  NULL,       5000000,       NULL),
+    ('basic',    'cash_withdraw', 'user',     2500000,    5000000,   25000000),
+    ('basic',    'ppob',          'user',     1000000,    2000000,   10000000),
+    ('verified', 'transfer_out',  'user',    25000000,   50000000,  500000000),
+    ('verified', 'transfer_out',  'account',  NULL,      25000000,       NULL),
+    ('verified', 'cash_withdraw', 'user',    10000000,   20000000,  200000000),
+    ('verified', 'ppob',          'user',     5000000,   10000000,   50000000),
+    ('premium',  'transfer_out',  'user',   100000000,  250000000, 2500000000),
+    ('premium',  'cash_withdraw', 'user',    25000000,   50000000,  500000000),
+    ('premium',  'ppob',          'user',    10000000,   25000000,  150000000)
+ON CONFLICT (tier, txn_type, scope) DO NOTHING;
+
+--
+-- Name: lab_limit_counters; Type: TABLE; Schema: public; Owner: postgres
+--
+-- Pemakaian per (scope, pemilik scope, jenis transaksi, periode). Di-upsert di dalam
+-- transaksi ledger, jadi baris counter ikut terkunci sampai commit/rollback.
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_limit_counters (
+    scope text NOT NULL,
+    scope_id uuid NOT NULL,
+    txn_type text NOT NULL,
+    period text NOT NULL,
+    period_start date NOT NULL,
+    used numeric(20,2) DEFAULT 0 NOT NULL,
+    CONSTRAINT lab_limit_counters_pkey PRIMARY KEY (scope, scope_id, txn_type, period, period_start),
+    CONSTRAINT lab_limit_counters_period_check CHECK ((period = ANY (ARRAY['day'::text, 'month'::text])))
+);
+
+
+ALTER TABLE public.lab_limit_counters OWNER TO postgres;
+
+INSERT INTO public.lab_permissions(name, description) VALUES
+    ('user:tier_manage', 'Ubah tier limit transaksi user')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO public.lab_role_permissions(role, permission) VALUES
+    ('admin', 'user:tier_manage')
+ON CONFLICT DO NOTHING;
+
+--
+-- Name: lab_fun_limit_periods(); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_limit_periods(OUT day_start date, OUT month_start date)
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT (now() AT TIME ZONE 'Asia/Jakarta')::date,
+         date_trunc('month', now() AT TIME ZONE 'Asia/Jakarta')::date;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_limit_periods(OUT day_start date, OUT month_start date) OWNER TO postgres;
+
+--
+-- Name: lab_fun_limit_bump(text, uuid, text, text, date, numeric, numeric); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_limit_bump(p_scope text, p_scope_id uuid, p_txn_type text, p_period text, p_period_start date, p_amount numeric, p_max numeric) RETURNS void
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_used numeric;
+BEGIN
+  INSERT INTO lab_limit_counters(scope, scope_id, txn_type, period, period_start, used)
+  VALUES (p_scope, p_scope_id, p_txn_type, p_period, p_period_start, p_amount)
+  ON CONFLICT (scope, scope_id, txn_type, period, period_start)
+  DO UPDATE SET used = lab_limit_counters.used + EXCLUDED.used
+  RETURNING used INTO v_used;
+
+  IF p_max IS NOT NULL AND v_used > p_max THEN
+    IF p_period = 'day' THEN
+      RAISE EXCEPTION 'LIMIT_DAILY';
+    END IF;
+    RAISE EXCEPTION 'LIMIT_MONTHLY';
+  END IF;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_limit_bump(p_scope text, p_scope_id uuid, p_txn_type text, p_period text, p_period_start date, p_amount numeric, p_max numeric) OWNER TO postgres;
+
+--
+-- Name: lab_fun_limit_consume(uuid, uuid, text, numeric); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Dipanggil fungsi ledger sebelum saldo didebit; RAISE membatalkan seluruh transaksi.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_limit_consume(p_user_id uuid, p_account_id uuid, p_txn_type text, p_amount numeric) RETURNS void
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_tier   text;
+  v_limit  record;
+  v_day    date;
+  v_month  date;
+  v_id     uuid;
+BEGIN
+  SELECT tier INTO v_tier FROM lab_users WHERE id = p_user_id;
+  SELECT day_start, month_start INTO v_day, v_month FROM lab_fun_limit_periods();
+
+  FOR v_limit IN
+    SELECT scope, per_txn_max, daily_max, monthly_max
+      FROM lab_transaction_limits
+     WHERE tier = v_tier AND txn_type = p_txn_type
+     ORDER BY scope
+  LOOP
+    IF v_limit.per_txn_max IS NOT NULL AND p_amount > v_limit.per_txn_max THEN
+      RAISE EXCEPTION 'LIMIT_PER_TXN';
+    END IF;
+
+    v_id := CASE WHEN v_limit.scope = 'user' THEN p_user_id ELSE p_account_id END;
+    PERFORM lab_fun_limit_bump(v_limit.scope, v_id, p_txn_type, 'day', v_day, p_amount, v_limit.daily_max);
+    PERFORM lab_fun_limit_bump(v_limit.scope, v_id, p_txn_type, 'month', v_month, p_amount, v_limit.monthly_max);
+  END LOOP;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_limit_consume(p_user_id uuid, p_account_id uuid, p_txn_type text, p_amount numeric) OWNER TO postgres;
+
+--
+-- Name: lab_fun_account_limits(uuid, uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Limit & sisa kuota periode berjalan untuk satu rekening milik user.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_limits(p_user_id uuid, p_account_id uuid) RETURNS TABLE(txn_type text, scope text, per_txn_max numeric, daily_max numeric, daily_used numeric, monthly_max numeric, monthly_used numeric)
+    LANGUAGE plpgsql STABLE
+    AS $$
+DECLARE
+  v_owner uuid;
+  v_day   date;
+  v_month date;
+BEGIN
+  SELECT user_id INTO v_owner FROM lab_accounts WHERE id = p_account_id;
+  IF v_owner IS NULL OR v_owner <> p_user_id THEN
+    RAISE EXCEPTION 'ACCOUNT_NOT_OWNED';
+  END IF;
+  SELECT day_start, month_start INTO v_day, v_month FROM lab_fun_limit_periods();
+
+  RETURN QUERY
+  SELECT l.txn_type, l.scope, l.per_txn_max,
+         l.daily_max, COALESCE(d.used, 0)::numeric,
+         l.monthly_max, COALESCE(m.used, 0)::numeric
+    FROM lab_users u
+    JOIN lab_transaction_limits l ON l.tier = u.tier
+    LEFT JOIN lab_limit_counters d
+           ON d.scope = l.scope
+          AND d.scope_id = CASE WHEN l.scope = 'user' THEN p_user_id ELSE p_account_id END
+          AND d.txn_type = l.txn_type AND d.period = 'day' AND d.period_start = v_day
+    LEFT JOIN lab_limit_counters m
+           ON m.scope = l.scope
+          AND m.scope_id = CASE WHEN l.scope = 'user' THEN p_user_id ELSE p_account_id END
+          AND m.txn_type = l.txn_type AND m.period = 'month' AND m.period_start = v_month
+   WHERE u.id = p_user_id
+   ORDER BY l.txn_type, l.scope;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_limits(p_user_id uuid, p_account_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_set_user_tier(uuid, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_set_user_tier(p_user_id uuid, p_tier text) RETURNS text
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_prev text;
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM lab_limit_tiers WHERE name = p_tier) THEN
+    RAISE EXCEPTION 'TIER_INVALID';
+  END IF;
+
+  SELECT tier INTO v_prev FROM lab_users WHERE id = p_user_id FOR UPDATE;
+  IF NOT FOUND THEN
+    RAISE EXCEPTION 'USER_NOT_FOUND';
+  END IF;
+
+  UPDATE lab_users SET tier = p_tier, updated_at = now() WHERE id = p_user_id;
+  RETURN v_prev;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_set_user_tier(p_user_id uuid, p_tier text) OWNER TO postgres;
+
+
+--
+-- PIN rekening: yang disimpan hanya hash Argon2id (string PHC), di-hash & diverifikasi di aplikasi.
+-- Hash bcrypt peninggalan lama diganti Argon2id saat PIN berikutnya berhasil diverifikasi;
+-- yang tidak terpakai selama masa tenggang dihapus (pin_hash NULL = PIN wajib diatur ulang).
+-- Salah PIN beruntun → PIN rekening dikunci sementara.
+--
+
+--
+-- Name: lab_fun_account_pin_state(uuid, uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Hash PIN (NULL jika wajib diatur ulang) & kunci aktif; kosong jika rekening tidak ada
+-- atau bukan milik user.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_pin_state(p_user_id uuid, p_account_id uuid) RETURNS TABLE(pin_hash text, locked_until timestamp with time zone)
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT a.pin_hash,
+         CASE WHEN a.pin_locked_until > now() THEN a.pin_locked_until END
+    FROM lab_accounts a
+   WHERE a.id = p_account_id
+     AND a.user_id = p_user_id;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_pin_state(p_user_id uuid, p_account_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_account_pin_failure(uuid, integer, integer); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Catat satu salah PIN. Setelah p_lock_after kali beruntun, PIN dikunci p_lock_secs detik
+-- (mengembalikan waktu akhir kunci, NULL jika belum terkunci). Counter mulai lagi dari nol
+-- setelah kunci sebelumnya lewat.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_pin_failure(p_account_id uuid, p_lock_after integer, p_lock_secs integer) RETURNS timestamp with time zone
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_attempts integer;
+  v_locked   timestamp with time zone;
+  v_until    timestamp with time zone;
+BEGIN
+  SELECT pin_failed_attempts, pin_locked_until
+    INTO v_attempts, v_locked
+    FROM lab_accounts
+   WHERE id = p_account_id
+   FOR UPDATE;
+
+  IF NOT FOUND THEN
+    RETURN NULL;
+  END IF;
+
+  v_attempts := CASE WHEN v_locked IS NOT NULL AND v_locked <= now() THEN 1 ELSE v_attempts + 1 END;
+  v_until := CASE WHEN v_attempts >= p_lock_after THEN now() + make_interval(secs => p_lock_secs) END;
+
+  UPDATE lab_accounts
+     SET pin_failed_attempts = CASE WHEN v_until IS NULL THEN v_attempts ELSE 0 END,
+         pin_locked_until = v_until
+   WHERE id = p_account_id;
+
+  RETURN v_until;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_pin_failure(p_account_id uuid, p_lock_after integer, p_lock_secs integer) OWNER TO postgres;
+
+--
+-- Name: lab_fun_account_pin_success(uuid, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Reset counter salah PIN; p_rehash diisi jika hash lama (bcrypt) perlu diganti Argon2id.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_pin_success(p_account_id uuid, p_rehash text) RETURNS void
+    LANGUAGE sql
+    AS $$
+  UPDATE lab_accounts
+     SET pin_failed_attempts = 0,
+         pin_locked_until = NULL,
+         pin_hash = COALESCE(p_rehash, pin_hash),
+         pin_changed_at = CASE WHEN p_rehash IS NULL THEN pin_changed_at ELSE now() END
+   WHERE id = p_account_id
+     AND (pin_failed_attempts <> 0 OR pin_locked_until IS NOT NULL OR p_rehash IS NOT NULL);
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_pin_success(p_account_id uuid, p_rehash text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_expire_legacy_pins(interval); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Hapus hash bcrypt yang tidak di-upgrade dalam p_grace; pemilik wajib mengatur PIN baru
+-- lewat step-up. Mengembalikan jumlah rekening yang terkena.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_expire_legacy_pins(p_grace interval) RETURNS integer
+    LANGUAGE plpgsql
+    AS $_$
+DECLARE
+  v_count integer;
+BEGIN
+  UPDATE lab_accounts
+     SET pin_hash = NULL,
+         pin_failed_attempts = 0,
+         pin_locked_until = NULL,
+         pin_changed_at = now(),
+         updated_at = now()
+   WHERE pin_hash ~ '^\$2[aby]\$'
+     AND pin_changed_at <= now() - p_grace;
+  GET DIAGNOSTICS v_count = ROW_COUNT;
+  RETURN v_count;
+END; $_$;
+
+
+ALTER FUNCTION public.lab_fun_expire_legacy_pins(p_grace interval) OWNER TO postgres;
+
+--
+-- Rekening koran (statement) per periode tanggal Asia/Jakarta [from, to]. Saldo awal = jumlah
+-- mutasi jurnal sebelum periode; saldo berjalan dihitung dari jurnal, bukan dari balance_after.
+-- Setiap statement yang diterbitkan dicatat dengan hash verifikasinya.
+--
+
+--
+-- Name: lab_account_statements; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_account_statements (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    account_id uuid NOT NULL,
+    user_id uuid NOT NULL,
+    period_from date NOT NULL,
+    period_to date NOT NULL,
+    format text NOT NULL,
+    opening_balance numeric(20,2) NOT NULL,
+    closing_balance numeric(20,2) NOT NULL,
+    line_count integer NOT NULL,
+    sha256 bytea NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT lab_account_statements_pkey PRIMARY KEY (id),
+    CONSTRAINT lab_account_statements_account_id_fkey FOREIGN KEY (account_id) REFERENCES public.lab_accounts(id) ON DELETE CASCADE,
+    CONSTRAINT lab_account_statements_format_check CHECK ((format = ANY (ARRAY['pdf'::text, 'csv'::text])))
+);
+
+
+ALTER TABLE public.lab_account_statements OWNER TO postgres;
+
+CREATE INDEX IF NOT EXISTS lab_account_statements_sha256_idx ON public.lab_account_statements USING btree (sha256);
+
+--
+-- Name: lab_fun_account_statement_header(uuid, uuid, date); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Saldo awal rekening koran dihitung mundur dari saldo rekening saat ini (saldo - mutasi sejak
+-- awal periode), bukan dari jumlah seluruh jurnal: rekening lama yang dibuka dengan
+-- initial_balance tidak punya jurnal setoran awal. current_balance dipakai aplikasi untuk
+-- memastikan saldo akhir statement sama dengan saldo rekening jika periode sampai hari ini.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_statement_header(p_user_id uuid, p_account_id uuid, p_from date) RETURNS TABLE(account_no text, holder_name text, opening_balance numeric, current_balance numeric)
+    LANGUAGE plpgsql STABLE
+    AS $$
+DECLARE
+  v_owner uuid;
+BEGIN
+  SELECT a.user_id INTO v_owner FROM lab_accounts a WHERE a.id = p_account_id;
+  IF v_owner IS NULL OR v_owner <> p_user_id THEN
+    RAISE EXCEPTION 'ACCOUNT_NOT_OWNED';
+  END IF;
+
+  RETURN QUERY
+  SELECT a.account_no::text,
+         p.nama_lengkap,
+         (a.saldo - COALESCE((SELECT sum(j.debit - j.credit)
+                                FROM lab_journals j
+                               WHERE j.account_id = a.id
+                                 AND j.trx_time >= (p_from::timestamp AT TIME ZONE 'Asia/Jakarta')), 0))::numeric,
+         a.saldo::numeric
+    FROM lab_accounts a
+    LEFT JOIN lab_profiles p ON p.user_id = a.user_id
+   WHERE a.id = p_account_id;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_statement_header(p_user_id uuid, p_account_id uuid, p_from date) OWNER TO postgres;
+
+--
+-- Name: lab_fun_account_statement_lines(uuid, date, date, numeric); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_statement_lines(p_account_id uuid, p_from date, p_to date, p_opening numeric) RETURNS TABLE(journal_id uuid, trx_time timestamp with time zone, description text, debit numeric, credit numeric, balance numeric)
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT j.id, j.trx_time, j.description, j.debit, j.credit,
+         p_opening + sum(j.debit - j.credit) OVER (ORDER BY j.trx_time, j.id)
+    FROM lab_journals j
+   WHERE j.account_id = p_account_id
+     AND j.trx_time >= (p_from::timestamp AT TIME ZONE 'Asia/Jakarta')
+     AND j.trx_time < ((p_to + 1)::timestamp AT TIME ZONE 'Asia/Jakarta')
+   ORDER BY j.trx_time, j.id;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_statement_lines(p_account_id uuid, p_from date, p_to date, p_opening numeric) OWNER TO postgres;
+
+--
+-- Name: lab_fun_account_statement_issue(uuid, uuid, date, date, text, numeric, numeric, integer, bytea); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_statement_issue(p_user_id uuid, p_account_id uuid, p_from date, p_to date, p_format text, p_opening numeric, p_closing numeric, p_line_count integer, p_sha256 bytea) RETURNS uuid
+    LANGUAGE sql
+    AS $$
+  INSERT INTO lab_account_statements(account_id, user_id, period_from, period_to, format,
+                                     opening_balance, closing_balance, line_count, sha256)
+  VALUES (p_account_id, p_user_id, p_from, p_to, p_format, p_opening, p_closing, p_line_count, p_sha256)
+  RETURNING id;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_statement_issue(p_user_id uuid, p_account_id uuid, p_from date, p_to date, p_format text, p_opening numeric, p_closing numeric, p_line_count integer, p_sha256 bytea) OWNER TO postgres;
+
+--
+-- Batas lookup rekening (verifikasi nomor rekening / cari penerima) per pemanggil, jendela tetap
+-- per menit & per hari, untuk mencegah scraping data nasabah. Pemanggil = user app
+-- (`user:<uuid>`) atau sistem corp yang memakai request bertanda tangan (`client:<client_id>`),
+-- masing-masing dengan batasnya sendiri.
+--
+
+--
+-- Name: lab_account_lookup_throttle; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_account_lookup_throttle (
+    principal text NOT NULL,
+    window_kind text NOT NULL,
+    window_start timestamp with time zone NOT NULL,
+    hits integer DEFAULT 0 NOT NULL,
+    CONSTRAINT lab_account_lookup_throttle_pkey PRIMARY KEY (principal, window_kind, window_start),
+    CONSTRAINT lab_account_lookup_throttle_window_kind_check CHECK ((window_kind = ANY (ARRAY['minute'::text, 'day'::text])))
+);
+
+
+ALTER TABLE public.lab_account_lookup_throttle OWNER TO postgres;
+
+--
+-- Name: lab_fun_account_lookup_hit(text, integer, integer); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Catat satu lookup; FALSE jika batas menit atau hari terlampaui (lookup yang ditolak tetap dihitung).
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_account_lookup_hit(p_principal text, p_per_minute integer, p_per_day integer) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_minute integer;
+  v_day    integer;
+BEGIN
+  INSERT INTO lab_account_lookup_throttle(principal, window_kind, window_start, hits)
+  VALUES (p_principal, 'minute', date_trunc('minute', now()), 1)
+  ON CONFLICT (principal, window_kind, window_start)
+  DO UPDATE SET hits = lab_account_lookup_throttle.hits + 1
+  RETURNING hits INTO v_minute;
+
+  INSERT INTO lab_account_lookup_throttle(principal, window_kind, window_start, hits)
+  VALUES (p_principal, 'day', date_trunc('day', now()), 1)
+  ON CONFLICT (principal, window_kind, window_start)
+  DO UPDATE SET hits = lab_account_lookup_throttle.hits + 1
+  RETURNING hits INTO v_day;
+
+  DELETE FROM lab_account_lookup_throttle
+   WHERE principal = p_principal
+     AND window_start < now() - interval '2 days';
+
+  RETURN v_minute <= p_per_minute AND v_day <= p_per_day;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_account_lookup_hit(p_principal text, p_per_minute integer, p_per_day integer) OWNER TO postgres;
+
+--
+-- Daftar rekening tujuan tersimpan (beneficiary) & masa tenggang (cooling-off) untuk penerima
+-- baru: selama N jam sejak penerima pertama kali disimpan / ditransfer, total transfer ke
+-- penerima itu dibatasi per tier. Dicek di dalam lab_fun_transfer_by_no, jadi berlaku juga
+-- untuk transfer dengan nomor rekening yang diketik langsung.
+--
+
+--
+-- Name: lab_beneficiaries; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_beneficiaries (
+    id uuid DEFAULT gen_random_uuid() NOT NULL,
+    user_id uuid NOT NULL,
+    account_no text NOT NULL,
+    alias text,
+    verified_name text NOT NULL,
+    verified_at timestamp with time zone DEFAULT now() NOT NULL,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    CONSTRAINT lab_beneficiaries_pkey PRIMARY KEY (id),
+    CONSTRAINT lab_beneficiaries_user_account_key UNIQUE (user_id, account_no),
+    CONSTRAINT lab_beneficiaries_user_id_fkey FOREIGN KEY (user_id) REFERENCES public.lab_users(id) ON DELETE CASCADE
+);
+
+
+ALTER TABLE public.lab_beneficiaries OWNER TO postgres;
+
+--
+-- Name: lab_payee_cooling_off; Type: TABLE; Schema: public; Owner: postgres
+--
+-- Satu baris per (user, rekening tujuan) yang masih/pernah dalam masa tenggang. Tidak ikut
+-- terhapus saat beneficiary dihapus, supaya hapus-lalu-simpan-ulang tidak mereset kuota.
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_payee_cooling_off (
+    user_id uuid NOT NULL,
+    account_no text NOT NULL,
+    started_at timestamp with time zone DEFAULT now() NOT NULL,
+    amount_used numeric(20,2) DEFAULT 0 NOT NULL,
+    CONSTRAINT lab_payee_cooling_off_pkey PRIMARY KEY (user_id, account_no),
+    CONSTRAINT lab_payee_cooling_off_user_id_fkey FOREIGN KEY (user_id) REFERENCES public.lab_users(id) ON DELETE CASCADE
+);
+
+
+ALTER TABLE public.lab_payee_cooling_off OWNER TO postgres;
+
+--
+-- Name: lab_fun_payee_cooling_start(uuid, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Mulai masa tenggang kecuali penerima sudah lama dikenal (transfer pertama lebih tua dari
+-- masa tenggang tier) atau tier tanpa batas. Mengembalikan akhir masa tenggang (NULL = tidak ada).
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_payee_cooling_start(p_user_id uuid, p_account_no text) RETURNS timestamp with time zone
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_hours   integer;
+  v_max     numeric;
+  v_started timestamp with time zone;
+BEGIN
+  SELECT t.new_payee_cooling_hours, t.new_payee_cooling_max
+    INTO v_hours, v_max
+    FROM lab_users u
+    JOIN lab_limit_tiers t ON t.name = u.tier
+   WHERE u.id = p_user_id;
+
+  IF v_max IS NULL THEN
+    RETURN NULL;
+  END IF;
+
+  IF EXISTS (SELECT 1 FROM lab_transfer_counterparties c
+              WHERE c.user_id = p_user_id
+                AND c.account_no = p_account_no
+                AND c.first_transfer_at <= now() - make_interval(hours => v_hours)) THEN
+    RETURN NULL;
+  END IF;
+
+  INSERT INTO lab_payee_cooling_off(user_id, account_no)
+  VALUES (p_user_id, p_account_no)
+  ON CONFLICT (user_id, account_no) DO NOTHING;
+
+  SELECT started_at INTO v_started
+    FROM lab_payee_cooling_off
+   WHERE user_id = p_user_id AND account_no = p_account_no;
+
+  IF v_started <= now() - make_interval(hours => v_hours) THEN
+    RETURN NULL;
+  END IF;
+  RETURN v_started + make_interval(hours => v_hours);
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_payee_cooling_start(p_user_id uuid, p_account_no text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_payee_cooling_consume(uuid, uuid, text, numeric); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Dipanggil lab_fun_transfer_by_no sebelum saldo didebit; transfer ke rekening sendiri bebas.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_payee_cooling_consume(p_user_id uuid, p_to_owner uuid, p_account_no text, p_amount numeric) RETURNS void
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_until timestamp with time zone;
+  v_max   numeric;
+  v_used  numeric;
+BEGIN
+  IF p_to_owner = p_user_id THEN
+    RETURN;
+  END IF;
+
+  v_until := lab_fun_payee_cooling_start(p_user_id, p_account_no);
+  IF v_until IS NULL THEN
+    RETURN;
+  END IF;
+
+  SELECT t.new_payee_cooling_max INTO v_max
+    FROM lab_users u
+    JOIN lab_limit_tiers t ON t.name = u.tier
+   WHERE u.id = p_user_id;
+
+  SELECT amount_used INTO v_used
+    FROM lab_payee_cooling_off
+   WHERE user_id = p_user_id AND account_no = p_account_no
+   FOR UPDATE;
+
+  IF v_used + p_amount > v_max THEN
+    RAISE EXCEPTION 'LIMIT_COOLING_OFF';
+  END IF;
+
+  UPDATE lab_payee_cooling_off
+     SET amount_used = amount_used + p_amount
+   WHERE user_id = p_user_id AND account_no = p_account_no;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_payee_cooling_consume(p_user_id uuid, p_to_owner uuid, p_account_no text, p_amount numeric) OWNER TO postgres;
+
+--
+-- Name: lab_fun_beneficiaries(uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_beneficiaries(p_user_id uuid) RETURNS TABLE(id uuid, account_no text, alias text, verified_name text, verified_at timestamp with time zone, created_at timestamp with time zone, cooling_off_until timestamp with time zone, cooling_off_remaining numeric)
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT b.id, b.account_no, b.alias, b.verified_name, b.verified_at, b.created_at,
+         c.until,
+         CASE WHEN c.until IS NOT NULL THEN GREATEST(t.new_payee_cooling_max - c.amount_used, 0) END
+    FROM lab_beneficiaries b
+    JOIN lab_users u ON u.id = b.user_id
+    JOIN lab_limit_tiers t ON t.name = u.tier
+    LEFT JOIN LATERAL (
+      SELECT co.amount_used, co.started_at + make_interval(hours => t.new_payee_cooling_hours) AS until
+        FROM lab_payee_cooling_off co
+       WHERE co.user_id = b.user_id
+         AND co.account_no = b.account_no
+         AND t.new_payee_cooling_max IS NOT NULL
+         AND co.started_at + make_interval(hours => t.new_payee_cooling_hours) > now()
+    ) c ON true
+   WHERE b.user_id = p_user_id
+   ORDER BY lower(COALESCE(b.alias, b.verified_name)), b.created_at;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_beneficiaries(p_user_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_beneficiary_add(uuid, text, text, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_beneficiary_add(p_user_id uuid, p_account_no text, p_alias text, p_verified_name text) RETURNS uuid
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_id uuid;
+BEGIN
+  IF EXISTS (SELECT 1 FROM lab_accounts a WHERE a.account_no = p_account_no AND a.user_id = p_user_id) THEN
+    RAISE EXCEPTION 'BENEFICIARY_OWN_ACCOUNT';
+  END IF;
+
+  INSERT INTO lab_beneficiaries(user_id, account_no, alias, verified_name)
+  VALUES (p_user_id, p_account_no, NULLIF(btrim(p_alias), ''), p_verified_name)
+  ON CONFLICT (user_id, account_no) DO NOTHING
+  RETURNING id INTO v_id;
+
+  IF v_id IS NULL THEN
+    RAISE EXCEPTION 'BENEFICIARY_EXISTS';
+  END IF;
+
+  PERFORM lab_fun_payee_cooling_start(p_user_id, p_account_no);
+  RETURN v_id;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_beneficiary_add(p_user_id uuid, p_account_no text, p_alias text, p_verified_name text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_beneficiary_delete(uuid, uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_beneficiary_delete(p_user_id uuid, p_id uuid) RETURNS text
+    LANGUAGE sql
+    AS $$
+  DELETE FROM lab_beneficiaries
+   WHERE id = p_id AND user_id = p_user_id
+  RETURNING account_no;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_beneficiary_delete(p_user_id uuid, p_id uuid) OWNER TO postgres;
+
+--
+-- Throttle kode 2FA per user (scope 'totp', key = user_id) memakai tabel & algoritma backoff
+-- lab_login_throttle. Counter ini tidak di-reset oleh login password yang sukses, hanya oleh
+-- kode 2FA yang benar. mfa_token (langkah kedua login) hanya bisa dipakai sekali (per jti).
+--
+
+--
+-- Name: lab_fun_throttle_bump(text, text, integer, integer, integer, integer); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Catat satu kegagalan untuk satu (scope, key). Setelah p_free gagal, jeda 2^(n-free) detik
+-- (maks p_backoff_cap_secs); setelah p_lock_at gagal, lockout p_lock_secs.
+-- Counter mulai dari nol lagi jika lockout sebelumnya sudah lewat atau tidak ada kegagalan 24 jam.
+CREATE OR REPLACE FUNCTION public.lab_fun_throttle_bump(p_scope text, p_key text, p_free integer, p_lock_at integer, p_lock_secs integer, p_backoff_cap_secs integer) RETURNS timestamp with time zone
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_failures integer;
+  v_until    timestamp with time zone;
+  v_locked   boolean;
+BEGIN
+  INSERT INTO lab_login_throttle(scope, key)
+  VALUES (p_scope, p_key)
+  ON CONFLICT (scope, key) DO NOTHING;
+
+  SELECT CASE
+           WHEN (t.locked AND t.blocked_until <= now())
+             OR t.last_failure_at < now() - interval '24 hours' THEN 1
+           ELSE t.failures + 1
+         END
+    INTO v_failures
+    FROM lab_login_throttle t
+   WHERE t.scope = p_scope AND t.key = p_key
+   FOR UPDATE;
+
+  v_locked := v_failures >= p_lock_at;
+  v_until := CASE
+    WHEN v_locked THEN now() + make_interval(secs => p_lock_secs)
+    WHEN v_failures > p_free THEN
+      now() + make_interval(secs => least(power(2, v_failures - p_free), p_backoff_cap_secs))
+    ELSE NULL
+  END;
+
+  UPDATE lab_login_throttle t
+     SET failures = v_failures,
+         last_failure_at = now(),
+         blocked_until = v_until,
+         locked = v_locked
+   WHERE t.scope = p_scope AND t.key = p_key;
+
+  RETURN v_until;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_throttle_bump(p_scope text, p_key text, p_free integer, p_lock_at integer, p_lock_secs integer, p_backoff_cap_secs integer) OWNER TO postgres;
+
+--
+-- Name: lab_fun_totp_blocked_until(uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_totp_blocked_until(p_user_id uuid) RETURNS timestamp with time zone
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT t.blocked_until
+    FROM lab_login_throttle t
+   WHERE t.scope = 'totp'
+     AND t.key = p_user_id::text
+     AND t.blocked_until > now();
+$$;
+
+
+ALTER FUNCTION public.lab_fun_totp_blocked_until(p_user_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_totp_failure(uuid, integer, integer, integer, integer); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_totp_failure(p_user_id uuid, p_free integer, p_lock_at integer, p_lock_secs integer, p_backoff_cap_secs integer) RETURNS TABLE(blocked_until timestamp with time zone, locked boolean)
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  blocked_until := lab_fun_throttle_bump('totp', p_user_id::text, p_free, p_lock_at, p_lock_secs, p_backoff_cap_secs);
+  SELECT t.locked INTO locked
+    FROM lab_login_throttle t
+   WHERE t.scope = 'totp' AND t.key = p_user_id::text;
+  RETURN NEXT;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_totp_failure(p_user_id uuid, p_free integer, p_lock_at integer, p_lock_secs integer, p_backoff_cap_secs integer) OWNER TO postgres;
+
+--
+-- Name: lab_fun_totp_success(uuid); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_totp_success(p_user_id uuid) RETURNS void
+    LANGUAGE sql
+    AS $$
+  DELETE FROM lab_login_throttle
+   WHERE scope = 'totp' AND key = p_user_id::text;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_totp_success(p_user_id uuid) OWNER TO postgres;
+
+--
+-- Name: lab_fun_mfa_pending_consume(uuid, timestamp with time zone); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+-- Tandai jti mfa_token sudah dipakai (disimpan di daftar jti dicabut sampai token kedaluwarsa);
+-- FALSE jika sudah pernah dipakai.
+CREATE OR REPLACE FUNCTION public.lab_fun_mfa_pending_consume(p_jti uuid, p_expires_at timestamp with time zone) RETURNS boolean
+    LANGUAGE plpgsql
+    AS $$
+BEGIN
+  INSERT INTO lab_revoked_access_tokens(jti, expires_at)
+  VALUES (p_jti, p_expires_at)
+  ON CONFLICT (jti) DO NOTHING;
+  RETURN FOUND;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_mfa_pending_consume(p_jti uuid, p_expires_at timestamp with time zone) OWNER TO postgres;
+
+--
+-- Name: lab_fun_limit_release(uuid, uuid, text, numeric, timestamp with time zone); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Kembalikan kuota yang dipakai debit yang di-reversal; p_charged_at = waktu debit asal,
+-- supaya yang dikurangi counter hari/bulan debit tersebut.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_limit_release(p_user_id uuid, p_account_id uuid, p_txn_type text, p_amount numeric, p_charged_at timestamp with time zone) RETURNS void
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_day   date := (p_charged_at AT TIME ZONE 'Asia/Jakarta')::date;
+  v_month date := date_trunc('month', p_charged_at AT TIME ZONE 'Asia/Jakarta')::date;
+BEGIN
+  IF p_amount IS NULL OR p_amount <= 0 THEN
+    RETURN;
+  END IF;
+
+  UPDATE lab_limit_counters
+     SET used = GREATEST(used - p_amount, 0)
+   WHERE txn_type = p_txn_type
+     AND ((scope = 'user' AND scope_id = p_user_id)
+       OR (scope = 'account' AND scope_id = p_account_id))
+     AND ((period = 'day' AND period_start = v_day)
+       OR (period = 'month' AND period_start = v_month));
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_limit_release(p_user_id uuid, p_account_id uuid, p_txn_type text, p_amount numeric, p_charged_at timestamp with time zone) OWNER TO postgres;
+
+--
+-- Reversal debit PPOB yang gagal di Digiflazz: pengembalian dana & pelepasan kuota limit `ppob`
+-- terjadi dalam satu transaksi. Jika reversal gagal, baris tetap 'pending' (dengan error
+-- terakhir) sampai dicoba ulang; satu debit hanya bisa di-reversal sekali.
+--
+
+--
+-- Name: lab_ppob_reversals; Type: TABLE; Schema: public; Owner: postgres
+--
+
+CREATE TABLE IF NOT EXISTS public.lab_ppob_reversals (
+    charge_journal_id uuid NOT NULL,
+    user_id uuid NOT NULL,
+    account_id uuid NOT NULL,
+    amount numeric(20,2) NOT NULL,
+    charged_at timestamp with time zone NOT NULL,
+    ref_id text NOT NULL,
+    status text DEFAULT 'pending'::text NOT NULL,
+    attempts integer DEFAULT 0 NOT NULL,
+    last_error text,
+    reversal_journal_id uuid,
+    created_at timestamp with time zone DEFAULT now() NOT NULL,
+    reversed_at timestamp with time zone,
+    CONSTRAINT lab_ppob_reversals_pkey PRIMARY KEY (charge_journal_id),
+    CONSTRAINT lab_ppob_reversals_status_check CHECK ((status = ANY (ARRAY['pending'::text, 'done'::text])))
+);
+
+
+ALTER TABLE public.lab_ppob_reversals OWNER TO postgres;
+
+CREATE INDEX IF NOT EXISTS lab_ppob_reversals_pending_idx ON public.lab_ppob_reversals USING btree (created_at) WHERE (status = 'pending'::text);
+
+--
+-- Name: lab_fun_ppob_reverse(uuid, uuid, uuid, numeric, timestamp with time zone, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Kredit balik debit PPOB + lepas kuota limitnya, atomik. REVERSAL_DONE jika debit ini
+-- sudah pernah di-reversal.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_ppob_reverse(p_charge_journal_id uuid, p_user_id uuid, p_account_id uuid, p_amount numeric, p_charged_at timestamp with time zone, p_ref_id text) RETURNS TABLE(journal_id uuid, balance_after numeric)
+    LANGUAGE plpgsql
+    AS $$
+DECLARE
+  v_status text;
+  v_dep    record;
+BEGIN
+  INSERT INTO lab_ppob_reversals(charge_journal_id, user_id, account_id, amount, charged_at, ref_id)
+  VALUES (p_charge_journal_id, p_user_id, p_account_id, p_amount, p_charged_at, p_ref_id)
+  ON CONFLICT (charge_journal_id) DO NOTHING;
+
+  SELECT r.status INTO v_status
+    FROM lab_ppob_reversals r
+   WHERE r.charge_journal_id = p_charge_journal_id
+   FOR UPDATE;
+  IF v_status = 'done' THEN
+    RAISE EXCEPTION 'REVERSAL_DONE';
+  END IF;
+
+  SELECT d.journal_id, d.balance_after INTO v_dep
+    FROM lab_fun_deposit(p_user_id, p_account_id, p_amount,
+                         format('Reversal dana Sejumlah %s Berhasil', p_amount), 'REVERSAL DANA') d;
+
+  PERFORM lab_fun_limit_release(p_user_id, p_account_id, 'ppob', p_amount, p_charged_at);
+
+  UPDATE lab_ppob_reversals
+     SET status = 'done',
+         attempts = attempts + 1,
+         last_error = NULL,
+         reversal_journal_id = v_dep.journal_id,
+         reversed_at = now()
+   WHERE charge_journal_id = p_charge_journal_id;
+
+  journal_id    := v_dep.journal_id;
+  balance_after := v_dep.balance_after;
+  RETURN NEXT;
+END;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_ppob_reverse(p_charge_journal_id uuid, p_user_id uuid, p_account_id uuid, p_amount numeric, p_charged_at timestamp with time zone, p_ref_id text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_ppob_reversal_failed(uuid, uuid, uuid, numeric, timestamp with time zone, text, text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+-- Catat reversal yang gagal sebagai 'pending' untuk dicoba ulang.
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_ppob_reversal_failed(p_charge_journal_id uuid, p_user_id uuid, p_account_id uuid, p_amount numeric, p_charged_at timestamp with time zone, p_ref_id text, p_error text) RETURNS void
+    LANGUAGE sql
+    AS $$
+  INSERT INTO lab_ppob_reversals(charge_journal_id, user_id, account_id, amount, charged_at, ref_id, attempts, last_error)
+  VALUES (p_charge_journal_id, p_user_id, p_account_id, p_amount, p_charged_at, p_ref_id, 1, p_error)
+  ON CONFLICT (charge_journal_id) DO UPDATE
+     SET attempts = lab_ppob_reversals.attempts + 1,
+         last_error = EXCLUDED.last_error
+   WHERE lab_ppob_reversals.status = 'pending';
+$$;
+
+
+ALTER FUNCTION public.lab_fun_ppob_reversal_failed(p_charge_journal_id uuid, p_user_id uuid, p_account_id uuid, p_amount numeric, p_charged_at timestamp with time zone, p_ref_id text, p_error text) OWNER TO postgres;
+
+--
+-- Name: lab_fun_list_ppob_reversals(text); Type: FUNCTION; Schema: public; Owner: postgres
+--
+
+CREATE OR REPLACE FUNCTION public.lab_fun_list_ppob_reversals(p_status text) RETURNS TABLE(charge_journal_id uuid, user_id uuid, account_id uuid, amount numeric, charged_at timestamp with time zone, ref_id text, status text, attempts integer, last_error text, reversal_journal_id uuid, created_at timestamp with time zone, reversed_at timestamp with time zone)
+    LANGUAGE sql STABLE
+    AS $$
+  SELECT r.charge_journal_id, r.user_id, r.account_id, r.amount, r.charged_at, r.ref_id, r.status,
+         r.attempts, r.last_error, r.reversal_journal_id, r.created_at, r.reversed_at
+    FROM lab_ppob_reversals r
+   WHERE p_status IS NULL OR r.status = p_status
+   ORDER BY r.created_at;
+$$;
+
+
+ALTER FUNCTION public.lab_fun_list_ppob_reversals(p_status text) OWNER TO postgres;
+
 --
 -- PostgreSQL database dump complete
 --
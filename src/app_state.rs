@@ -3,6 +3,15 @@ use std::sync::Arc;
 use serde::Deserialize;
 use sqlx::PgPool;
 
+use crate::{
+    delivery::DeliveryChannel,
+    jwt_keys::JwtKeys,
+    middleware::{request_context::TrustedProxy, request_signature::SigningClients},
+    password_policy::PasswordPolicy,
+    revoked_jti::RevokedJtiCache,
+    routes::step_up::StepUpPolicy,
+};
+
 #[derive(Clone, Deserialize)]
 pub struct FirebaseServiceAccount {
     pub project_id: String,
@@ -15,9 +24,15 @@ pub struct FirebaseServiceAccount {
 pub struct AppState {
     pub pool: PgPool,
     pub pool2: PgPool,
-    pub jwt_secret: Arc<String>,
+    pub jwt_keys: Arc<JwtKeys>,
     pub firebase: Option<Arc<FirebaseServiceAccount>>,
     pub digiflazz: DigiflazzConfig,
+    pub delivery: DeliveryChannel,
+    pub trusted_proxies: Arc<Vec<TrustedProxy>>,
+    pub signing_clients: Arc<SigningClients>,
+    pub password_policy: Arc<PasswordPolicy>,
+    pub revoked_jtis: Arc<RevokedJtiCache>,
+    pub step_up_policy: Arc<StepUpPolicy>,
 }
 
 pub type SharedState = Arc<AppState>;
@@ -0,0 +1,51 @@
+use std::path::PathBuf;
+
+use tokio::io::AsyncWriteExt;
+
+use crate::errors::ApiError;
+
+/// Kanal pengiriman pesan keluar ke user (token reset password, dsb).
+/// Dipilih lewat env `DELIVERY_CHANNEL`: `log` (default) atau `file:/path/outbox.log`.
+#[derive(Clone)]
+pub enum DeliveryChannel {
+    /// Tulis ke tracing log (untuk dev)
+    Log,
+    /// Append ke file outbox (untuk dev/QA)
+    File(PathBuf),
+}
+
+impl DeliveryChannel {
+    pub fn from_env_value(raw: &str) -> Self {
+        match raw.trim().strip_prefix("file:") {
+            Some(path) if !path.trim().is_empty() => DeliveryChannel::File(PathBuf::from(path)),
+            _ => DeliveryChannel::Log,
+        }
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), ApiError> {
+        match self {
+            DeliveryChannel::Log => {
+                tracing::info!(to = %to, subject = %subject, "delivery: {}", body);
+                Ok(())
+            }
+            DeliveryChannel::File(path) => {
+                let line = serde_json::json!({
+                    "to": to,
+                    "subject": subject,
+                    "body": body,
+                    "sent_at": chrono::Utc::now(),
+                });
+                let mut file = tokio::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .await
+                    .map_err(|e| ApiError::Internal(format!("delivery file: {}", e)))?;
+                file.write_all(format!("{}\n", line).as_bytes())
+                    .await
+                    .map_err(|e| ApiError::Internal(format!("delivery file: {}", e)))?;
+                Ok(())
+            }
+        }
+    }
+}
@@ -13,6 +13,8 @@ pub enum ApiError {
     Internal(String),
     #[error("{0}")]
     NotFound(String),
+    #[error("too_many_requests")]
+    TooManyRequests(String),
 }
 
 impl From<sqlx::Error> for ApiError {
@@ -29,6 +31,7 @@ impl From<ApiError> for (StatusCode, String) {
             ApiError::Forbidden(m) => (StatusCode::FORBIDDEN, m),
             ApiError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             ApiError::Internal(m) => (StatusCode::INTERNAL_SERVER_ERROR, m),
+            ApiError::TooManyRequests(m) => (StatusCode::TOO_MANY_REQUESTS, m),
         }
     }
 }
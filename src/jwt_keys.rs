@@ -0,0 +1,183 @@
+use std::{collections::HashMap, path::Path};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData,
+    Validation,
+};
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair, RsaPublicKeyComponents};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::errors::ApiError;
+
+/// Jenis token yang ditandatangani keyring: header `typ` & klaim `aud` diisi saat sign dan
+/// dicocokkan saat verify, jadi token satu jenis tidak bisa dipakai sebagai jenis lain
+/// (mis. mfa_token atau token undangan sebagai access token).
+pub trait TokenKind {
+    const TYP: &'static str;
+    const AUD: &'static str;
+}
+
+/// Satu kunci penandatangan JWT, diidentifikasi oleh `kid`
+struct SigningKey {
+    alg: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    /// Representasi JWK publik (tanpa bagian privat) untuk /.well-known/jwks.json
+    jwk: serde_json::Value,
+}
+
+/// Keyring JWT asimetris (RS256 / EdDSA).
+///
+/// Semua kunci di keyring valid untuk verifikasi; hanya `active_kid` yang dipakai
+/// menandatangani token baru. Rotasi: tambah file kunci baru → restart → ganti
+/// `JWT_ACTIVE_KID` → hapus kunci lama setelah token terakhir yang ditandatanganinya expired.
+pub struct JwtKeys {
+    active_kid: String,
+    keys: HashMap<String, SigningKey>,
+}
+
+impl JwtKeys {
+    /// Muat semua `<kid>.pem` (private key PKCS#8 RSA/Ed25519, atau PKCS#1 RSA) dari direktori
+    pub fn load_dir(dir: &Path, active_kid: &str) -> anyhow::Result<Self> {
+        let mut keys = HashMap::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("pem") {
+                continue;
+            }
+            let Some(kid) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .map(str::to_string)
+            else {
+                continue;
+            };
+            let raw = std::fs::read(&path)?;
+            let key = SigningKey::from_pem(&kid, &raw)
+                .map_err(|e| anyhow::anyhow!("jwt key {}: {}", path.display(), e))?;
+            keys.insert(kid, key);
+        }
+
+        if !keys.contains_key(active_kid) {
+            anyhow::bail!(
+                "JWT_ACTIVE_KID '{}' not found in {}",
+                active_kid,
+                dir.display()
+            );
+        }
+        Ok(JwtKeys {
+            active_kid: active_kid.to_string(),
+            keys,
+        })
+    }
+
+    /// Tandatangani claims dengan kunci aktif (header membawa `kid` & `typ`, payload `aud`)
+    pub fn sign<T: Serialize + TokenKind>(&self, claims: &T) -> Result<String, ApiError> {
+        let key = &self.keys[&self.active_kid];
+        let mut header = Header::new(key.alg);
+        header.typ = Some(T::TYP.to_string());
+        header.kid = Some(self.active_kid.clone());
+
+        let mut payload =
+            serde_json::to_value(claims).map_err(|e| ApiError::Internal(e.to_string()))?;
+        let Some(fields) = payload.as_object_mut() else {
+            return Err(ApiError::Internal("jwt claims must be an object".into()));
+        };
+        fields.insert("aud".into(), T::AUD.into());
+        encode(&header, &payload, &key.encoding).map_err(|e| ApiError::Internal(e.to_string()))
+    }
+
+    /// Verifikasi token dengan kunci sesuai `kid` di header; algoritma dikunci per kunci,
+    /// `typ` & `aud` harus sesuai jenis token `T`
+    pub fn verify<T: DeserializeOwned + TokenKind>(
+        &self,
+        token: &str,
+    ) -> Result<TokenData<T>, jsonwebtoken::errors::Error> {
+        use jsonwebtoken::errors::ErrorKind;
+
+        let header = decode_header(token)?;
+        let key = header
+            .kid
+            .as_deref()
+            .and_then(|kid| self.keys.get(kid))
+            .ok_or_else(|| jsonwebtoken::errors::Error::from(ErrorKind::InvalidToken))?;
+        if header.alg != key.alg {
+            return Err(ErrorKind::InvalidAlgorithm.into());
+        }
+        if header.typ.as_deref() != Some(T::TYP) {
+            return Err(ErrorKind::InvalidToken.into());
+        }
+        let mut validation = Validation::new(key.alg);
+        validation.set_audience(&[T::AUD]);
+        decode::<T>(token, &key.decoding, &validation)
+    }
+
+    /// JWK Set publik untuk verifikasi token oleh service lain
+    pub fn jwks(&self) -> serde_json::Value {
+        let mut kids: Vec<&String> = self.keys.keys().collect();
+        kids.sort();
+        let keys: Vec<&serde_json::Value> = kids.iter().map(|kid| &self.keys[*kid].jwk).collect();
+        serde_json::json!({ "keys": keys })
+    }
+}
+
+impl SigningKey {
+    fn from_pem(kid: &str, raw: &[u8]) -> anyhow::Result<Self> {
+        let pem = pem::parse(raw)?;
+        let der = pem.contents();
+
+        match pem.tag() {
+            "PRIVATE KEY" => {
+                if let Ok(pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+                    return Self::ed25519(kid, raw, pair.public_key().as_ref());
+                }
+                let pair = RsaKeyPair::from_pkcs8(der)
+                    .map_err(|e| anyhow::anyhow!("unsupported private key: {}", e))?;
+                Self::rsa(kid, raw, &pair)
+            }
+            "RSA PRIVATE KEY" => {
+                let pair = RsaKeyPair::from_der(der)
+                    .map_err(|e| anyhow::anyhow!("invalid rsa key: {}", e))?;
+                Self::rsa(kid, raw, &pair)
+            }
+            other => anyhow::bail!("unsupported pem block '{}'", other),
+        }
+    }
+
+    fn ed25519(kid: &str, pem: &[u8], public: &[u8]) -> anyhow::Result<Self> {
+        let x = URL_SAFE_NO_PAD.encode(public);
+        Ok(SigningKey {
+            alg: Algorithm::EdDSA,
+            encoding: EncodingKey::from_ed_pem(pem)?,
+            decoding: DecodingKey::from_ed_der(public),
+            jwk: serde_json::json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "use": "sig",
+                "alg": "EdDSA",
+                "kid": kid,
+                "x": x,
+            }),
+        })
+    }
+
+    fn rsa(kid: &str, pem: &[u8], pair: &RsaKeyPair) -> anyhow::Result<Self> {
+        let public = RsaPublicKeyComponents::<Vec<u8>>::from(pair.public());
+        let n = URL_SAFE_NO_PAD.encode(&public.n);
+        let e = URL_SAFE_NO_PAD.encode(&public.e);
+        Ok(SigningKey {
+            alg: Algorithm::RS256,
+            encoding: EncodingKey::from_rsa_pem(pem)?,
+            decoding: DecodingKey::from_rsa_components(&n, &e)?,
+            jwk: serde_json::json!({
+                "kty": "RSA",
+                "use": "sig",
+                "alg": "RS256",
+                "kid": kid,
+                "n": n,
+                "e": e,
+            }),
+        })
+    }
+}
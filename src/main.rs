@@ -1,8 +1,8 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
     middleware::from_fn_with_state,
-    routing::{get, patch, post},
+    routing::{delete, get, patch, post},
     Router,
 };
 use dotenvy::dotenv;
@@ -13,30 +13,55 @@ use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
 
 mod app_state;
+mod delivery;
 mod errors;
+mod jwt_keys;
 mod models;
+mod money;
+mod password_policy;
+mod pdf;
+mod revoked_jti;
+mod totp;
 mod utils;
 
 mod middleware {
     pub mod auth;
     pub mod rbac;
+    pub mod request_context;
+    pub mod request_signature;
 }
 
 mod routes {
+    pub mod account_lifecycle;
     pub mod accounts;
     pub mod admin;
+    pub mod api_keys;
     pub mod auth;
+    pub mod beneficiaries;
     pub mod cash;
     pub mod disbursment;
     pub mod digiflaz;
     pub mod investment;
+    pub mod invitations;
     pub mod journals;
+    pub mod limits;
+    pub mod login_locks;
+    pub mod mfa;
     pub mod notifications;
     pub mod profile;
+    pub mod sessions;
+    pub mod statements;
+    pub mod step_up;
     pub mod transfers;
 }
 
 use app_state::{AppState, DigiflazzConfig};
+use delivery::DeliveryChannel;
+use jwt_keys::JwtKeys;
+use middleware::{request_context::TrustedProxy, request_signature::SigningClients};
+use models::perm;
+use password_policy::PasswordPolicy;
+use revoked_jti::RevokedJtiCache;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
@@ -48,7 +73,9 @@ async fn main() -> anyhow::Result<()> {
 
     let db_url = std::env::var("DATABASE_URL")?;
     let db_url2 = std::env::var("DATABASE_URL2")?;
-    let jwt_secret = std::env::var("JWT_SECRET")?;
+    let jwt_keys_dir = std::env::var("JWT_KEYS_DIR").unwrap_or_else(|_| "screets/jwt".to_string());
+    let jwt_active_kid = std::env::var("JWT_ACTIVE_KID")?;
+    let jwt_keys = JwtKeys::load_dir(std::path::Path::new(&jwt_keys_dir), &jwt_active_kid)?;
     let digiflazz_username = std::env::var("DIGIFLAZZ_USERNAME")?.trim().to_string();
     let digiflazz_dev_key = std::env::var("DIGIFLAZZ_DEV_KEY")?.trim().to_string();
     let digiflazz_prod_key = std::env::var("DIGIFLAZZ_PROD_KEY")?.trim().to_string();
@@ -56,6 +83,16 @@ async fn main() -> anyhow::Result<()> {
     let digiflazz_use_production =
         matches!(digiflazz_mode.as_str(), "prod" | "production" | "live");
 
+    let delivery = DeliveryChannel::from_env_value(
+        &std::env::var("DELIVERY_CHANNEL").unwrap_or_else(|_| "log".to_string()),
+    );
+
+    let trusted_proxies =
+        TrustedProxy::parse_list(&std::env::var("TRUSTED_PROXIES").unwrap_or_default());
+
+    let signing_clients =
+        SigningClients::parse(&std::env::var("SIGNED_CLIENTS").unwrap_or_default());
+
     let pool = PgPoolOptions::new()
         .max_connections(10)
         .connect(&db_url)
@@ -65,6 +102,12 @@ async fn main() -> anyhow::Result<()> {
         .connect(&db_url2)
         .await?;
 
+    let revoked_jtis = Arc::new(RevokedJtiCache::default());
+    revoked_jtis.spawn_listener(pool.clone());
+    routes::account_lifecycle::spawn_dormancy_job(pool.clone());
+    routes::accounts::spawn_legacy_pin_expiry_job(pool.clone());
+    routes::login_locks::spawn_throttle_purge_job(pool.clone());
+
     let firebase_path = std::env::var("FIREBASE_SERVICE_ACCOUNT")
         .unwrap_or_else(|_| "screets/my-firebase-adminsdk.json".to_string());
     let firebase = match std::fs::read_to_string(&firebase_path) {
@@ -84,7 +127,7 @@ async fn main() -> anyhow::Result<()> {
     let state = Arc::new(AppState {
         pool,
         pool2,
-        jwt_secret: Arc::new(jwt_secret),
+        jwt_keys: Arc::new(jwt_keys),
         firebase,
         digiflazz: DigiflazzConfig {
             username: digiflazz_username,
@@ -92,6 +135,12 @@ async fn main() -> anyhow::Result<()> {
             prod_key: digiflazz_prod_key,
             use_production: digiflazz_use_production,
         },
+        delivery,
+        trusted_proxies: Arc::new(trusted_proxies),
+        signing_clients: Arc::new(signing_clients),
+        password_policy: Arc::new(PasswordPolicy::from_env()),
+        revoked_jtis,
+        step_up_policy: Arc::new(routes::step_up::StepUpPolicy::from_env()),
     });
 
     let cors = CorsLayer::new()
@@ -102,6 +151,7 @@ async fn main() -> anyhow::Result<()> {
     // === Public (tanpa Authorization) ===
     let public = Router::new()
         .route("/health", get(|| async { "ok" }))
+        .route("/.well-known/jwks.json", get(routes::auth::jwks))
         .route("/journals/:id", get(routes::journals::get_journal_public))
         .route(
             "/journals/public",
@@ -111,24 +161,53 @@ async fn main() -> anyhow::Result<()> {
             "/journals/list_all",
             get(routes::journals::list_journals_list_all),
         )
-        .route("/accounts/verify", post(routes::accounts::verify_account))
         .route(
-            "/notifications/send-public",
-            post(routes::notifications::send_notification_public),
+            "/accounts/verify",
+            post(routes::accounts::verify_account).route_layer(from_fn_with_state(
+                state.clone(),
+                middleware::request_signature::require_signed_request_or_user,
+            )),
         );
 
     // === Auth endpoints (juga public) ===
     let auth_routes = Router::new()
         .route("/register", post(routes::auth::register))
         .route("/login", post(routes::auth::login))
+        .route("/login/mfa", post(routes::mfa::login_mfa))
         .route("/refresh", post(routes::auth::refresh))
-        .route("/password_reset", post(routes::auth::password_reset))
-        .route("/check_email", post(routes::auth::check_email));
+        .route(
+            "/password_reset/request",
+            post(routes::auth::password_reset_request),
+        )
+        .route(
+            "/password_reset/confirm",
+            post(routes::auth::password_reset_confirm),
+        )
+        .route("/check_email", post(routes::auth::check_email))
+        .route(
+            "/invitations/accept",
+            post(routes::invitations::accept_invitation),
+        );
 
     // === Protected (wajib Authorization) ===
     let protected = Router::new()
         .route("/me", get(me))
         .route("/auth/logout/:token_id", post(routes::auth::logout))
+        .route("/auth/change_password", post(routes::auth::change_password))
+        .route(
+            "/auth/step_up/challenge",
+            post(routes::step_up::create_challenge),
+        )
+        .route(
+            "/auth/sessions",
+            get(routes::sessions::list_sessions).delete(routes::sessions::revoke_all_sessions),
+        )
+        .route(
+            "/auth/sessions/:token_id",
+            delete(routes::sessions::revoke_session),
+        )
+        .route("/auth/mfa/enroll", post(routes::mfa::enroll))
+        .route("/auth/mfa/confirm", post(routes::mfa::confirm))
         .route(
             "/profile",
             get(routes::profile::get_profile).put(routes::profile::upsert_profile),
@@ -139,18 +218,53 @@ async fn main() -> anyhow::Result<()> {
             "/accounts/:account_id/pin",
             patch(routes::accounts::update_account_pin),
         )
+        .route(
+            "/accounts/:account_id/limits",
+            get(routes::limits::get_account_limits),
+        )
+        .route(
+            "/accounts/:account_id/statement",
+            get(routes::statements::get_account_statement),
+        )
+        .route(
+            "/accounts/:account_id/freeze",
+            post(routes::account_lifecycle::freeze_account),
+        )
+        .route(
+            "/accounts/:account_id/unfreeze",
+            post(routes::account_lifecycle::unfreeze_account),
+        )
+        .route(
+            "/accounts/:account_id/close",
+            post(routes::account_lifecycle::close_account),
+        )
         .route(
             "/journals",
             post(routes::journals::post_journal).get(routes::journals::list_journals),
         )
         .route("/transfers", post(routes::transfers::transfer))
-        .route("/accounts/deposit", post(routes::cash::cash_deposit))
-        .route("/accounts/withdraw", post(routes::cash::cash_withdraw))
-        .route("/accounts/check_widhraw", get(routes::cash::check_widhraw))
-        .route("/accounts/get_eod", get(routes::cash::get_eod))
         .route(
-            "/accounts/update_widhraw_journal",
-            post(routes::cash::update_widhraw_journal),
+            "/beneficiaries",
+            get(routes::beneficiaries::list_beneficiaries)
+                .post(routes::beneficiaries::create_beneficiary),
+        )
+        .route(
+            "/beneficiaries/:beneficiary_id",
+            delete(routes::beneficiaries::delete_beneficiary),
+        )
+        .route(
+            "/accounts/deposit",
+            post(routes::cash::cash_deposit).route_layer(from_fn_with_state(
+                perm::CASH_DEPOSIT,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/accounts/withdraw",
+            post(routes::cash::cash_withdraw).route_layer(from_fn_with_state(
+                perm::CASH_WITHDRAW,
+                middleware::rbac::require_permission,
+            )),
         )
         .route("/accounts/check_pin", post(routes::accounts::check_pin))
         .route(
@@ -228,10 +342,6 @@ async fn main() -> anyhow::Result<()> {
             "/investment/save_akun_pel",
             post(routes::investment::save_akun_pel),
         )
-        .route(
-            "/notifications/send",
-            post(routes::notifications::send_notification),
-        )
         .route(
             "/disbursment/bank-accounts",
             post(routes::disbursment::create_bank_account)
@@ -246,13 +356,164 @@ async fn main() -> anyhow::Result<()> {
             middleware::auth::auth_middleware,
         ));
 
-    // === Admin (RBAC + Auth) ===
-    let admin = Router::new()
-        .route("/admin/audit-logs", get(routes::admin::list_audit_logs))
+    // === Sistem corp dengan request bertanda tangan: principal & scope dari SIGNED_CLIENTS ===
+    let signed = Router::new().route(
+        "/notifications/send-public",
+        post(routes::notifications::send_notification_public)
+            .route_layer(from_fn_with_state(
+                perm::NOTIFICATION_STORE,
+                middleware::rbac::require_permission,
+            ))
+            .route_layer(from_fn_with_state(
+                state.clone(),
+                middleware::request_signature::require_signed_request,
+            )),
+    );
+
+    // === Machine-to-machine (X-Api-Key atau Bearer), setiap route wajib punya guard permission ===
+    let machine = Router::new()
+        .route(
+            "/notifications/send",
+            post(routes::notifications::send_notification).route_layer(from_fn_with_state(
+                perm::NOTIFICATION_BROADCAST,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/accounts/check_widhraw",
+            get(routes::cash::check_widhraw).route_layer(from_fn_with_state(
+                perm::WITHDRAW_READ,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/accounts/get_eod",
+            get(routes::cash::get_eod).route_layer(from_fn_with_state(
+                perm::WITHDRAW_READ,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/accounts/update_widhraw_journal",
+            post(routes::cash::update_widhraw_journal).route_layer(from_fn_with_state(
+                perm::WITHDRAW_APPROVE,
+                middleware::rbac::require_permission,
+            )),
+        )
         .layer(from_fn_with_state(
             state.clone(),
-            middleware::rbac::rbac_middleware,
-        ))
+            middleware::auth::auth_or_api_key_middleware,
+        ));
+
+    // === Admin (Auth + permission per route) ===
+    let admin = Router::new()
+        .route(
+            "/admin/audit-logs",
+            get(routes::admin::list_audit_logs).route_layer(from_fn_with_state(
+                perm::AUDIT_READ,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/users/:user_id/mfa/reset",
+            post(routes::admin::reset_user_mfa).route_layer(from_fn_with_state(
+                perm::USER_MFA_RESET,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/login-locks",
+            get(routes::login_locks::list_login_locks).route_layer(from_fn_with_state(
+                perm::AUTH_LOCK_MANAGE,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/login-locks/:scope/:key",
+            delete(routes::login_locks::release_login_lock).route_layer(from_fn_with_state(
+                perm::AUTH_LOCK_MANAGE,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/api-keys",
+            get(routes::api_keys::list_api_keys)
+                .post(routes::api_keys::create_api_key)
+                .route_layer(from_fn_with_state(
+                    perm::API_KEY_MANAGE,
+                    middleware::rbac::require_permission,
+                )),
+        )
+        .route(
+            "/admin/api-keys/:key_id",
+            delete(routes::api_keys::revoke_api_key).route_layer(from_fn_with_state(
+                perm::API_KEY_MANAGE,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/api-keys/:key_id/rotate",
+            post(routes::api_keys::rotate_api_key).route_layer(from_fn_with_state(
+                perm::API_KEY_MANAGE,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/accounts/:account_id/freeze",
+            post(routes::admin::freeze_account).route_layer(from_fn_with_state(
+                perm::ACCOUNT_MANAGE,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/accounts/:account_id/unfreeze",
+            post(routes::admin::unfreeze_account).route_layer(from_fn_with_state(
+                perm::ACCOUNT_MANAGE,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/accounts/:account_id/close",
+            post(routes::admin::close_account).route_layer(from_fn_with_state(
+                perm::ACCOUNT_MANAGE,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/users/:user_id/tier",
+            patch(routes::admin::set_user_tier).route_layer(from_fn_with_state(
+                perm::USER_TIER_MANAGE,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/ppob/reversals",
+            get(routes::admin::list_ppob_reversals).route_layer(from_fn_with_state(
+                perm::PPOB_REFUND,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/ppob/reversals/:journal_id/retry",
+            post(routes::admin::retry_ppob_reversal).route_layer(from_fn_with_state(
+                perm::PPOB_REFUND,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/invitations",
+            post(routes::invitations::create_invitation).route_layer(from_fn_with_state(
+                perm::USER_INVITE,
+                middleware::rbac::require_permission,
+            )),
+        )
+        .route(
+            "/admin/invitations/:invitation_id",
+            delete(routes::invitations::revoke_invitation).route_layer(from_fn_with_state(
+                perm::USER_INVITE,
+                middleware::rbac::require_permission,
+            )),
+        )
         .layer(from_fn_with_state(
             state.clone(),
             middleware::auth::auth_middleware,
@@ -262,13 +523,23 @@ async fn main() -> anyhow::Result<()> {
         .nest("/auth", auth_routes)
         .merge(public)
         .merge(protected)
+        .merge(signed)
+        .merge(machine)
         .merge(admin)
+        .layer(from_fn_with_state(
+            state.clone(),
+            middleware::request_context::request_context_middleware,
+        ))
         .layer(cors)
         .with_state(state.clone());
 
     let listener = TcpListener::bind("0.0.0.0:8080").await?;
     info!("listening on 0.0.0.0:8080");
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
     Ok(())
 }
 
@@ -276,5 +547,9 @@ async fn main() -> anyhow::Result<()> {
 async fn me(
     axum::Extension(claims): axum::Extension<models::Claims>,
 ) -> axum::Json<serde_json::Value> {
-    axum::Json(serde_json::json!({ "user_id": claims.sub, "role": claims.role }))
+    axum::Json(serde_json::json!({
+        "user_id": claims.sub,
+        "role": claims.role,
+        "perms": claims.perms,
+    }))
 }
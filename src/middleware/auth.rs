@@ -1,12 +1,23 @@
 use axum::{
     extract::State,
-    http::{header, StatusCode},
+    http::{header, HeaderMap, HeaderName, StatusCode},
     middleware::Next,
     response::Response,
 };
-use jsonwebtoken::{decode, DecodingKey, TokenData, Validation};
+use jsonwebtoken::TokenData;
+use sqlx::Row;
+use uuid::Uuid;
 
-use crate::{app_state::SharedState, errors::ApiError, models::Claims};
+use crate::{
+    app_state::SharedState,
+    errors::ApiError,
+    middleware::request_context::RequestContext,
+    models::Claims,
+    utils::{audit, sha256_bytes},
+};
+
+static X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
+const API_KEY_ROLE: &str = "api_key";
 
 pub async fn auth_middleware(
     State(state): State<SharedState>,
@@ -24,7 +35,39 @@ pub async fn auth_middleware(
         return Ok(next.run(req).await);
     }
 
-    let Some(auth) = req.headers().get(header::AUTHORIZATION) else {
+    let claims = bearer_claims(&state, req.headers()).await?;
+    req.extensions_mut().insert(claims);
+    Ok(next.run(req).await)
+}
+
+/// Varian untuk route yang juga dipanggil sistem lain: terima `X-Api-Key` atau Bearer.
+/// Principal API key dipetakan ke `Claims` dengan `perms` = scopes key, sehingga guard
+/// `rbac::require_permission` berlaku sama; `sub` sengaja bukan UUID user.
+pub async fn auth_or_api_key_middleware(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    mut req: axum::http::Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let claims = match req.headers().get(&X_API_KEY) {
+        Some(key) => {
+            let key = key
+                .to_str()
+                .map_err(|_| (StatusCode::UNAUTHORIZED, "Bad X-Api-Key header".into()))?;
+            api_key_claims(&state, &ctx, key.trim()).await?
+        }
+        None => bearer_claims(&state, req.headers()).await?,
+    };
+    req.extensions_mut().insert(claims);
+    Ok(next.run(req).await)
+}
+
+/// Bearer JWT → Claims (signature, expiry, blacklist jti)
+async fn bearer_claims(
+    state: &SharedState,
+    headers: &HeaderMap,
+) -> Result<Claims, (StatusCode, String)> {
+    let Some(auth) = headers.get(header::AUTHORIZATION) else {
         return Err((
             StatusCode::UNAUTHORIZED,
             "Missing Authorization header".into(),
@@ -41,22 +84,61 @@ pub async fn auth_middleware(
         return Err((StatusCode::UNAUTHORIZED, "Invalid scheme".into()));
     }
 
-    let decoding_key = DecodingKey::from_secret(state.jwt_secret.as_bytes());
-    let data: TokenData<Claims> = decode::<Claims>(token, &decoding_key, &Validation::default())
+    let data: TokenData<Claims> = state
+        .jwt_keys
+        .verify::<Claims>(token)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid/expired token".into()))?;
 
-    // cek blacklist access token (opsional)
-    let blacklisted: Option<bool> = sqlx::query_scalar(
-        "SELECT TRUE FROM lab_revoked_access_tokens WHERE jti = $1 AND now() < expires_at",
+    // cek blacklist access token: cache in-memory, DB hanya jika listener sedang putus
+    let blacklisted = match state.revoked_jtis.is_revoked(data.claims.jti) {
+        Some(revoked) => revoked,
+        None => sqlx::query_scalar::<_, bool>(
+            "SELECT TRUE FROM lab_revoked_access_tokens WHERE jti = $1 AND now() < expires_at",
+        )
+        .bind(data.claims.jti)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::from)?
+        .unwrap_or(false),
+    };
+    if blacklisted {
+        return Err((StatusCode::UNAUTHORIZED, "Token revoked".into()));
+    }
+
+    Ok(data.claims)
+}
+
+async fn api_key_claims(
+    state: &SharedState,
+    ctx: &RequestContext,
+    key: &str,
+) -> Result<Claims, (StatusCode, String)> {
+    let row = sqlx::query(
+        r#"SELECT key_id, scopes, ip_allowed
+           FROM lab_fun_api_key_authenticate($1,$2)"#,
     )
-    .bind(data.claims.jti)
+    .bind(sha256_bytes(key))
+    .bind(&ctx.ip)
     .fetch_optional(&state.pool)
     .await
-    .map_err(ApiError::from)?;
-    if blacklisted.unwrap_or(false) {
-        return Err((StatusCode::UNAUTHORIZED, "Token revoked".into()));
+    .map_err(ApiError::from)?
+    .ok_or((StatusCode::UNAUTHORIZED, "Invalid API key".into()))?;
+
+    let key_id: Uuid = row.get("key_id");
+    if !row.get::<bool, _>("ip_allowed") {
+        let meta = serde_json::json!({ "key_id": key_id });
+        audit(state, ctx, None, "api_key_ip_denied", None, Some(meta)).await;
+        return Err((
+            StatusCode::FORBIDDEN,
+            "API key not allowed from this IP".into(),
+        ));
     }
 
-    req.extensions_mut().insert(data.claims);
-    Ok(next.run(req).await)
+    Ok(Claims {
+        sub: format!("api_key:{}", key_id),
+        role: API_KEY_ROLE.into(),
+        exp: 0,
+        jti: key_id,
+        perms: row.get("scopes"),
+    })
 }
@@ -1,24 +1,25 @@
-use axum::{http::StatusCode, middleware::Next, response::Response};
+use axum::{extract::State, http::StatusCode, middleware::Next, response::Response};
 
-use crate::models::{Claims, Role};
+use crate::models::Claims;
 
-pub async fn rbac_middleware(
+/// Guard per-route, dipasang setelah `auth_middleware`:
+/// `.route_layer(from_fn_with_state(perm::AUDIT_READ, rbac::require_permission))`
+pub async fn require_permission(
+    State(perm): State<&'static str>,
     req: axum::http::Request<axum::body::Body>,
     next: Next,
 ) -> Result<Response, (StatusCode, String)> {
-    let claims = req.extensions().get::<Claims>().cloned().ok_or((
+    let claims = req.extensions().get::<Claims>().ok_or((
         StatusCode::UNAUTHORIZED,
         "Missing claims (not authenticated)".into(),
     ))?;
 
-    let role = Role::from_str(&claims.role).unwrap_or(Role::User);
-    let path = req.uri().path();
-    if path.starts_with("/admin") {
-        match role {
-            Role::Admin => Ok(next.run(req).await),
-            _ => Err((StatusCode::FORBIDDEN, "Admin access required".into())),
-        }
-    } else {
+    if claims.has_perm(perm) {
         Ok(next.run(req).await)
+    } else {
+        Err((
+            StatusCode::FORBIDDEN,
+            format!("Missing permission: {}", perm),
+        ))
     }
 }
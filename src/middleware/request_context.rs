@@ -0,0 +1,263 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, State},
+    http::{request::Parts, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use uuid::Uuid;
+
+use crate::app_state::SharedState;
+
+const MAX_USER_AGENT_LEN: usize = 512;
+static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+static X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// Konteks per-request untuk audit & token: IP klien asli, user agent, request id
+#[derive(Clone, Debug)]
+pub struct RequestContext {
+    pub ip: String,
+    pub user_agent: String,
+    pub request_id: Uuid,
+}
+
+/// Satu entri `TRUSTED_PROXIES` (IP tunggal atau CIDR, mis. `10.0.0.0/8`)
+#[derive(Clone, Debug)]
+pub struct TrustedProxy {
+    net: IpAddr,
+    prefix: u8,
+}
+
+impl TrustedProxy {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        let (addr, prefix) = match raw.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix.parse::<u8>().ok()?)),
+            None => (raw, None),
+        };
+        let net: IpAddr = addr.parse().ok()?;
+        let max = if net.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        if prefix > max {
+            return None;
+        }
+        Some(TrustedProxy { net, prefix })
+    }
+
+    /// Parse daftar dipisah koma; entri tidak valid di-log dan dilewati
+    pub fn parse_list(raw: &str) -> Vec<Self> {
+        raw.split(',')
+            .filter(|s| !s.trim().is_empty())
+            .filter_map(|s| {
+                let parsed = TrustedProxy::parse(s);
+                if parsed.is_none() {
+                    tracing::warn!("TRUSTED_PROXIES entry ignored: {}", s.trim());
+                }
+                parsed
+            })
+            .collect()
+    }
+
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.net, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(*ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(*ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn is_trusted(proxies: &[TrustedProxy], ip: &IpAddr) -> bool {
+    proxies.iter().any(|p| p.contains(ip))
+}
+
+/// IP klien: peer langsung, kecuali peer adalah trusted proxy — maka X-Forwarded-For
+/// dibaca dari kanan dan entri pertama yang bukan trusted proxy dipakai.
+fn client_ip(proxies: &[TrustedProxy], peer: Option<IpAddr>, xff: Option<&str>) -> Option<IpAddr> {
+    let peer = peer?;
+    if !is_trusted(proxies, &peer) {
+        return Some(peer);
+    }
+    let hops: Vec<IpAddr> = xff
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|s| s.trim().parse().ok())
+        .collect();
+    hops.iter()
+        .rev()
+        .find(|ip| !is_trusted(proxies, ip))
+        .or_else(|| hops.first())
+        .copied()
+        .or(Some(peer))
+}
+
+impl RequestContext {
+    fn from_parts(state: &SharedState, parts: &Parts) -> Self {
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let from_trusted_proxy = peer
+            .map(|ip| is_trusted(&state.trusted_proxies, &ip))
+            .unwrap_or(false);
+
+        let xff = parts
+            .headers
+            .get(&X_FORWARDED_FOR)
+            .and_then(|v| v.to_str().ok());
+        let ip = client_ip(&state.trusted_proxies, peer, xff)
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let mut user_agent = parts
+            .headers
+            .get(axum::http::header::USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("unknown")
+            .to_string();
+        if user_agent.len() > MAX_USER_AGENT_LEN {
+            let mut end = MAX_USER_AGENT_LEN;
+            while !user_agent.is_char_boundary(end) {
+                end -= 1;
+            }
+            user_agent.truncate(end);
+        }
+
+        // request id dari upstream hanya dipercaya jika datang lewat trusted proxy
+        let request_id = parts
+            .headers
+            .get(&X_REQUEST_ID)
+            .filter(|_| from_trusted_proxy)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| Uuid::parse_str(v.trim()).ok())
+            .unwrap_or_else(Uuid::new_v4);
+
+        RequestContext {
+            ip,
+            user_agent,
+            request_id,
+        }
+    }
+}
+
+/// Pasang `RequestContext` di extensions dan balikan `x-request-id` di response
+pub async fn request_context_middleware(
+    State(state): State<SharedState>,
+    req: axum::http::Request<axum::body::Body>,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = req.into_parts();
+    let ctx = RequestContext::from_parts(&state, &parts);
+    let request_id = ctx.request_id;
+    parts.extensions.insert(ctx);
+
+    let mut res = next.run(axum::http::Request::from_parts(parts, body)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id.to_string()) {
+        res.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+    res
+}
+
+#[async_trait]
+impl FromRequestParts<SharedState> for RequestContext {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &SharedState,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(ctx) = parts.extensions.get::<RequestContext>() {
+            return Ok(ctx.clone());
+        }
+        Ok(RequestContext::from_parts(state, parts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::{client_ip, TrustedProxy};
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn trusted_proxy_parses_ips_and_cidrs() {
+        let net = TrustedProxy::parse(" 10.0.0.0/8 ").expect("cidr");
+        assert!(net.contains(&ip("10.200.1.2")));
+        assert!(!net.contains(&ip("11.0.0.1")));
+        assert!(!net.contains(&ip("::ffff:10.0.0.1")));
+
+        let single = TrustedProxy::parse("192.168.1.10").expect("single ip");
+        assert!(single.contains(&ip("192.168.1.10")));
+        assert!(!single.contains(&ip("192.168.1.11")));
+
+        let v6 = TrustedProxy::parse("fd00::/8").expect("v6 cidr");
+        assert!(v6.contains(&ip("fd12:3456::1")));
+        assert!(!v6.contains(&ip("fe80::1")));
+
+        let any = TrustedProxy::parse("0.0.0.0/0").expect("/0");
+        assert!(any.contains(&ip("8.8.8.8")));
+
+        for bad in [
+            "10.0.0.0/33",
+            "::/129",
+            "10.0.0/8",
+            "10.0.0.0/x",
+            "proxy",
+            "",
+        ] {
+            assert!(TrustedProxy::parse(bad).is_none(), "{:?} accepted", bad);
+        }
+        assert_eq!(
+            TrustedProxy::parse_list("10.0.0.0/8, bogus,,127.0.0.1").len(),
+            2
+        );
+    }
+
+    #[test]
+    fn client_ip_ignores_xff_from_untrusted_peer() {
+        let proxies = TrustedProxy::parse_list("10.0.0.0/8");
+        let got = client_ip(&proxies, Some(ip("203.0.113.9")), Some("1.2.3.4"));
+        assert_eq!(got, Some(ip("203.0.113.9")));
+    }
+
+    #[test]
+    fn client_ip_takes_rightmost_untrusted_hop() {
+        let proxies = TrustedProxy::parse_list("10.0.0.0/8");
+        // entri paling kiri bisa dipalsukan client; yang dipakai hop terakhir sebelum proxy
+        let xff = "6.6.6.6, 198.51.100.7, 10.0.0.2";
+        let got = client_ip(&proxies, Some(ip("10.0.0.1")), Some(xff));
+        assert_eq!(got, Some(ip("198.51.100.7")));
+
+        // sampah di XFF dilewati
+        let got = client_ip(
+            &proxies,
+            Some(ip("10.0.0.1")),
+            Some("not-an-ip, 198.51.100.7"),
+        );
+        assert_eq!(got, Some(ip("198.51.100.7")));
+    }
+
+    #[test]
+    fn client_ip_falls_back_when_all_hops_trusted_or_missing() {
+        let proxies = TrustedProxy::parse_list("10.0.0.0/8");
+        let got = client_ip(&proxies, Some(ip("10.0.0.1")), Some("10.1.1.1, 10.2.2.2"));
+        assert_eq!(got, Some(ip("10.1.1.1")));
+
+        let got = client_ip(&proxies, Some(ip("10.0.0.1")), None);
+        assert_eq!(got, Some(ip("10.0.0.1")));
+
+        assert_eq!(client_ip(&proxies, None, Some("1.2.3.4")), None);
+    }
+}
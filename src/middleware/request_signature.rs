@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderName, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::ApiError,
+    middleware::{auth::auth_middleware, request_context::RequestContext},
+    models::Claims,
+    utils::audit,
+};
+
+/// Selisih maksimal `X-Timestamp` terhadap jam server (detik)
+const SIGNATURE_WINDOW_SECS: i64 = 300;
+const MAX_SIGNED_BODY_BYTES: usize = 1024 * 1024;
+const MAX_NONCE_LEN: usize = 128;
+const SIGNED_CLIENT_ROLE: &str = "signed_client";
+
+static X_CLIENT_ID: HeaderName = HeaderName::from_static("x-client-id");
+static X_TIMESTAMP: HeaderName = HeaderName::from_static("x-timestamp");
+static X_NONCE: HeaderName = HeaderName::from_static("x-nonce");
+static X_SIGNATURE: HeaderName = HeaderName::from_static("x-signature");
+
+/// Client yang boleh mengirim request bertanda tangan, dari env
+/// `SIGNED_CLIENTS=client_id:base64secret:scope|scope,client2:base64secret`.
+/// Scope memakai nama dari lab_permissions; client tanpa scope hanya bisa memakai route
+/// bertanda tangan yang tidak butuh permission.
+#[derive(Clone, Default)]
+pub struct SigningClients(HashMap<String, SigningClient>);
+
+#[derive(Clone)]
+struct SigningClient {
+    secret: Vec<u8>,
+    scopes: Vec<String>,
+}
+
+impl SigningClients {
+    pub fn parse(raw: &str) -> Self {
+        let mut clients = HashMap::new();
+        for entry in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let mut fields = entry.splitn(3, ':').map(str::trim);
+            let id = fields.next().unwrap_or_default();
+            let secret = fields
+                .next()
+                .and_then(|secret| STANDARD.decode(secret).ok())
+                .unwrap_or_default();
+            let scopes = fields
+                .next()
+                .map(|scopes| {
+                    scopes
+                        .split('|')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default();
+            if id.is_empty() || secret.is_empty() {
+                tracing::warn!("SIGNED_CLIENTS entry ignored (expected id:base64secret[:scopes])");
+                continue;
+            }
+            clients.insert(id.to_string(), SigningClient { secret, scopes });
+        }
+        SigningClients(clients)
+    }
+}
+
+/// Client yang tanda tangannya sudah diverifikasi, disisipkan ke extensions request
+#[derive(Clone)]
+pub struct SignedClient(pub String);
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// String yang ditandatangani client:
+/// `METHOD \n PATH[?QUERY] \n TIMESTAMP \n NONCE \n hex(sha256(body))`
+fn canonical_string(
+    method: &str,
+    path_and_query: &str,
+    ts: &str,
+    nonce: &str,
+    body: &[u8],
+) -> String {
+    format!(
+        "{}\n{}\n{}\n{}\n{}",
+        method.to_ascii_uppercase(),
+        path_and_query,
+        ts,
+        nonce,
+        hex(&Sha256::digest(body))
+    )
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Result<&'a str, ApiError> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .ok_or_else(|| ApiError::Unauthorized(format!("missing {} header", name)))
+}
+
+/// Guard per-route: request wajib bertanda tangan HMAC-SHA256 dari client di `SIGNED_CLIENTS`.
+/// Client yang lolos menjadi principal request (`Claims` dengan sub `client:<id>` & perms =
+/// scope client), jadi route di belakangnya cukup dijaga `rbac::require_permission`.
+/// `.route_layer(from_fn_with_state(state.clone(), request_signature::require_signed_request))`
+pub async fn require_signed_request(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    req: axum::http::Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let (parts, body) = req.into_parts();
+    let body = axum::body::to_bytes(body, MAX_SIGNED_BODY_BYTES)
+        .await
+        .map_err(|_| ApiError::BadRequest("request body too large".into()))?;
+
+    let client_id = header(&parts.headers, &X_CLIENT_ID)?;
+    let client = match verify(&state, &parts, client_id, &body).await {
+        Ok(client) => client,
+        Err(e) => {
+            let meta = serde_json::json!({
+                "client_id": client_id,
+                "path": parts.uri.path(),
+            });
+            audit(
+                &state,
+                &ctx,
+                None,
+                "signed_request_rejected",
+                None,
+                Some(meta),
+            )
+            .await;
+            return Err(e.into());
+        }
+    };
+
+    let claims = Claims {
+        sub: format!("client:{}", client_id),
+        role: SIGNED_CLIENT_ROLE.into(),
+        exp: 0,
+        jti: Uuid::nil(),
+        perms: client.scopes.clone(),
+    };
+    let signed = SignedClient(client_id.to_string());
+    let mut req = axum::http::Request::from_parts(parts, axum::body::Body::from(body));
+    req.extensions_mut().insert(signed);
+    req.extensions_mut().insert(claims);
+    Ok(next.run(req).await)
+}
+
+/// Guard untuk route yang dipakai app maupun sistem corp: request dengan `X-Client-Id`
+/// wajib bertanda tangan, selainnya wajib Bearer JWT (sama seperti router protected).
+pub async fn require_signed_request_or_user(
+    state: State<SharedState>,
+    ctx: RequestContext,
+    req: axum::http::Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    if req.headers().contains_key(&X_CLIENT_ID) {
+        require_signed_request(state, ctx, req, next).await
+    } else {
+        auth_middleware(state, req, next).await
+    }
+}
+
+async fn verify<'a>(
+    state: &'a SharedState,
+    parts: &axum::http::request::Parts,
+    client_id: &str,
+    body: &[u8],
+) -> Result<&'a SigningClient, ApiError> {
+    let invalid = || ApiError::Unauthorized("invalid request signature".into());
+
+    let client = state.signing_clients.0.get(client_id).ok_or_else(invalid)?;
+    let ts = header(&parts.headers, &X_TIMESTAMP)?;
+    let nonce = header(&parts.headers, &X_NONCE)?;
+    let signature = header(&parts.headers, &X_SIGNATURE)?;
+
+    let ts_secs: i64 = ts.parse().map_err(|_| invalid())?;
+    if (chrono::Utc::now().timestamp() - ts_secs).abs() > SIGNATURE_WINDOW_SECS {
+        return Err(ApiError::Unauthorized(
+            "request timestamp outside allowed window".into(),
+        ));
+    }
+    if nonce.len() > MAX_NONCE_LEN {
+        return Err(invalid());
+    }
+
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or("/");
+    let canonical = canonical_string(parts.method.as_str(), path_and_query, ts, nonce, body);
+    let signature = STANDARD.decode(signature).map_err(|_| invalid())?;
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(&client.secret).expect("hmac accepts any key length");
+    mac.update(canonical.as_bytes());
+    mac.verify_slice(&signature).map_err(|_| invalid())?;
+
+    // nonce dicatat setelah signature valid, supaya pihak lain tidak bisa "membakar" nonce
+    let fresh: bool = sqlx::query_scalar("SELECT lab_fun_consume_request_nonce($1,$2,$3)")
+        .bind(client_id)
+        .bind(nonce)
+        .bind(SIGNATURE_WINDOW_SECS as i32)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if !fresh {
+        return Err(ApiError::Unauthorized("request replayed".into()));
+    }
+    Ok(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::canonical_string;
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    #[test]
+    fn canonical_string_layout() {
+        let s = canonical_string(
+            "post",
+            "/accounts/verify?x=1",
+            "1760000000",
+            "n-1",
+            br#"{"a":1}"#,
+        );
+        assert_eq!(
+            s,
+            "POST\n/accounts/verify?x=1\n1760000000\nn-1\n\
+             015abd7f5cc57a2dd94b7590f04ad8084273905ee33ec5cebeae62276a97f862"
+        );
+    }
+
+    #[test]
+    fn canonical_string_empty_body_hashes_empty_input() {
+        let s = canonical_string("GET", "/x", "1", "n", b"");
+        assert_eq!(s, format!("GET\n/x\n1\nn\n{}", EMPTY_SHA256));
+    }
+
+    #[test]
+    fn canonical_string_binds_every_component() {
+        let base = canonical_string("POST", "/a?b=1", "10", "n", b"{}");
+        assert_ne!(base, canonical_string("PUT", "/a?b=1", "10", "n", b"{}"));
+        assert_ne!(base, canonical_string("POST", "/a?b=2", "10", "n", b"{}"));
+        assert_ne!(base, canonical_string("POST", "/a?b=1", "11", "n", b"{}"));
+        assert_ne!(base, canonical_string("POST", "/a?b=1", "10", "m", b"{}"));
+        assert_ne!(base, canonical_string("POST", "/a?b=1", "10", "n", b"{ }"));
+    }
+}
@@ -1,25 +1,74 @@
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::jwt_keys::TokenKind;
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub sub: String, // user_id
     pub role: String,
     pub exp: usize,
     pub jti: Uuid,
+    /// Permission milik role (lab_role_permissions), dibaca ulang setiap token diterbitkan
+    #[serde(default)]
+    pub perms: Vec<String>,
 }
 
-#[derive(Clone, Copy)]
-pub enum Role {
-    Admin,
-    User,
-}
-impl Role {
-    pub fn from_str(s: &str) -> Option<Self> {
-        match s {
-            "admin" => Some(Role::Admin),
-            "user" => Some(Role::User),
-            _ => None,
-        }
+impl Claims {
+    pub fn has_perm(&self, perm: &str) -> bool {
+        self.perms.iter().any(|p| p == perm)
     }
 }
+
+impl TokenKind for Claims {
+    const TYP: &'static str = "at+jwt";
+    const AUD: &'static str = "labapi";
+}
+
+/// Token sementara setelah password benar tapi TOTP belum diverifikasi.
+/// Sengaja tanpa field `role` sehingga tidak bisa di-decode sebagai `Claims`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MfaPendingClaims {
+    pub sub: String, // user_id
+    pub purpose: String,
+    pub exp: usize,
+    pub jti: Uuid,
+}
+
+impl TokenKind for MfaPendingClaims {
+    const TYP: &'static str = "mfa+jwt";
+    const AUD: &'static str = "labapi:mfa";
+}
+
+/// Token undangan akun admin/staf (jti = id baris lab_invitations).
+/// Tanpa field `role` sehingga tidak bisa di-decode sebagai `Claims`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InvitationClaims {
+    pub sub: String, // email invitee
+    pub purpose: String,
+    pub exp: usize,
+    pub jti: Uuid,
+}
+
+impl TokenKind for InvitationClaims {
+    const TYP: &'static str = "invite+jwt";
+    const AUD: &'static str = "labapi:invite";
+}
+
+/// Nama permission; harus sama dengan isi tabel `lab_permissions`
+pub mod perm {
+    pub const AUDIT_READ: &str = "audit:read";
+    pub const USER_MFA_RESET: &str = "user:mfa_reset";
+    pub const USER_INVITE: &str = "user:invite";
+    pub const AUTH_LOCK_MANAGE: &str = "auth:lock_manage";
+    pub const API_KEY_MANAGE: &str = "api_key:manage";
+    pub const NOTIFICATION_STORE: &str = "notification:store";
+    pub const WITHDRAW_READ: &str = "withdraw:read";
+    pub const WITHDRAW_APPROVE: &str = "withdraw:approve";
+    pub const CASH_DEPOSIT: &str = "cash:deposit";
+    pub const CASH_WITHDRAW: &str = "cash:withdraw";
+    pub const PPOB_REFUND: &str = "ppob:refund";
+    pub const NOTIFICATION_BROADCAST: &str = "notification:broadcast";
+    pub const ACCOUNT_MANAGE: &str = "account:manage";
+    pub const USER_TIER_MANAGE: &str = "user:tier_manage";
+}
@@ -0,0 +1,197 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::types::BigDecimal;
+
+/// Digit desimal maksimum (kolom DB `numeric(20,2)`)
+const MONEY_SCALE: i64 = 2;
+const MONEY_MAX_INTEGER_DIGITS: u64 = 18;
+
+/// Nominal uang desimal eksak (rupiah, 2 digit desimal).
+///
+/// JSON: diserialisasi sebagai string `"1500000.00"`; input boleh string atau angka
+/// (angka dibaca dari representasi desimal terpendeknya, bukan lewat aritmetika float).
+/// DB: bind/decode langsung sebagai `numeric`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct Money(BigDecimal);
+
+#[derive(Debug)]
+pub struct MoneyError(&'static str);
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Money {
+    pub fn zero() -> Self {
+        Money(BigDecimal::from(0).with_scale(MONEY_SCALE))
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.0 > BigDecimal::from(0)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0 < BigDecimal::from(0)
+    }
+
+    /// Nilai dalam rupiah utuh; None jika masih ada sen atau di luar rentang i64
+    pub fn to_whole_rupiah(&self) -> Option<i64> {
+        let whole = self.0.with_scale(0);
+        if whole != self.0 {
+            return None;
+        }
+        whole.to_string().parse().ok()
+    }
+
+    fn new(value: BigDecimal) -> Result<Self, MoneyError> {
+        let (_, scale) = value.normalized().as_bigint_and_exponent();
+        if scale > MONEY_SCALE {
+            return Err(MoneyError("amount must have at most 2 decimal places"));
+        }
+        let value = value.with_scale(MONEY_SCALE);
+        if value.digits() > MONEY_MAX_INTEGER_DIGITS + MONEY_SCALE as u64 {
+            return Err(MoneyError("amount out of range"));
+        }
+        Ok(Money(value))
+    }
+}
+
+impl From<i64> for Money {
+    fn from(value: i64) -> Self {
+        Money(BigDecimal::from(value).with_scale(MONEY_SCALE))
+    }
+}
+
+impl FromStr for Money {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        // hanya notasi desimal biasa: tanpa eksponen, tanpa pemisah ribuan
+        let digits = s.strip_prefix('-').unwrap_or(s);
+        let valid = !digits.is_empty()
+            && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+            && digits.matches('.').count() <= 1
+            && digits.chars().any(|c| c.is_ascii_digit());
+        if !valid {
+            return Err(MoneyError("invalid amount"));
+        }
+        let value = BigDecimal::from_str(s).map_err(|_| MoneyError("invalid amount"))?;
+        Money::new(value)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.with_scale(MONEY_SCALE))
+    }
+}
+
+impl std::ops::Add for Money {
+    type Output = Money;
+
+    fn add(self, rhs: Money) -> Money {
+        Money(self.0 + rhs.0)
+    }
+}
+
+impl std::iter::Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::zero(), |acc, m| acc + m)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MoneyVisitor;
+
+        impl de::Visitor<'_> for MoneyVisitor {
+            type Value = Money;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a decimal amount with at most 2 decimal places")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Money, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Money, E> {
+                Ok(Money::from(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Money, E> {
+                Money::new(BigDecimal::from(v)).map_err(E::custom)
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Money, E> {
+                if !v.is_finite() {
+                    return Err(E::custom("invalid amount"));
+                }
+                // Display f64 = representasi terpendek yang round-trip, mis. 0.1 → "0.1"
+                v.to_string().parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Money;
+
+    #[test]
+    fn parses_strings_and_numbers_to_two_decimals() {
+        assert_eq!(
+            "1500000".parse::<Money>().unwrap().to_string(),
+            "1500000.00"
+        );
+        assert_eq!(" 12.5 ".parse::<Money>().unwrap().to_string(), "12.50");
+        assert_eq!("-0.01".parse::<Money>().unwrap().to_string(), "-0.01");
+        // nol di belakang tidak dihitung sebagai digit desimal
+        assert_eq!("7.500".parse::<Money>().unwrap().to_string(), "7.50");
+
+        let from_json = |v: &str| serde_json::from_str::<Money>(v).map(|m| m.to_string());
+        assert_eq!(from_json("\"250.75\"").unwrap(), "250.75");
+        assert_eq!(from_json("1500").unwrap(), "1500.00");
+        assert_eq!(from_json("0.1").unwrap(), "0.10");
+        assert_eq!(from_json("19.99").unwrap(), "19.99");
+    }
+
+    #[test]
+    fn serializes_as_string_with_two_decimals() {
+        let json = serde_json::to_value(Money::from(1_500_000)).unwrap();
+        assert_eq!(json, serde_json::json!("1500000.00"));
+
+        let json = serde_json::to_value("0.5".parse::<Money>().unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!("0.50"));
+    }
+
+    #[test]
+    fn rejects_more_than_two_decimals() {
+        assert!("1.005".parse::<Money>().is_err());
+        assert!("0.001".parse::<Money>().is_err());
+        assert!(serde_json::from_str::<Money>("\"10.123\"").is_err());
+        assert!(serde_json::from_str::<Money>("1.005").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_amounts() {
+        for input in ["", "-", ".", "1e3", "1,000", "1.2.3", "abc", "+5"] {
+            assert!(input.parse::<Money>().is_err(), "{:?} accepted", input);
+        }
+        assert!("1234567890123456789".parse::<Money>().is_err());
+        assert!(serde_json::from_str::<Money>("true").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::PathBuf;
+
+use argon2::{Argon2, Params};
+use password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use sha1::{Digest, Sha1};
+
+use crate::errors::ApiError;
+
+/// Batas atas panjang password (hashing Argon2 untuk input raksasa = DoS murah)
+const PASSWORD_MAX_LENGTH: usize = 128;
+/// Riwayat hash yang disimpan DB per user (lihat trigger lab_users_password_history)
+pub const PASSWORD_HISTORY_MAX: i32 = 24;
+
+/// Kebijakan password baru (register, reset, undangan, ganti password), dari env:
+/// `PASSWORD_MIN_LENGTH`, `PASSWORD_MIN_CLASSES`, `PASSWORD_HISTORY`, `PASSWORD_BREACHED_DIR`,
+/// plus parameter hashing `ARGON2_M_COST` (KiB), `ARGON2_T_COST`, `ARGON2_P_COST`
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    /// Minimal jenis karakter berbeda dari: huruf kecil, huruf besar, angka, simbol
+    pub min_classes: usize,
+    /// Password tidak boleh sama dengan N hash terakhir (termasuk yang sekarang)
+    pub history: i32,
+    /// Direktori daftar password bocor bergaya k-anonymity: satu file per prefix
+    /// SHA-1 5 karakter (`<PREFIX>.txt`), isinya baris `SUFFIX:COUNT` seperti range API HIBP
+    breached_dir: Option<PathBuf>,
+    argon2_params: Params,
+}
+
+/// Cocokkan password dengan hash PHC tersimpan (parameter Argon2 dibaca dari hash-nya)
+pub fn verify_password(stored_hash: &str, password: &str) -> bool {
+    PasswordHash::new(stored_hash)
+        .map(|parsed| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+impl PasswordPolicy {
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(default)
+        }
+
+        let dir = PathBuf::from(
+            std::env::var("PASSWORD_BREACHED_DIR").unwrap_or_else(|_| "screets/pwned".into()),
+        );
+        let breached_dir = if dir.is_dir() {
+            Some(dir)
+        } else {
+            tracing::warn!(
+                "breached password list not found ({}), check disabled",
+                dir.display()
+            );
+            None
+        };
+
+        let argon2_params = Params::new(
+            var("ARGON2_M_COST", Params::DEFAULT_M_COST),
+            var("ARGON2_T_COST", Params::DEFAULT_T_COST),
+            var("ARGON2_P_COST", Params::DEFAULT_P_COST),
+            None,
+        )
+        .unwrap_or_else(|e| {
+            tracing::warn!("invalid ARGON2_* params ({}), using defaults", e);
+            Params::default()
+        });
+
+        PasswordPolicy {
+            min_length: var("PASSWORD_MIN_LENGTH", 10).clamp(8, PASSWORD_MAX_LENGTH),
+            min_classes: var("PASSWORD_MIN_CLASSES", 3).clamp(1, 4),
+            history: var("PASSWORD_HISTORY", 5).clamp(0, PASSWORD_HISTORY_MAX),
+            breached_dir,
+            argon2_params,
+        }
+    }
+
+    /// Hash Argon2id dengan parameter dari config; hash lama tetap terverifikasi
+    /// karena parameternya tersimpan di string PHC masing-masing
+    pub fn hash(&self, password: &str) -> Result<String, ApiError> {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::new(
+            argon2::Algorithm::Argon2id,
+            argon2::Version::V0x13,
+            self.argon2_params.clone(),
+        )
+        .hash_password(password.as_bytes(), &salt)
+        .map(|h| h.to_string())
+        .map_err(|e| ApiError::Internal(e.to_string()))
+    }
+
+    /// Validasi password baru; `previous_hashes` = hash lama user (kosong untuk akun baru)
+    pub async fn check(&self, password: &str, previous_hashes: &[String]) -> Result<(), ApiError> {
+        let len = password.chars().count();
+        if len < self.min_length {
+            return Err(ApiError::BadRequest(format!(
+                "password must be at least {} characters",
+                self.min_length
+            )));
+        }
+        if len > PASSWORD_MAX_LENGTH {
+            return Err(ApiError::BadRequest(format!(
+                "password must be at most {} characters",
+                PASSWORD_MAX_LENGTH
+            )));
+        }
+
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_numeric()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ];
+        if classes.iter().filter(|&&has| has).count() < self.min_classes {
+            return Err(ApiError::BadRequest(format!(
+                "password must mix at least {} of: lowercase, uppercase, digits, symbols",
+                self.min_classes
+            )));
+        }
+
+        if previous_hashes.iter().any(|h| verify_password(h, password)) {
+            return Err(ApiError::BadRequest(format!(
+                "password must differ from the last {} passwords",
+                self.history
+            )));
+        }
+
+        if self.is_breached(password).await? {
+            return Err(ApiError::BadRequest(
+                "password appears in a known data breach, choose another".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Cek daftar bocor lokal: hanya file untuk prefix hash ini yang dibaca
+    async fn is_breached(&self, password: &str) -> Result<bool, ApiError> {
+        let Some(dir) = &self.breached_dir else {
+            return Ok(false);
+        };
+
+        let digest: String = Sha1::digest(password.as_bytes())
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect();
+        let (prefix, suffix) = digest.split_at(5);
+
+        let raw = match tokio::fs::read_to_string(dir.join(format!("{}.txt", prefix))).await {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(ApiError::Internal(e.to_string())),
+        };
+        Ok(raw.lines().any(|line| {
+            line.split(':')
+                .next()
+                .is_some_and(|s| s.trim().eq_ignore_ascii_case(suffix))
+        }))
+    }
+}
@@ -0,0 +1,164 @@
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 40.0;
+/// Lebar glyph Courier = 0.6 × ukuran font
+const COURIER_ADVANCE: f32 = 0.6;
+
+pub struct PdfLine {
+    pub text: String,
+    pub bold: bool,
+}
+
+impl PdfLine {
+    pub fn plain(text: impl Into<String>) -> Self {
+        PdfLine {
+            text: text.into(),
+            bold: false,
+        }
+    }
+
+    pub fn bold(text: impl Into<String>) -> Self {
+        PdfLine {
+            text: text.into(),
+            bold: true,
+        }
+    }
+}
+
+/// Penulis PDF teks minimal tanpa dependensi: halaman A4, font standar Courier / Courier-Bold
+/// (tidak di-embed, selalu tersedia di viewer), encoding WinAnsi. Cukup untuk dokumen tabel
+/// monospace seperti rekening koran; pemecahan halaman diatur pemanggil.
+pub struct TextPdf {
+    font_size: f32,
+    leading: f32,
+}
+
+impl TextPdf {
+    pub fn new(font_size: f32) -> Self {
+        TextPdf {
+            font_size,
+            leading: font_size * 1.25,
+        }
+    }
+
+    pub fn lines_per_page(&self) -> usize {
+        ((PAGE_HEIGHT - 2.0 * MARGIN) / self.leading) as usize
+    }
+
+    pub fn chars_per_line(&self) -> usize {
+        ((PAGE_WIDTH - 2.0 * MARGIN) / (self.font_size * COURIER_ADVANCE)) as usize
+    }
+
+    /// Render dokumen; tiap halaman maksimal `lines_per_page()` baris
+    pub fn render(&self, title: &str, pages: &[Vec<PdfLine>]) -> Vec<u8> {
+        let page_count = pages.len().max(1);
+        // 1 catalog, 2 pages, 3-4 font, 5 info, lalu (page, content) per halaman
+        let page_obj = |i: usize| 6 + 2 * i;
+
+        let mut objects: Vec<Vec<u8>> = Vec::with_capacity(5 + 2 * page_count);
+        objects.push(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+        let kids = (0..page_count)
+            .map(|i| format!("{} 0 R", page_obj(i)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        objects.push(
+            format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids, page_count).into_bytes(),
+        );
+        objects.push(
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+                .to_vec(),
+        );
+        objects.push(
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier-Bold /Encoding /WinAnsiEncoding >>"
+                .to_vec(),
+        );
+        let mut info = b"<< /Title (".to_vec();
+        info.extend(escape_text(title));
+        info.extend_from_slice(b") /Producer (labapi) >>");
+        objects.push(info);
+
+        let empty = Vec::new();
+        for i in 0..page_count {
+            let lines = pages.get(i).unwrap_or(&empty);
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                     /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    PAGE_WIDTH,
+                    PAGE_HEIGHT,
+                    page_obj(i) + 1
+                )
+                .into_bytes(),
+            );
+            let content = self.content_stream(lines);
+            let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+            stream.extend(content);
+            stream.extend_from_slice(b"\nendstream");
+            objects.push(stream);
+        }
+
+        let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, body) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend(format!("{} 0 obj\n", i + 1).into_bytes());
+            out.extend_from_slice(body);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+        let xref_at = out.len();
+        out.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+        for offset in offsets {
+            out.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+        }
+        out.extend(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R /Info 5 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref_at
+            )
+            .into_bytes(),
+        );
+        out
+    }
+
+    fn content_stream(&self, lines: &[PdfLine]) -> Vec<u8> {
+        let mut s = format!(
+            "BT\n/F1 {size} Tf\n{leading} TL\n{x} {y} Td\n",
+            size = self.font_size,
+            leading = self.leading,
+            x = MARGIN,
+            y = PAGE_HEIGHT - MARGIN - self.font_size
+        )
+        .into_bytes();
+        let mut bold = false;
+        for line in lines {
+            if line.bold != bold {
+                bold = line.bold;
+                let font = if bold { "F2" } else { "F1" };
+                s.extend(format!("/{} {} Tf\n", font, self.font_size).into_bytes());
+            }
+            s.push(b'(');
+            s.extend(escape_text(&line.text));
+            s.extend_from_slice(b") Tj T*\n");
+        }
+        s.extend_from_slice(b"ET");
+        s
+    }
+}
+
+/// String literal PDF dalam WinAnsi: Latin-1 dipakai apa adanya, karakter lain jadi '?'
+fn escape_text(text: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push(b'\\');
+                out.push(c as u8);
+            }
+            ' '..='~' => out.push(c as u8),
+            '\u{a0}'..='\u{ff}' => out.push(c as u32 as u8),
+            _ => out.push(b'?'),
+        }
+    }
+    out
+}
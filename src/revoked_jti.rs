@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use sqlx::{postgres::PgListener, PgPool, Row};
+use uuid::Uuid;
+
+/// Channel NOTIFY dari trigger `lab_revoked_access_tokens_notify`, payload `jti:exp_epoch`
+const REVOKED_JTI_CHANNEL: &str = "lab_revoked_access_tokens";
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+const RECONNECT_BACKOFF_CAP: Duration = Duration::from_secs(30);
+
+/// Cache in-process jti access token yang dicabut, supaya auth middleware tidak
+/// query `lab_revoked_access_tokens` di setiap request.
+///
+/// Diisi snapshot tabel lalu diperbarui lewat LISTEN/NOTIFY. Entri hidup sampai
+/// token aslinya expired. Selama listener putus cache dianggap tidak sinkron dan
+/// `is_revoked` mengembalikan `None` → caller wajib cek DB.
+#[derive(Default)]
+pub struct RevokedJtiCache {
+    entries: RwLock<HashMap<Uuid, i64>>,
+    synced: AtomicBool,
+}
+
+impl RevokedJtiCache {
+    /// `Some(revoked)` jika cache sinkron, `None` jika harus fallback ke DB
+    pub fn is_revoked(&self, jti: Uuid) -> Option<bool> {
+        if !self.synced.load(Ordering::Acquire) {
+            return None;
+        }
+        let now = chrono::Utc::now().timestamp();
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        Some(entries.get(&jti).is_some_and(|&exp| exp > now))
+    }
+
+    /// Catat pencabutan lokal (tanpa menunggu NOTIFY balik dari DB)
+    pub fn insert(&self, jti: Uuid, exp: i64) {
+        if exp > chrono::Utc::now().timestamp() {
+            self.entries
+                .write()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(jti, exp);
+        }
+    }
+
+    fn prune(&self) {
+        let now = chrono::Utc::now().timestamp();
+        self.entries
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|_, exp| *exp > now);
+    }
+
+    /// Jalankan listener di background; reconnect otomatis dengan backoff
+    pub fn spawn_listener(self: &Arc<Self>, pool: PgPool) {
+        let cache = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut backoff = Duration::from_secs(1);
+            loop {
+                if let Err(e) = cache.listen(&pool, &mut backoff).await {
+                    tracing::warn!("revoked jti listener down: {}", e);
+                }
+                cache.synced.store(false, Ordering::Release);
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(RECONNECT_BACKOFF_CAP);
+            }
+        });
+    }
+
+    async fn listen(&self, pool: &PgPool, backoff: &mut Duration) -> Result<(), sqlx::Error> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(REVOKED_JTI_CHANNEL).await?;
+
+        // snapshot diambil setelah LISTEN aktif supaya tidak ada pencabutan yang terlewat
+        let rows = sqlx::query(
+            r#"SELECT jti, extract(epoch FROM expires_at)::bigint AS exp
+               FROM lab_revoked_access_tokens
+               WHERE now() < expires_at"#,
+        )
+        .fetch_all(pool)
+        .await?;
+        {
+            let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+            entries.clear();
+            for row in rows {
+                entries.insert(row.try_get("jti")?, row.try_get("exp")?);
+            }
+        }
+        self.synced.store(true, Ordering::Release);
+        *backoff = Duration::from_secs(1);
+        tracing::info!("revoked jti cache synced");
+
+        let mut prune = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            tokio::select! {
+                msg = listener.try_recv() => {
+                    // Ok(None) = koneksi putus; notifikasi selama putus bisa hilang
+                    let Some(msg) = msg? else {
+                        return Err(sqlx::Error::Protocol("listener connection lost".into()));
+                    };
+                    match parse_payload(msg.payload()) {
+                        Some((jti, exp)) => self.insert(jti, exp),
+                        None => tracing::warn!("bad revoked jti payload: {}", msg.payload()),
+                    }
+                }
+                _ = prune.tick() => self.prune(),
+            }
+        }
+    }
+}
+
+fn parse_payload(payload: &str) -> Option<(Uuid, i64)> {
+    let (jti, exp) = payload.split_once(':')?;
+    Some((Uuid::parse_str(jti).ok()?, exp.parse().ok()?))
+}
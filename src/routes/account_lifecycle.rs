@@ -0,0 +1,341 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
+    models::Claims,
+    money::Money,
+    routes::{limits::limit_error, notifications::notify_user},
+    utils::{audit, verify_account_pin},
+};
+
+const DORMANCY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Deserialize)]
+pub struct AccountFreezeReq {
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AccountUnfreezeReq {
+    pub pin: String,
+}
+
+#[derive(Deserialize)]
+pub struct AccountCloseReq {
+    pub pin: String,
+    /// wajib jika saldo > 0: rekening aktif milik user sendiri tujuan pindahan saldo
+    pub sweep_to_account_no: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AccountStatusRes {
+    pub account_id: Uuid,
+    pub status: String,
+    pub previous_status: String,
+}
+
+#[derive(Serialize)]
+pub struct AccountCloseRes {
+    pub account_id: Uuid,
+    pub status: String,
+    pub previous_status: String,
+    pub swept: Money,
+    pub sweep_to_account_no: Option<String>,
+    pub journal_id_credit: Option<Uuid>,
+    pub journal_id_debit: Option<Uuid>,
+}
+
+/// Terjemahkan RAISE status rekening dari fungsi ledger (lab_fun_account_assert_status)
+pub(crate) fn account_status_error(msg: &str) -> Option<ApiError> {
+    if msg.contains("ACCOUNT_FROZEN") {
+        Some(ApiError::Forbidden("account frozen".into()))
+    } else if msg.contains("ACCOUNT_DORMANT") {
+        Some(ApiError::Forbidden("account dormant".into()))
+    } else if msg.contains("ACCOUNT_CLOSED") {
+        Some(ApiError::Forbidden("account closed".into()))
+    } else {
+        None
+    }
+}
+
+/// Error fungsi ledger (deposit/withdraw/post_journal): status rekening / limit → 403, sisanya 500
+pub(crate) fn ledger_error(e: sqlx::Error) -> ApiError {
+    let msg = e.to_string();
+    account_status_error(&msg)
+        .or_else(|| limit_error(&msg))
+        .unwrap_or_else(|| ApiError::from(e))
+}
+
+fn lifecycle_error(e: sqlx::Error) -> ApiError {
+    let msg = e.to_string();
+    if let Some(err) = account_status_error(&msg) {
+        err
+    } else if msg.contains("ACCOUNT_NOT_FOUND") {
+        ApiError::NotFound("account not found".into())
+    } else if msg.contains("ACCOUNT_NOT_OWNED") {
+        ApiError::Forbidden("account not owned".into())
+    } else if msg.contains("FROZEN_BY_ADMIN") {
+        ApiError::Forbidden("account frozen by the bank, contact support".into())
+    } else if msg.contains("STATUS_UNCHANGED") {
+        ApiError::BadRequest("account already in that state".into())
+    } else if msg.contains("BALANCE_NOT_ZERO") {
+        ApiError::BadRequest("balance is not zero, sweep_to_account_no required".into())
+    } else if msg.contains("ACCOUNT_TO_NOT_FOUND") {
+        ApiError::BadRequest("sweep target account not found".into())
+    } else if msg.contains("SAME_ACCOUNT") {
+        ApiError::BadRequest("sweep target must be a different account".into())
+    } else if msg.contains("SWEEP_TARGET_NOT_OWNED") {
+        ApiError::BadRequest("sweep target must belong to the same owner".into())
+    } else {
+        ApiError::Internal(msg)
+    }
+}
+
+/// Tandai rekening tanpa mutasi sebagai dormant, dicek tiap jam.
+/// `ACCOUNT_DORMANT_DAYS` (default 365, 0 = nonaktif)
+pub fn spawn_dormancy_job(pool: PgPool) {
+    let days: i32 = std::env::var("ACCOUNT_DORMANT_DAYS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(365);
+    if days <= 0 {
+        tracing::info!("account dormancy job disabled");
+        return;
+    }
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(DORMANCY_CHECK_INTERVAL);
+        loop {
+            tick.tick().await;
+            let res: Result<i32, _> = sqlx::query_scalar(
+                "SELECT lab_fun_mark_dormant_accounts(make_interval(days => $1))",
+            )
+            .bind(days)
+            .fetch_one(&pool)
+            .await;
+            match res {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("{} accounts marked dormant", n),
+                Err(e) => tracing::warn!("account dormancy job failed: {}", e),
+            }
+        }
+    });
+}
+
+fn notify_owner(state: &SharedState, owner_id: Uuid, title: &'static str, body: String) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        if let Err((_, e)) = notify_user(&state, owner_id, title, &body, None).await {
+            tracing::warn!("account status notification failed: {}", e);
+        }
+    });
+}
+
+pub(crate) async fn set_account_status(
+    state: &SharedState,
+    ctx: &RequestContext,
+    actor_id: Uuid,
+    owner_id: Option<Uuid>,
+    account_id: Uuid,
+    to: &str,
+    reason: Option<&str>,
+) -> Result<AccountStatusRes, ApiError> {
+    let row = sqlx::query(
+        "SELECT owner_id, previous_status FROM lab_fun_account_set_status($1,$2,$3,$4,$5)",
+    )
+    .bind(account_id)
+    .bind(owner_id)
+    .bind(actor_id)
+    .bind(to)
+    .bind(reason)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(lifecycle_error)?;
+    let res = AccountStatusRes {
+        account_id,
+        status: to.into(),
+        previous_status: row.get("previous_status"),
+    };
+
+    let verb = if to == "frozen" { "freeze" } else { "unfreeze" };
+    let action = match owner_id {
+        Some(_) => format!("account_{}", verb),
+        None => format!("admin_account_{}", verb),
+    };
+    let meta = serde_json::json!({
+        "from": res.previous_status,
+        "to": res.status,
+        "reason": reason,
+    });
+    audit(
+        state,
+        ctx,
+        Some(actor_id),
+        &action,
+        Some(&account_id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    if owner_id.is_none() {
+        let (title, body) = if to == "frozen" {
+            (
+                "Rekening dibekukan",
+                "Rekening Anda dibekukan oleh bank. Hubungi layanan nasabah untuk informasi lebih lanjut.",
+            )
+        } else {
+            (
+                "Rekening aktif kembali",
+                "Rekening Anda telah diaktifkan kembali.",
+            )
+        };
+        notify_owner(state, row.get("owner_id"), title, body.into());
+    }
+    Ok(res)
+}
+
+pub(crate) async fn close_and_sweep(
+    state: &SharedState,
+    ctx: &RequestContext,
+    actor_id: Uuid,
+    owner_id: Option<Uuid>,
+    account_id: Uuid,
+    sweep_to_account_no: Option<String>,
+    reason: Option<&str>,
+) -> Result<AccountCloseRes, ApiError> {
+    let row = sqlx::query(
+        r#"SELECT owner_id, previous_status, swept, journal_id_credit, journal_id_debit
+           FROM lab_fun_account_close($1,$2,$3,$4,$5)"#,
+    )
+    .bind(account_id)
+    .bind(owner_id)
+    .bind(actor_id)
+    .bind(sweep_to_account_no.as_deref())
+    .bind(reason)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(lifecycle_error)?;
+
+    let swept: Money = row.get("swept");
+    let res = AccountCloseRes {
+        account_id,
+        status: "closed".into(),
+        previous_status: row.get("previous_status"),
+        sweep_to_account_no: swept.is_positive().then_some(sweep_to_account_no).flatten(),
+        swept,
+        journal_id_credit: row.get("journal_id_credit"),
+        journal_id_debit: row.get("journal_id_debit"),
+    };
+
+    let action = if owner_id.is_some() {
+        "account_close"
+    } else {
+        "admin_account_close"
+    };
+    let meta = serde_json::json!({
+        "from": res.previous_status,
+        "swept": res.swept,
+        "sweep_to_account_no": res.sweep_to_account_no,
+        "reason": reason,
+    });
+    audit(
+        state,
+        ctx,
+        Some(actor_id),
+        action,
+        Some(&account_id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    let body = if res.swept.is_positive() {
+        format!(
+            "Rekening Anda telah ditutup. Saldo {} dipindahkan ke rekening {}.",
+            res.swept,
+            res.sweep_to_account_no.as_deref().unwrap_or("-")
+        )
+    } else {
+        "Rekening Anda telah ditutup.".into()
+    };
+    notify_owner(state, row.get("owner_id"), "Rekening ditutup", body);
+    Ok(res)
+}
+
+/// POST /accounts/:account_id/freeze — bekukan rekening sendiri (mis. HP hilang); tanpa PIN
+pub async fn freeze_account(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(account_id): Path<Uuid>,
+    Json(req): Json<AccountFreezeReq>,
+) -> ApiResult<Json<AccountStatusRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    let res = set_account_status(
+        &state,
+        &ctx,
+        user_id,
+        Some(user_id),
+        account_id,
+        "frozen",
+        req.reason.as_deref(),
+    )
+    .await?;
+    Ok(Json(res))
+}
+
+/// POST /accounts/:account_id/unfreeze — aktifkan kembali rekening frozen (oleh owner) atau dormant
+pub async fn unfreeze_account(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(account_id): Path<Uuid>,
+    Json(req): Json<AccountUnfreezeReq>,
+) -> ApiResult<Json<AccountStatusRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    verify_account_pin(&state, &ctx, user_id, account_id, &req.pin).await?;
+    let res = set_account_status(
+        &state,
+        &ctx,
+        user_id,
+        Some(user_id),
+        account_id,
+        "active",
+        None,
+    )
+    .await?;
+    Ok(Json(res))
+}
+
+/// POST /accounts/:account_id/close — tutup rekening; saldo harus nol atau dipindah
+pub async fn close_account(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(account_id): Path<Uuid>,
+    Json(req): Json<AccountCloseReq>,
+) -> ApiResult<Json<AccountCloseRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    verify_account_pin(&state, &ctx, user_id, account_id, &req.pin).await?;
+    let res = close_and_sweep(
+        &state,
+        &ctx,
+        user_id,
+        Some(user_id),
+        account_id,
+        req.sweep_to_account_no,
+        req.reason.as_deref(),
+    )
+    .await?;
+    Ok(Json(res))
+}
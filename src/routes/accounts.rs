@@ -3,27 +3,52 @@ use axum::{
     Extension, Json,
 };
 use serde::{Deserialize, Serialize}; // <-- import Serialize juga
-use sqlx::Row;
+use sqlx::{PgPool, Row};
 use uuid::Uuid;
 
 use crate::{
     app_state::SharedState,
     errors::{ApiError, ApiResult},
+    middleware::{request_context::RequestContext, request_signature::SignedClient},
     models::Claims,
-    utils::audit,
+    money::Money,
+    routes::{
+        account_lifecycle::account_status_error,
+        limits::limit_error,
+        notifications::notify_user,
+        step_up::{ensure_step_up, require_step_up, StepUpOperation, StepUpProof},
+    },
+    utils::{audit, validate_new_pin, verify_account_pin},
 };
 
+const LEGACY_PIN_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Rekening baru selalu bersaldo nol; `initial_balance` lama ditolak (unknown field)
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct AccountOpenReq {
     pub pin: String,
-    pub initial_balance: Option<f64>,
+    pub funding: Option<AccountFundingReq>,
+}
+
+/// Setoran awal: transfer dari rekening lain milik user sendiri
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountFundingReq {
+    pub from_account_id: Uuid,
+    pub amount: Money,
+    /// PIN rekening sumber
+    pub pin: String,
+    pub step_up: Option<StepUpProof>,
 }
 
 #[derive(Serialize)]
 pub struct AccountRes {
     pub id: Uuid,
     pub account_no: String,
-    pub saldo: f64,
+    pub saldo: Money,
+    /// active | frozen | dormant | closed
+    pub status: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
@@ -36,6 +61,9 @@ pub struct AccountsListRes {
 #[derive(Deserialize)]
 pub struct UpdatePinReq {
     pub new_pin: String,
+    /// PIN saat ini; jika lupa PIN, kirim `step_up` (challenge operasi `pin_reset`) sebagai gantinya
+    pub current_pin: Option<String>,
+    pub step_up: Option<StepUpProof>,
 }
 
 #[derive(Deserialize)]
@@ -46,9 +74,9 @@ pub struct VerifyAccountReq {
 #[derive(Serialize)]
 pub struct VerifyAccountRes {
     pub account_no: String,
+    /// Nama pemilik tersamar, mis. "B**i S*****o"
     pub owner_name: Option<String>,
     pub status: String,
-    pub email: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -74,6 +102,42 @@ pub struct GetRekeningByNoAccountReq {
     pub no_account: String,
 }
 
+/// Batas lookup rekening (verify / cari penerima) per user app
+const ACCOUNT_LOOKUP_PER_MINUTE: i32 = 10;
+const ACCOUNT_LOOKUP_PER_DAY: i32 = 200;
+/// Batas lookup per sistem corp (request bertanda tangan)
+const CLIENT_LOOKUP_PER_MINUTE: i32 = 120;
+const CLIENT_LOOKUP_PER_DAY: i32 = 20_000;
+
+/// Pemanggil lookup rekening yang dihitung kuotanya
+pub(crate) enum LookupCaller<'a> {
+    User(Uuid),
+    SignedClient(&'a str),
+}
+
+impl LookupCaller<'_> {
+    fn principal(&self) -> String {
+        match self {
+            LookupCaller::User(user_id) => format!("user:{}", user_id),
+            LookupCaller::SignedClient(client_id) => format!("client:{}", client_id),
+        }
+    }
+
+    fn limits(&self) -> (i32, i32) {
+        match self {
+            LookupCaller::User(_) => (ACCOUNT_LOOKUP_PER_MINUTE, ACCOUNT_LOOKUP_PER_DAY),
+            LookupCaller::SignedClient(_) => (CLIENT_LOOKUP_PER_MINUTE, CLIENT_LOOKUP_PER_DAY),
+        }
+    }
+
+    fn user_id(&self) -> Option<Uuid> {
+        match self {
+            LookupCaller::User(user_id) => Some(*user_id),
+            LookupCaller::SignedClient(_) => None,
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct TransferReceiverRes {
     pub to_account_no: String,
@@ -82,26 +146,66 @@ pub struct TransferReceiverRes {
 
 pub async fn open_account(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Json(req): Json<AccountOpenReq>,
 ) -> ApiResult<Json<AccountRes>> {
-    if req.pin.len() != 6 || !req.pin.chars().all(|c| c.is_ascii_digit()) {
-        return Err(ApiError::BadRequest("pin must be 6 digits".into()).into());
-    }
+    validate_new_pin(&req.pin)?;
     let user_id =
         Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    if let Some(funding) = &req.funding {
+        if !funding.amount.is_positive() {
+            return Err(ApiError::BadRequest("funding amount must be > 0".into()).into());
+        }
+        verify_account_pin(&state, &ctx, user_id, funding.from_account_id, &funding.pin).await?;
+        let op = StepUpOperation::AccountFunding {
+            from_account_id: funding.from_account_id,
+            amount: funding.amount.clone(),
+        };
+        ensure_step_up(
+            &state,
+            &ctx,
+            user_id,
+            &op,
+            &funding.amount,
+            false,
+            funding.step_up.as_ref(),
+        )
+        .await?;
+    }
 
-    let row = sqlx::query("SELECT * FROM lab_fun_open_account($1,$2,$3)")
-        .bind(user_id)
-        .bind(&req.pin)
-        .bind(req.initial_balance.unwrap_or(0.0_f64))
-        .fetch_one(&state.pool)
-        .await
-        .map_err(ApiError::from)?;
+    let pin_hash = state.password_policy.hash(&req.pin)?;
+
+    let row = sqlx::query(
+        r#"SELECT account_id, journal_id_credit, journal_id_debit
+           FROM lab_fun_open_account($1,$2,$3,$4)"#,
+    )
+    .bind(user_id)
+    .bind(&pin_hash)
+    .bind(req.funding.as_ref().map(|f| f.from_account_id))
+    .bind(req.funding.as_ref().map(|f| &f.amount))
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| {
+        let msg = e.to_string();
+        if let Some(err) = account_status_error(&msg).or_else(|| limit_error(&msg)) {
+            err
+        } else if msg.contains("ACCOUNT_NOT_OWNED") || msg.contains("ACCOUNT_FROM_NOT_FOUND") {
+            ApiError::BadRequest("funding account not found".into())
+        } else if msg.contains("INSUFFICIENT_FUNDS") {
+            ApiError::BadRequest("insufficient funds".into())
+        } else if msg.contains("AMOUNT_INVALID") {
+            ApiError::BadRequest("amount invalid".into())
+        } else {
+            ApiError::Internal(msg)
+        }
+    })?;
     let account_id: Uuid = row.get("account_id");
+    let journal_id_credit: Option<Uuid> = row.get("journal_id_credit");
+    let journal_id_debit: Option<Uuid> = row.get("journal_id_debit");
 
     let row = sqlx::query(
-        r#"SELECT id, account_no, saldo::float8 AS saldo, created_at, updated_at
+        r#"SELECT id, account_no, saldo, status, created_at, updated_at
            FROM lab_accounts WHERE id = $1"#,
     )
     .bind(account_id)
@@ -112,17 +216,24 @@ pub async fn open_account(
     let acc = AccountRes {
         id: row.get("id"),
         account_no: row.get("account_no"),
-        saldo: row.get::<f64, _>("saldo"),
+        saldo: row.get::<Money, _>("saldo"),
+        status: row.get("status"),
         created_at: row.get("created_at"),
         updated_at: row.get("updated_at"),
     };
 
     let meta = serde_json::json!({
         "account_no": acc.account_no,
-        "initial_balance": req.initial_balance.unwrap_or(0.0_f64)
+        "funding": req.funding.as_ref().map(|f| serde_json::json!({
+            "from_account_id": f.from_account_id,
+            "amount": f.amount,
+            "journal_id_credit": journal_id_credit,
+            "journal_id_debit": journal_id_debit,
+        })),
     });
     audit(
         &state,
+        &ctx,
         Some(user_id),
         "account_open",
         Some(&acc.id.to_string()),
@@ -135,42 +246,108 @@ pub async fn open_account(
 
 pub async fn update_account_pin(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Path(account_id): Path<Uuid>,
     Json(req): Json<UpdatePinReq>,
 ) -> ApiResult<axum::http::StatusCode> {
-    if req.new_pin.len() != 6 || !req.new_pin.chars().all(|c| c.is_ascii_digit()) {
-        return Err(ApiError::BadRequest("new_pin must be 6 digits".into()).into());
-    }
+    validate_new_pin(&req.new_pin)?;
     let user_id =
         Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
 
+    let method = match (&req.current_pin, &req.step_up) {
+        (Some(current_pin), _) => {
+            verify_account_pin(&state, &ctx, user_id, account_id, current_pin).await?;
+            if current_pin == &req.new_pin {
+                return Err(
+                    ApiError::BadRequest("new_pin must differ from current PIN".into()).into(),
+                );
+            }
+            "current_pin"
+        }
+        (None, Some(proof)) => {
+            let op = StepUpOperation::PinReset { account_id };
+            require_step_up(&state, &ctx, user_id, &op, Some(proof)).await?;
+            "step_up"
+        }
+        (None, None) => {
+            return Err(ApiError::BadRequest("current_pin or step_up is required".into()).into())
+        }
+    };
+
+    let new_pin_hash = state.password_policy.hash(&req.new_pin)?;
+
     let ok = sqlx::query_scalar!(
         r#"SELECT lab_fun_update_account_pin($1,$2,$3) AS ok"#,
         user_id,
         account_id,
-        req.new_pin
+        new_pin_hash
     )
     .fetch_one(&state.pool)
     .await
     .map_err(ApiError::from)?;
 
     if ok.unwrap_or(false) {
-        let meta = serde_json::json!({ "account_id": account_id });
+        let meta = serde_json::json!({ "account_id": account_id, "method": method });
         audit(
             &state,
+            &ctx,
             Some(user_id),
             "account_pin_update",
             Some(&account_id.to_string()),
             Some(meta),
         )
         .await;
+
+        let notify_state = state.clone();
+        tokio::spawn(async move {
+            let account_no: String =
+                sqlx::query_scalar("SELECT account_no FROM lab_accounts WHERE id = $1")
+                    .bind(account_id)
+                    .fetch_one(&notify_state.pool)
+                    .await
+                    .unwrap_or_default();
+            let body = format!(
+                "PIN rekening {} baru saja diubah. Jika bukan Anda, segera bekukan rekening dan hubungi kami.",
+                account_no
+            );
+            let res = notify_user(&notify_state, user_id, "PIN rekening diubah", &body, None).await;
+            if let Err((_, e)) = res {
+                tracing::warn!("pin change notification failed: {}", e);
+            }
+        });
         Ok(axum::http::StatusCode::OK)
     } else {
         Err(ApiError::BadRequest("account not found or not owner".into()).into())
     }
 }
 
+/// Hapus hash PIN bcrypt lama yang tidak di-upgrade dalam masa tenggang, dicek tiap jam.
+/// `LEGACY_PIN_GRACE_DAYS` (default 30); pemilik lalu wajib mengatur PIN baru lewat step-up.
+pub fn spawn_legacy_pin_expiry_job(pool: PgPool) {
+    let days: i32 = std::env::var("LEGACY_PIN_GRACE_DAYS")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(30)
+        .max(0);
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(LEGACY_PIN_CHECK_INTERVAL);
+        loop {
+            tick.tick().await;
+            let res: Result<i32, _> =
+                sqlx::query_scalar("SELECT lab_fun_expire_legacy_pins(make_interval(days => $1))")
+                    .bind(days)
+                    .fetch_one(&pool)
+                    .await;
+            match res {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("{} legacy account PINs expired, reset required", n),
+                Err(e) => tracing::warn!("legacy PIN expiry job failed: {}", e),
+            }
+        }
+    });
+}
+
 pub async fn list_accounts(
     State(state): State<SharedState>,
     Extension(claims): Extension<Claims>,
@@ -179,7 +356,7 @@ pub async fn list_accounts(
         Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
 
     let rows = sqlx::query(
-        r#"SELECT id, account_no, saldo::float8 AS saldo, created_at, updated_at
+        r#"SELECT id, account_no, saldo, status, created_at, updated_at
            FROM lab_fun_list_accounts_by_user($1)"#,
     )
     .bind(user_id)
@@ -192,7 +369,8 @@ pub async fn list_accounts(
         items.push(AccountRes {
             id: r.get("id"),
             account_no: r.get("account_no"),
-            saldo: r.get::<f64, _>("saldo"),
+            saldo: r.get::<Money, _>("saldo"),
+            status: r.get("status"),
             created_at: r.get("created_at"),
             updated_at: r.get("updated_at"),
         });
@@ -201,14 +379,79 @@ pub async fn list_accounts(
     Ok(Json(AccountsListRes { items }))
 }
 
+/// Samarkan nama per kata: huruf pertama & terakhir tetap, sisanya '*'
+/// ("Budi Santoso" → "B**i S*****o"; kata 1-2 huruf → "B*" / "*")
+pub(crate) fn mask_name(name: &str) -> String {
+    name.split_whitespace()
+        .map(|word| {
+            let chars: Vec<char> = word.chars().collect();
+            match chars.len() {
+                1 => "*".to_string(),
+                2 => format!("{}*", chars[0]),
+                n => format!("{}{}{}", chars[0], "*".repeat(n - 2), chars[n - 1]),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Hitung satu lookup rekening untuk pemanggil; lewat batas → 429
+pub(crate) async fn ensure_lookup_allowed(
+    state: &SharedState,
+    ctx: &RequestContext,
+    caller: &LookupCaller<'_>,
+    action: &str,
+) -> Result<(), ApiError> {
+    let principal = caller.principal();
+    let (per_minute, per_day) = caller.limits();
+    let allowed: bool = sqlx::query_scalar("SELECT lab_fun_account_lookup_hit($1, $2, $3)")
+        .bind(&principal)
+        .bind(per_minute)
+        .bind(per_day)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    if !allowed {
+        audit(
+            state,
+            ctx,
+            caller.user_id(),
+            "account_lookup_throttled",
+            Some(&principal),
+            Some(serde_json::json!({ "endpoint": action })),
+        )
+        .await;
+        return Err(ApiError::TooManyRequests(
+            "too many account lookups, try again later".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// POST /accounts/verify — user app (Bearer) atau sistem corp (request bertanda tangan)
 pub async fn verify_account(
     State(state): State<SharedState>,
+    ctx: RequestContext,
+    claims: Option<Extension<Claims>>,
+    client: Option<Extension<SignedClient>>,
     Json(req): Json<VerifyAccountReq>,
 ) -> ApiResult<Json<VerifyAccountRes>> {
+    let caller = match (&claims, &client) {
+        (_, Some(Extension(client))) => LookupCaller::SignedClient(&client.0),
+        (Some(Extension(claims)), None) => LookupCaller::User(
+            Uuid::parse_str(&claims.sub)
+                .map_err(|_| ApiError::Unauthorized("bad subject".into()))?,
+        ),
+        (None, None) => return Err(ApiError::Unauthorized("Missing Authorization".into()).into()),
+    };
+    ensure_lookup_allowed(&state, &ctx, &caller, "accounts_verify").await?;
+
     let row = sqlx::query(
         r#"
-        SELECT account_no, owner_name, status,email
-        FROM lab_fun_verify_account($1)
+        SELECT v.account_no, v.owner_name, COALESCE(a.status, v.status) AS status
+        FROM lab_fun_verify_account($1) v
+        LEFT JOIN lab_accounts a ON a.account_no = v.account_no
         "#,
     )
     .bind(&req.account_no)
@@ -221,7 +464,6 @@ pub async fn verify_account(
             account_no: req.account_no,
             owner_name: None,
             status: "not_found".to_string(),
-            email: None,
         }));
     };
 
@@ -229,44 +471,37 @@ pub async fn verify_account(
         account_no: row.try_get("account_no").unwrap_or_default(),
         owner_name: row
             .try_get::<Option<String>, _>("owner_name")
-            .unwrap_or(None),
+            .unwrap_or(None)
+            .map(|name| mask_name(&name)),
         status: row
             .try_get::<String, _>("status")
             .unwrap_or_else(|_| "unknown".to_string()),
-        email: row.try_get::<Option<String>, _>("email").unwrap_or(None),
     }))
 }
 
 pub async fn check_pin(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Json(req): Json<CheckPinReq>,
 ) -> ApiResult<Json<CheckPinRes>> {
-    if req.pin.len() != 6 || !req.pin.chars().all(|c| c.is_ascii_digit()) {
-        return Err(ApiError::BadRequest("pin must be 6 digits".into()).into());
-    }
-
     let user_id =
         Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
 
-    let valid = sqlx::query_scalar!(
-        r#"SELECT lab_fun_verify_account_pin($1,$2,$3) AS ok"#,
-        user_id,
-        req.account_id,
-        req.pin
-    )
-    .fetch_one(&state.pool)
-    .await
-    .map_err(ApiError::from)?
-    .unwrap_or(false);
+    // salah PIN tetap dihitung ke counter kunci; PIN terkunci → 429
+    let valid = match verify_account_pin(&state, &ctx, user_id, req.account_id, &req.pin).await {
+        Ok(()) => true,
+        Err(ApiError::Unauthorized(_)) => false,
+        Err(e) => return Err(e.into()),
+    };
 
     let meta = serde_json::json!({
         "account_id": req.account_id,
         "valid": valid,
-        "pin_hash":  req.pin
     });
     audit(
         &state,
+        &ctx,
         Some(user_id),
         "account_pin_check",
         Some(&req.account_id.to_string()),
@@ -305,8 +540,20 @@ pub async fn list_rekening_pt(
 
 pub async fn get_rekening_by_no_account(
     State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
     Query(req): Query<GetRekeningByNoAccountReq>,
 ) -> ApiResult<Json<Vec<TransferReceiverRes>>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    ensure_lookup_allowed(
+        &state,
+        &ctx,
+        &LookupCaller::User(user_id),
+        "get_rekening_by_no_account",
+    )
+    .await?;
+
     let rows = sqlx::query(
         r#"
         SELECT *
@@ -322,7 +569,7 @@ pub async fn get_rekening_by_no_account(
         .into_iter()
         .map(|row| TransferReceiverRes {
             to_account_no: row.try_get("to_account_no").unwrap_or_default(),
-            nama_lengkap: row.try_get("nama_lengkap").unwrap_or_default(),
+            nama_lengkap: mask_name(&row.try_get::<String, _>("nama_lengkap").unwrap_or_default()),
         })
         .collect();
 
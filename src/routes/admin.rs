@@ -1,12 +1,77 @@
-use axum::{extract::State, Extension, Json};
-use serde::Serialize;
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
 
 use crate::{
     app_state::SharedState,
     errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
     models::Claims,
+    money::Money,
+    routes::account_lifecycle::{
+        close_and_sweep, ledger_error, set_account_status, AccountCloseRes, AccountStatusRes,
+    },
+    routes::digiflaz::{apply_ppob_reversal, record_failed_ppob_reversal, PpobCharge},
+    utils::audit,
 };
 
+#[derive(Deserialize)]
+pub struct AdminAccountStatusReq {
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct AdminAccountCloseReq {
+    pub reason: String,
+    pub sweep_to_account_no: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AdminUserTierReq {
+    pub tier: String,
+}
+
+#[derive(Serialize)]
+pub struct AdminUserTierRes {
+    pub user_id: Uuid,
+    pub tier: String,
+    pub previous_tier: String,
+}
+
+#[derive(Deserialize)]
+pub struct PpobReversalQuery {
+    /// pending | done; kosong = semua
+    pub status: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PpobReversalRes {
+    pub charge_journal_id: Uuid,
+    pub user_id: Uuid,
+    pub account_id: Uuid,
+    pub amount: Money,
+    pub charged_at: DateTime<Utc>,
+    pub ref_id: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub reversal_journal_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub reversed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct PpobReversalRetryRes {
+    pub charge_journal_id: Uuid,
+    pub reversal_journal_id: Uuid,
+    pub balance_after: Money,
+}
+
 #[derive(Serialize)]
 pub struct AuditLogRes {
     pub id: uuid::Uuid,
@@ -19,7 +84,7 @@ pub struct AuditLogRes {
 
 pub async fn list_audit_logs(
     State(state): State<SharedState>,
-    Extension(_claims): Extension<Claims>, // sudah lewat auth & rbac (admin)
+    Extension(_claims): Extension<Claims>, // sudah lewat auth & guard audit:read
 ) -> ApiResult<Json<Vec<AuditLogRes>>> {
     let rows = sqlx::query!(
         r#"SELECT id, user_id, action, ip_addr, user_agent, created_at
@@ -45,3 +110,268 @@ pub async fn list_audit_logs(
 
     Ok(Json(logs))
 }
+
+/// POST /admin/users/:user_id/mfa/reset — hapus 2FA user (mis. device hilang & recovery code habis)
+pub async fn reset_user_mfa(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(user_id): Path<Uuid>,
+) -> ApiResult<axum::http::StatusCode> {
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let found: bool = sqlx::query_scalar("SELECT lab_fun_mfa_reset($1)")
+        .bind(user_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if !found {
+        return Err(ApiError::NotFound("mfa not enrolled".into()).into());
+    }
+
+    let meta = serde_json::json!({ "user_id": user_id });
+    audit(
+        &state,
+        &ctx,
+        Some(admin_id),
+        "admin_mfa_reset",
+        Some(&user_id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    Ok(axum::http::StatusCode::OK)
+}
+
+/// POST /admin/accounts/:account_id/freeze
+pub async fn freeze_account(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(account_id): Path<Uuid>,
+    Json(req): Json<AdminAccountStatusReq>,
+) -> ApiResult<Json<AccountStatusRes>> {
+    if req.reason.trim().is_empty() {
+        return Err(ApiError::BadRequest("reason is required".into()).into());
+    }
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    let res = set_account_status(
+        &state,
+        &ctx,
+        admin_id,
+        None,
+        account_id,
+        "frozen",
+        Some(&req.reason),
+    )
+    .await?;
+    Ok(Json(res))
+}
+
+/// POST /admin/accounts/:account_id/unfreeze — termasuk rekening dormant
+pub async fn unfreeze_account(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(account_id): Path<Uuid>,
+    Json(req): Json<AdminAccountStatusReq>,
+) -> ApiResult<Json<AccountStatusRes>> {
+    if req.reason.trim().is_empty() {
+        return Err(ApiError::BadRequest("reason is required".into()).into());
+    }
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    let res = set_account_status(
+        &state,
+        &ctx,
+        admin_id,
+        None,
+        account_id,
+        "active",
+        Some(&req.reason),
+    )
+    .await?;
+    Ok(Json(res))
+}
+
+/// POST /admin/accounts/:account_id/close
+pub async fn close_account(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(account_id): Path<Uuid>,
+    Json(req): Json<AdminAccountCloseReq>,
+) -> ApiResult<Json<AccountCloseRes>> {
+    if req.reason.trim().is_empty() {
+        return Err(ApiError::BadRequest("reason is required".into()).into());
+    }
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    let res = close_and_sweep(
+        &state,
+        &ctx,
+        admin_id,
+        None,
+        account_id,
+        req.sweep_to_account_no,
+        Some(&req.reason),
+    )
+    .await?;
+    Ok(Json(res))
+}
+
+/// PATCH /admin/users/:user_id/tier — ubah tier limit transaksi user (basic/verified/premium)
+pub async fn set_user_tier(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(user_id): Path<Uuid>,
+    Json(req): Json<AdminUserTierReq>,
+) -> ApiResult<Json<AdminUserTierRes>> {
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let previous_tier: String = sqlx::query_scalar("SELECT lab_fun_set_user_tier($1,$2)")
+        .bind(user_id)
+        .bind(req.tier.trim())
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| {
+            let msg = e.to_string();
+            if msg.contains("TIER_INVALID") {
+                ApiError::BadRequest("tier invalid".into())
+            } else if msg.contains("USER_NOT_FOUND") {
+                ApiError::NotFound("user not found".into())
+            } else {
+                ApiError::Internal(msg)
+            }
+        })?;
+
+    let res = AdminUserTierRes {
+        user_id,
+        tier: req.tier.trim().into(),
+        previous_tier,
+    };
+    let meta = serde_json::json!({ "from": res.previous_tier, "to": res.tier });
+    audit(
+        &state,
+        &ctx,
+        Some(admin_id),
+        "admin_user_tier",
+        Some(&user_id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    Ok(Json(res))
+}
+
+/// GET /admin/ppob/reversals?status=pending — reversal PPOB (yang gagal menunggu dicoba ulang)
+pub async fn list_ppob_reversals(
+    State(state): State<SharedState>,
+    Extension(_claims): Extension<Claims>,
+    Query(q): Query<PpobReversalQuery>,
+) -> ApiResult<Json<Vec<PpobReversalRes>>> {
+    if let Some(status) = q.status.as_deref() {
+        if !matches!(status, "pending" | "done") {
+            return Err(ApiError::BadRequest("status must be pending|done".into()).into());
+        }
+    }
+    let rows = sqlx::query(
+        r#"SELECT charge_journal_id, user_id, account_id, amount, charged_at, ref_id, status,
+                  attempts, last_error, reversal_journal_id, created_at, reversed_at
+           FROM lab_fun_list_ppob_reversals($1)"#,
+    )
+    .bind(q.status.as_deref())
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::from)?;
+
+    let mut items = Vec::with_capacity(rows.len());
+    for row in rows {
+        items.push(PpobReversalRes {
+            charge_journal_id: row.try_get("charge_journal_id").map_err(ApiError::from)?,
+            user_id: row.try_get("user_id").map_err(ApiError::from)?,
+            account_id: row.try_get("account_id").map_err(ApiError::from)?,
+            amount: row.try_get("amount").map_err(ApiError::from)?,
+            charged_at: row.try_get("charged_at").map_err(ApiError::from)?,
+            ref_id: row.try_get("ref_id").map_err(ApiError::from)?,
+            status: row.try_get("status").map_err(ApiError::from)?,
+            attempts: row.try_get("attempts").map_err(ApiError::from)?,
+            last_error: row.try_get("last_error").map_err(ApiError::from)?,
+            reversal_journal_id: row.try_get("reversal_journal_id").map_err(ApiError::from)?,
+            created_at: row.try_get("created_at").map_err(ApiError::from)?,
+            reversed_at: row.try_get("reversed_at").map_err(ApiError::from)?,
+        });
+    }
+
+    Ok(Json(items))
+}
+
+/// POST /admin/ppob/reversals/:journal_id/retry — coba ulang refund debit PPOB yang gagal di-reversal
+pub async fn retry_ppob_reversal(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(journal_id): Path<Uuid>,
+) -> ApiResult<Json<PpobReversalRetryRes>> {
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let row = sqlx::query(
+        r#"SELECT user_id, account_id, amount, charged_at, ref_id, status
+           FROM lab_ppob_reversals WHERE charge_journal_id = $1"#,
+    )
+    .bind(journal_id)
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(ApiError::from)?
+    .ok_or_else(|| ApiError::NotFound("reversal not found".into()))?;
+    if row.try_get::<String, _>("status").map_err(ApiError::from)? != "pending" {
+        return Err(ApiError::BadRequest("charge already reversed".into()).into());
+    }
+
+    let user_id: Uuid = row.try_get("user_id").map_err(ApiError::from)?;
+    let charge = PpobCharge {
+        journal_id,
+        account_id: row.try_get("account_id").map_err(ApiError::from)?,
+        amount: row.try_get("amount").map_err(ApiError::from)?,
+        charged_at: row.try_get("charged_at").map_err(ApiError::from)?,
+        ref_id: row.try_get("ref_id").map_err(ApiError::from)?,
+    };
+
+    let (reversal_journal_id, balance_after) =
+        match apply_ppob_reversal(&state, user_id, &charge).await {
+            Ok(res) => res,
+            Err(e) if e.to_string().contains("REVERSAL_DONE") => {
+                return Err(ApiError::BadRequest("charge already reversed".into()).into());
+            }
+            Err(e) => {
+                record_failed_ppob_reversal(&state, user_id, &charge, &e.to_string()).await;
+                return Err(ledger_error(e).into());
+            }
+        };
+
+    let meta = serde_json::json!({
+        "ref_id": charge.ref_id,
+        "user_id": user_id,
+        "reversal_journal_id": reversal_journal_id,
+    });
+    audit(
+        &state,
+        &ctx,
+        Some(admin_id),
+        "admin_ppob_refund",
+        Some(&journal_id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    Ok(Json(PpobReversalRetryRes {
+        charge_journal_id: journal_id,
+        reversal_journal_id,
+        balance_after,
+    }))
+}
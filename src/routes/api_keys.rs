@@ -0,0 +1,248 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
+    models::Claims,
+    utils::{audit, random_token, sha256_bytes},
+};
+
+const API_KEY_PREFIX: &str = "lak_";
+/// Panjang awalan key yang disimpan plaintext untuk identifikasi di daftar admin
+const API_KEY_DISPLAY_LEN: usize = 12;
+const ROTATE_GRACE_SECS_DEFAULT: i32 = 60 * 60;
+const ROTATE_GRACE_SECS_MAX: i32 = 60 * 60 * 24 * 7;
+
+#[derive(Deserialize)]
+pub struct CreateApiKeyReq {
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// IP / CIDR yang boleh memakai key; kosong = semua IP
+    #[serde(default)]
+    pub ip_allowlist: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct RotateApiKeyReq {
+    /// berapa lama key lama masih diterima setelah rotasi (detik)
+    pub grace_secs: Option<i32>,
+}
+
+/// Key plaintext hanya dikembalikan sekali, saat create/rotate
+#[derive(Serialize)]
+pub struct ApiKeySecretRes {
+    pub id: Uuid,
+    pub api_key: String,
+    pub key_prefix: String,
+}
+
+#[derive(Serialize)]
+pub struct ApiKeyRes {
+    pub id: Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub scopes: Vec<String>,
+    pub ip_allowlist: Option<Vec<String>>,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
+}
+
+fn generate_key() -> (String, String) {
+    let key = format!("{}{}", API_KEY_PREFIX, random_token());
+    let prefix = key[..API_KEY_DISPLAY_LEN].to_string();
+    (key, prefix)
+}
+
+/// POST /admin/api-keys
+pub async fn create_api_key(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<CreateApiKeyReq>,
+) -> ApiResult<Json<ApiKeySecretRes>> {
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    if req.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name is required".into()).into());
+    }
+    if req.scopes.is_empty() {
+        return Err(ApiError::BadRequest("at least one scope is required".into()).into());
+    }
+
+    let (api_key, key_prefix) = generate_key();
+    let id: Uuid =
+        sqlx::query_scalar("SELECT lab_fun_api_key_create($1,$2,$3,$4,$5::text[]::inet[],$6,$7)")
+            .bind(req.name.trim())
+            .bind(&key_prefix)
+            .bind(sha256_bytes(&api_key))
+            .bind(&req.scopes)
+            .bind(&req.ip_allowlist)
+            .bind(req.expires_at)
+            .bind(admin_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(|e| {
+                let msg = e.to_string();
+                if msg.contains("SCOPE_UNKNOWN") {
+                    ApiError::BadRequest("unknown scope".into())
+                } else if msg.contains("type inet") {
+                    ApiError::BadRequest("invalid ip_allowlist entry".into())
+                } else {
+                    ApiError::Internal(msg)
+                }
+            })?;
+
+    let meta = serde_json::json!({
+        "name": req.name.trim(),
+        "scopes": req.scopes,
+        "ip_allowlist": req.ip_allowlist,
+        "key_prefix": key_prefix,
+    });
+    audit(
+        &state,
+        &ctx,
+        Some(admin_id),
+        "api_key_create",
+        Some(&id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    Ok(Json(ApiKeySecretRes {
+        id,
+        api_key,
+        key_prefix,
+    }))
+}
+
+/// GET /admin/api-keys
+pub async fn list_api_keys(
+    State(state): State<SharedState>,
+    Extension(_claims): Extension<Claims>,
+) -> ApiResult<Json<Vec<ApiKeyRes>>> {
+    let rows = sqlx::query(
+        r#"SELECT id, name, key_prefix, scopes, ip_allowlist, created_by, created_at,
+                  rotated_at, expires_at, revoked_at, last_used_at, last_used_ip
+           FROM lab_fun_api_key_list()"#,
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::from)?;
+
+    let mut items = Vec::with_capacity(rows.len());
+    for row in rows {
+        items.push(ApiKeyRes {
+            id: row.try_get("id").map_err(ApiError::from)?,
+            name: row.try_get("name").map_err(ApiError::from)?,
+            key_prefix: row.try_get("key_prefix").map_err(ApiError::from)?,
+            scopes: row.try_get("scopes").map_err(ApiError::from)?,
+            ip_allowlist: row.try_get("ip_allowlist").map_err(ApiError::from)?,
+            created_by: row.try_get("created_by").map_err(ApiError::from)?,
+            created_at: row.try_get("created_at").map_err(ApiError::from)?,
+            rotated_at: row.try_get("rotated_at").map_err(ApiError::from)?,
+            expires_at: row.try_get("expires_at").map_err(ApiError::from)?,
+            revoked_at: row.try_get("revoked_at").map_err(ApiError::from)?,
+            last_used_at: row.try_get("last_used_at").map_err(ApiError::from)?,
+            last_used_ip: row.try_get("last_used_ip").map_err(ApiError::from)?,
+        });
+    }
+
+    Ok(Json(items))
+}
+
+/// POST /admin/api-keys/:key_id/rotate — key baru; key lama masih berlaku selama grace_secs
+pub async fn rotate_api_key(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(key_id): Path<Uuid>,
+    req: Option<Json<RotateApiKeyReq>>,
+) -> ApiResult<Json<ApiKeySecretRes>> {
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    let req = req.map(|Json(r)| r).unwrap_or_default();
+    let grace_secs = req.grace_secs.unwrap_or(ROTATE_GRACE_SECS_DEFAULT);
+    if !(0..=ROTATE_GRACE_SECS_MAX).contains(&grace_secs) {
+        return Err(ApiError::BadRequest(format!(
+            "grace_secs must be 0..={}",
+            ROTATE_GRACE_SECS_MAX
+        ))
+        .into());
+    }
+
+    let (api_key, key_prefix) = generate_key();
+    let ok: bool = sqlx::query_scalar("SELECT lab_fun_api_key_rotate($1,$2,$3,$4)")
+        .bind(key_id)
+        .bind(&key_prefix)
+        .bind(sha256_bytes(&api_key))
+        .bind(grace_secs)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if !ok {
+        return Err(ApiError::NotFound("active api key not found".into()).into());
+    }
+
+    let meta = serde_json::json!({ "key_prefix": key_prefix, "grace_secs": grace_secs });
+    audit(
+        &state,
+        &ctx,
+        Some(admin_id),
+        "api_key_rotate",
+        Some(&key_id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    Ok(Json(ApiKeySecretRes {
+        id: key_id,
+        api_key,
+        key_prefix,
+    }))
+}
+
+/// DELETE /admin/api-keys/:key_id
+pub async fn revoke_api_key(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(key_id): Path<Uuid>,
+) -> ApiResult<axum::http::StatusCode> {
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let ok: bool = sqlx::query_scalar("SELECT lab_fun_api_key_revoke($1)")
+        .bind(key_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if !ok {
+        return Err(ApiError::NotFound("active api key not found".into()).into());
+    }
+
+    audit(
+        &state,
+        &ctx,
+        Some(admin_id),
+        "api_key_revoke",
+        Some(&key_id.to_string()),
+        None,
+    )
+    .await;
+
+    Ok(axum::http::StatusCode::OK)
+}
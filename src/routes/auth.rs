@@ -1,12 +1,12 @@
-use argon2::{password_hash::rand_core::OsRng, Argon2};
+use std::sync::OnceLock;
+
+use argon2::password_hash::rand_core::OsRng;
 use axum::{
     extract::{Path, State},
-    Json,
+    Extension, Json,
 };
 use base64::Engine;
 use chrono::{Duration, Utc};
-use jsonwebtoken::{encode, EncodingKey, Header};
-use password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
 use serde::{Deserialize, Serialize};
 use sqlx::Row;
 use uuid::Uuid;
@@ -14,10 +14,18 @@ use uuid::Uuid;
 use crate::{
     app_state::SharedState,
     errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
     models::Claims,
-    utils::{audit, sha256_bytes},
+    password_policy::verify_password,
+    routes::login_locks::{ensure_login_allowed, record_login_failure, record_login_success},
+    routes::mfa::{issue_mfa_pending_token, mfa_enabled, MfaPendingRes},
+    routes::notifications::notify_user,
+    routes::sessions::revoke_current_access_token,
+    utils::{audit, random_token, sha256_bytes},
 };
 
+const PASSWORD_RESET_TTL_MINUTES: i64 = 30;
+
 #[derive(Deserialize)]
 pub struct RegisterReq {
     pub email: String,
@@ -41,20 +49,38 @@ pub struct TokenRes {
     pub expires_in: i64,
     pub token_type: String,
 }
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum LoginRes {
+    Token(TokenRes),
+    MfaRequired(MfaPendingRes),
+}
 #[derive(Deserialize)]
 pub struct RefreshReq {
-    pub user_id: Uuid,
     pub refresh_token: String,
 }
 
 #[derive(Deserialize)]
-pub struct PasswordResetReq {
+pub struct PasswordResetRequestReq {
     pub email: String,
+}
+#[derive(Serialize)]
+pub struct PasswordResetRequestRes {
+    pub status: String,
+}
+#[derive(Deserialize)]
+pub struct PasswordResetConfirmReq {
+    pub token: String,
+    pub new_password: String,
+}
+#[derive(Deserialize)]
+pub struct ChangePasswordReq {
+    pub current_password: String,
     pub new_password: String,
 }
 #[derive(Serialize)]
-pub struct PasswordResetRes {
-    pub user_id: Uuid,
+pub struct ChangePasswordRes {
+    pub revoked_sessions: i32,
 }
 #[derive(Deserialize)]
 pub struct CheckEmailReq {
@@ -67,26 +93,37 @@ pub struct CheckEmailRes {
 
 pub async fn register(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Json(req): Json<RegisterReq>,
 ) -> ApiResult<Json<RegisterRes>> {
-    let role = req.role.clone().unwrap_or_else(|| "user".to_string());
-    if role != "admin" && role != "user" {
-        return Err(ApiError::BadRequest("role must be admin|user".into()).into());
+    // registrasi publik selalu membuat user biasa; admin/staf lewat undangan
+    if req.role.as_deref().is_some_and(|r| r != "user") {
+        let meta = serde_json::json!({ "requested_role": req.role });
+        audit(
+            &state,
+            &ctx,
+            None,
+            "register_role_rejected",
+            Some(&req.email),
+            Some(meta),
+        )
+        .await;
+        return Err(
+            ApiError::Forbidden("privileged accounts are created by invitation".into()).into(),
+        );
     }
+    let role = "user".to_string();
 
-    let salt = SaltString::generate(&mut OsRng);
-    let password_hash = Argon2::default()
-        .hash_password(req.password.as_bytes(), &salt)
-        .map_err(|e| ApiError::Internal(e.to_string()))?
-        .to_string();
+    state.password_policy.check(&req.password, &[]).await?;
+    let password_hash = hash_password(&state, &req.password)?;
 
     let row = sqlx::query!(
         "SELECT lab_fun_register_user($1,$2,$3,$4,$5) AS user_id",
         req.email,
         password_hash,
         role,
-        "ua",
-        "ip"
+        ctx.user_agent,
+        ctx.ip
     )
     .fetch_one(&state.pool)
     .await
@@ -100,15 +137,18 @@ pub async fn register(
     })?;
 
     let uid = row.user_id.unwrap();
-    audit(&state, Some(uid), "register", Some(&req.email), None).await;
+    audit(&state, &ctx, Some(uid), "register", Some(&req.email), None).await;
 
     Ok(Json(RegisterRes { user_id: uid }))
 }
 
 pub async fn login(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Json(req): Json<LoginReq>,
-) -> ApiResult<Json<TokenRes>> {
+) -> ApiResult<Json<LoginRes>> {
+    ensure_login_allowed(&state, &ctx, &req.email).await?;
+
     let auth = sqlx::query!(
         r#"SELECT user_id, password_hash, role, is_active
            FROM lab_fun_get_user_auth($1)"#,
@@ -116,34 +156,113 @@ pub async fn login(
     )
     .fetch_optional(&state.pool)
     .await
-    .map_err(ApiError::from)?
-    .ok_or(ApiError::Unauthorized("invalid email or password".into()))?;
+    .map_err(ApiError::from)?;
+
+    // email tidak terdaftar tetap menjalankan verifikasi Argon2 (hash dummy) supaya
+    // respons & waktunya sama dengan password salah
+    let stored_hash = auth
+        .as_ref()
+        .and_then(|a| a.password_hash.as_deref())
+        .unwrap_or_else(|| dummy_password_hash(&state));
+    let password_ok = verify_password(stored_hash, &req.password);
+
+    let auth = match auth {
+        Some(auth) if password_ok => auth,
+        other => {
+            let user_id = other.and_then(|a| a.user_id);
+            let reason = if user_id.is_some() {
+                "bad_password"
+            } else {
+                "unknown_email"
+            };
+            record_login_failure(&state, &ctx, &req.email, user_id, reason).await?;
+            return Err(ApiError::Unauthorized("invalid email or password".into()).into());
+        }
+    };
 
     if !auth.is_active.unwrap_or(true) {
         return Err(ApiError::Forbidden("account disabled".into()).into());
     }
 
-    let parsed = PasswordHash::new(auth.password_hash.as_deref().unwrap_or(""))
-        .map_err(|_| ApiError::Unauthorized("invalid email or password".into()))?;
-    Argon2::default()
-        .verify_password(req.password.as_bytes(), &parsed)
-        .map_err(|_| ApiError::Unauthorized("invalid email or password".into()))?;
-
-    let expires_in = 60 * 15;
-    let exp = (Utc::now() + Duration::seconds(expires_in)).timestamp() as usize;
-    let jti = Uuid::new_v4();
-    let claims = Claims {
-        sub: auth.user_id.unwrap().to_string(),
-        role: auth.role.unwrap_or("user".to_string()),
-        exp,
-        jti,
-    };
-    let access_token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
-    )
-    .map_err(|e| ApiError::Internal(e.to_string()))?;
+    let user_id = auth.user_id.unwrap();
+    let role = auth.role.unwrap_or("user".to_string());
+
+    // 2FA aktif: belum terbitkan token, minta kode TOTP dulu; counter gagal email baru
+    // di-reset setelah 2FA lolos (login_mfa)
+    if mfa_enabled(&state, user_id).await? {
+        let res = issue_mfa_pending_token(&state, user_id)?;
+        audit(&state, &ctx, Some(user_id), "login_mfa_pending", None, None).await;
+        return Ok(Json(LoginRes::MfaRequired(res)));
+    }
+    record_login_success(&state, &req.email).await?;
+
+    let tokens = issue_tokens(&state, &ctx, user_id, role.clone()).await?;
+
+    let meta = serde_json::json!({ "token_id": tokens.token_id, "role": role });
+    audit(&state, &ctx, Some(user_id), "login", None, Some(meta)).await;
+
+    Ok(Json(LoginRes::Token(tokens)))
+}
+
+const ACCESS_TOKEN_TTL_SECS: i64 = 60 * 15;
+
+/// Hash pembanding untuk email yang tidak terdaftar (parameter Argon2 sama dengan hash asli)
+fn dummy_password_hash(state: &SharedState) -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| hash_password(state, &random_token()).unwrap_or_default())
+}
+
+/// Hash password baru (register, reset, undangan, ganti password)
+pub(crate) fn hash_password(state: &SharedState, password: &str) -> Result<String, ApiError> {
+    state.password_policy.hash(password)
+}
+
+/// Hash password user saat ini + riwayatnya, sebanyak `PASSWORD_HISTORY`
+pub(crate) async fn recent_password_hashes(
+    state: &SharedState,
+    user_id: Uuid,
+) -> Result<Vec<String>, ApiError> {
+    sqlx::query_scalar("SELECT lab_fun_recent_password_hashes($1,$2)")
+        .bind(user_id)
+        .bind(state.password_policy.history)
+        .fetch_all(&state.pool)
+        .await
+        .map_err(ApiError::from)
+}
+
+/// Claims access token untuk user; satu-satunya tempat Claims dibentuk
+/// (login, login MFA, refresh) supaya isinya selalu sama.
+async fn access_claims(
+    state: &SharedState,
+    user_id: Uuid,
+    role: String,
+) -> Result<Claims, ApiError> {
+    let perms: Vec<String> = sqlx::query_scalar("SELECT lab_fun_role_permissions($1)")
+        .bind(&role)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    Ok(Claims {
+        sub: user_id.to_string(),
+        role,
+        exp: (Utc::now() + Duration::seconds(ACCESS_TOKEN_TTL_SECS)).timestamp() as usize,
+        jti: Uuid::new_v4(),
+        perms,
+    })
+}
+
+/// Terbitkan access token + refresh token baru untuk user (dipakai login & login MFA)
+pub(crate) async fn issue_tokens(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+    role: String,
+) -> Result<TokenRes, ApiError> {
+    let expires_in = ACCESS_TOKEN_TTL_SECS;
+    let claims = access_claims(state, user_id, role).await?;
+    let (jti, exp) = (claims.jti, claims.exp);
+    let access_token = state.jwt_keys.sign(&claims)?;
 
     use rand_core::RngCore;
     let mut bytes = [0u8; 32];
@@ -152,41 +271,31 @@ pub async fn login(
     let refresh_sha = sha256_bytes(&refresh_raw);
 
     let expires_at = Utc::now() + Duration::days(7);
-    let ua = "unknown".to_string();
-    let ip = "unknown".to_string();
-
-    let rec = sqlx::query("SELECT lab_fun_create_refresh_token($1,$2,$3,$4,$5) AS token_id")
-        .bind(Uuid::parse_str(&claims.sub).unwrap())
+    let rec = sqlx::query("SELECT lab_fun_create_refresh_token($1,$2,$3,$4,$5,$6) AS token_id")
+        .bind(user_id)
         .bind(refresh_sha)
-        .bind(ua)
-        .bind(ip)
+        .bind(&ctx.user_agent)
+        .bind(&ctx.ip)
         .bind(expires_at)
+        .bind(ctx.request_id)
         .fetch_one(&state.pool)
         .await
         .map_err(ApiError::from)?;
     let token_id: Uuid = rec.get("token_id");
+    bind_access_jti(state, token_id, jti, exp).await?;
 
-    let meta = serde_json::json!({ "token_id": token_id, "role": claims.role });
-    audit(
-        &state,
-        Some(Uuid::parse_str(&claims.sub).unwrap()),
-        "login",
-        None,
-        Some(meta),
-    )
-    .await;
-
-    Ok(Json(TokenRes {
+    Ok(TokenRes {
         token_id,
         access_token,
         refresh_token: refresh_raw,
         expires_in: expires_in as i64,
         token_type: "Bearer".into(),
-    }))
+    })
 }
 
 pub async fn refresh(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Json(req): Json<RefreshReq>,
 ) -> ApiResult<Json<serde_json::Value>> {
     let current_sha = crate::utils::sha256_bytes(&req.refresh_token);
@@ -198,47 +307,59 @@ pub async fn refresh(
     let new_sha = crate::utils::sha256_bytes(&new_refresh_raw);
     let new_expires_at = Utc::now() + Duration::days(7);
 
-    let ua = "unknown".to_string();
-    let ip = "unknown".to_string();
-
-    let rec =
-        sqlx::query(r#"SELECT lab_fun_consume_refresh_token($1,$2,$3,$4,$5,$6) AS new_token_id"#)
-            .bind(req.user_id)
-            .bind(current_sha)
-            .bind(new_sha.clone())
-            .bind(new_expires_at)
-            .bind(ua)
-            .bind(ip)
-            .fetch_one(&state.pool)
-            .await
-            .map_err(|e| {
-                let msg = e.to_string();
-                if msg.contains("REFRESH_INVALID_OR_EXPIRED") {
-                    ApiError::Unauthorized("invalid or expired refresh token".into())
-                } else {
-                    ApiError::Internal(msg)
-                }
-            })?;
-    let _new_token_id: Uuid = rec.get("new_token_id");
-
-    let expires_in = 60 * 15;
-    let exp = (Utc::now() + Duration::seconds(expires_in)).timestamp() as usize;
-    let jti = Uuid::new_v4();
-    let claims = Claims {
-        sub: req.user_id.to_string(),
-        role: "user".into(),
-        exp,
-        jti,
-    };
-    let access_token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
+    // user, role & status diambil dari token + lab_users, bukan dari body request
+    let rotated = sqlx::query(
+        r#"SELECT new_token_id, user_id, role, is_active
+           FROM lab_fun_rotate_refresh_token($1,$2,$3,$4,$5,$6)"#,
     )
-    .map_err(|e| ApiError::Internal(e.to_string()))?;
+    .bind(&current_sha)
+    .bind(new_sha.clone())
+    .bind(new_expires_at)
+    .bind(&ctx.user_agent)
+    .bind(&ctx.ip)
+    .bind(ctx.request_id)
+    .fetch_one(&state.pool)
+    .await;
+    let rec = match rotated {
+        Ok(rec) => rec,
+        Err(e) if e.to_string().contains("REFRESH_INVALID_OR_EXPIRED") => {
+            let owner: Option<Uuid> = sqlx::query_scalar("SELECT lab_fun_refresh_token_owner($1)")
+                .bind(&current_sha)
+                .fetch_one(&state.pool)
+                .await
+                .map_err(ApiError::from)?;
+            if let Some(owner) = owner {
+                detect_refresh_reuse(&state, &ctx, owner, &current_sha).await?;
+            }
+            return Err(ApiError::Unauthorized("invalid or expired refresh token".into()).into());
+        }
+        Err(e) => return Err(ApiError::from(e).into()),
+    };
+    let user_id: Uuid = rec.get("user_id");
+    if !rec.get::<bool, _>("is_active") {
+        // semua sesi user sudah dicabut di lab_fun_rotate_refresh_token
+        audit(
+            &state,
+            &ctx,
+            Some(user_id),
+            "refresh_denied_disabled",
+            None,
+            None,
+        )
+        .await;
+        return Err(ApiError::Forbidden("account disabled".into()).into());
+    }
+    let new_token_id: Uuid = rec.get("new_token_id");
+    let role: String = rec.get("role");
+
+    let expires_in = ACCESS_TOKEN_TTL_SECS;
+    let claims = access_claims(&state, user_id, role.clone()).await?;
+    let (jti, exp) = (claims.jti, claims.exp);
+    let access_token = state.jwt_keys.sign(&claims)?;
+    bind_access_jti(&state, new_token_id, jti, exp).await?;
 
-    let meta = serde_json::json!({ "rotated": true });
-    audit(&state, Some(req.user_id), "refresh", None, Some(meta)).await;
+    let meta = serde_json::json!({ "rotated": true, "token_id": new_token_id, "role": role });
+    audit(&state, &ctx, Some(user_id), "refresh", None, Some(meta)).await;
 
     Ok(Json(serde_json::json!({
         "access_token": access_token,
@@ -248,38 +369,224 @@ pub async fn refresh(
     })))
 }
 
-pub async fn password_reset(
-    State(state): State<SharedState>,
-    Json(req): Json<PasswordResetReq>,
-) -> ApiResult<Json<PasswordResetRes>> {
-    let salt = SaltString::generate(&mut OsRng);
-    let new_hash = Argon2::default()
-        .hash_password(req.new_password.as_bytes(), &salt)
-        .map_err(|e| ApiError::Internal(e.to_string()))?
-        .to_string();
+/// Catat jti access token pada refresh token pasangannya, supaya bisa di-blacklist
+/// ketika rantai rotasinya dicabut.
+async fn bind_access_jti(
+    state: &SharedState,
+    token_id: Uuid,
+    jti: Uuid,
+    exp: usize,
+) -> Result<(), ApiError> {
+    let expires_at = chrono::DateTime::<Utc>::from_timestamp(exp as i64, 0)
+        .unwrap_or_else(|| Utc::now() + Duration::minutes(15));
+    sqlx::query("SELECT lab_fun_set_refresh_access_jti($1,$2,$3)")
+        .bind(token_id)
+        .bind(jti)
+        .bind(expires_at)
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(())
+}
 
-    let row = sqlx::query!(
-        "SELECT lab_fun_update_password($1,$2) AS user_id",
-        req.email,
-        new_hash
+/// Refresh token yang sudah di-rotate dipakai lagi => kemungkinan dicuri.
+/// Cabut seluruh family rotasi dan blacklist access token yang masih hidup.
+async fn detect_refresh_reuse(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+    token_sha: &[u8],
+) -> Result<(), ApiError> {
+    let row = sqlx::query(
+        r#"SELECT family_root, revoked_refresh, revoked_access
+           FROM lab_fun_revoke_refresh_family($1,$2)"#,
     )
-    .fetch_one(&state.pool)
+    .bind(user_id)
+    .bind(token_sha)
+    .fetch_optional(&state.pool)
     .await
     .map_err(ApiError::from)?;
 
-    let user_id = row
-        .user_id
-        .ok_or_else(|| ApiError::Internal("missing user_id from lab_fun_update_password".into()))?;
+    if let Some(row) = row {
+        let family_root: Uuid = row.get("family_root");
+        let meta = serde_json::json!({
+            "family_root": family_root,
+            "revoked_refresh": row.get::<i32, _>("revoked_refresh"),
+            "revoked_access": row.get::<i32, _>("revoked_access"),
+        });
+        tracing::warn!("refresh token reuse detected (family {})", family_root);
+        audit(
+            state,
+            ctx,
+            Some(user_id),
+            "refresh_reuse_detected",
+            Some(&family_root.to_string()),
+            Some(meta),
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// Fase 1: kirim token reset (sekali pakai, berlaku 30 menit) lewat delivery channel.
+/// Respons selalu sama supaya tidak membocorkan email mana yang terdaftar.
+pub async fn password_reset_request(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Json(req): Json<PasswordResetRequestReq>,
+) -> ApiResult<Json<PasswordResetRequestRes>> {
+    let token = random_token();
+    let expires_at = Utc::now() + Duration::minutes(PASSWORD_RESET_TTL_MINUTES);
+
+    let user_id: Option<Uuid> =
+        sqlx::query_scalar("SELECT lab_fun_create_password_reset($1,$2,$3)")
+            .bind(&req.email)
+            .bind(sha256_bytes(&token))
+            .bind(expires_at)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(ApiError::from)?;
+
+    if let Some(user_id) = user_id {
+        let body = format!(
+            "Gunakan token berikut untuk reset password (berlaku {} menit): {}",
+            PASSWORD_RESET_TTL_MINUTES, token
+        );
+        if let Err(e) = state
+            .delivery
+            .send(&req.email, "Reset password", &body)
+            .await
+        {
+            tracing::warn!("password reset delivery failed: {:?}", e);
+        }
+        audit(
+            &state,
+            &ctx,
+            Some(user_id),
+            "password_reset_request",
+            Some(&req.email),
+            None,
+        )
+        .await;
+    }
+
+    Ok(Json(PasswordResetRequestRes {
+        status: "if the email is registered, a reset token has been sent".into(),
+    }))
+}
+
+/// Fase 2: tukar token reset dengan password baru; semua refresh token user dicabut
+pub async fn password_reset_confirm(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Json(req): Json<PasswordResetConfirmReq>,
+) -> ApiResult<axum::http::StatusCode> {
+    let token_sha = sha256_bytes(req.token.trim());
+    let invalid = || ApiError::BadRequest("invalid or expired reset token".into());
 
+    // token dicek dulu (tanpa dipakai) supaya password bisa dibandingkan dengan riwayat user
+    let owner: Option<Uuid> = sqlx::query_scalar("SELECT lab_fun_password_reset_owner($1)")
+        .bind(&token_sha)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    let owner = owner.ok_or_else(invalid)?;
+    let previous = recent_password_hashes(&state, owner).await?;
+    state
+        .password_policy
+        .check(&req.new_password, &previous)
+        .await?;
+    let new_hash = hash_password(&state, &req.new_password)?;
+
+    let user_id: Uuid = sqlx::query_scalar("SELECT lab_fun_consume_password_reset($1,$2)")
+        .bind(token_sha)
+        .bind(new_hash)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| {
+            let msg = e.to_string();
+            if msg.contains("RESET_TOKEN_INVALID") {
+                invalid()
+            } else {
+                ApiError::Internal(msg)
+            }
+        })?;
+
+    audit(&state, &ctx, Some(user_id), "password_reset", None, None).await;
+    Ok(axum::http::StatusCode::OK)
+}
+
+/// POST /auth/change_password — ganti password dengan konfirmasi password saat ini.
+/// Sesi lain dicabut; sesi yang dipakai request ini tetap hidup.
+pub async fn change_password(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<ChangePasswordReq>,
+) -> ApiResult<Json<ChangePasswordRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let row =
+        sqlx::query("SELECT email::text AS email, password_hash FROM lab_users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(ApiError::from)?
+            .ok_or_else(|| ApiError::Unauthorized("user not found".into()))?;
+    let email: String = row.get("email");
+    let current_hash: String = row.get("password_hash");
+
+    // tebakan password lewat access token curian ikut throttle login per email/IP
+    ensure_login_allowed(&state, &ctx, &email).await?;
+    if !verify_password(&current_hash, &req.current_password) {
+        record_login_failure(&state, &ctx, &email, Some(user_id), "change_password").await?;
+        return Err(ApiError::BadRequest("current password is incorrect".into()).into());
+    }
+    record_login_success(&state, &email).await?;
+
+    let previous = recent_password_hashes(&state, user_id).await?;
+    state
+        .password_policy
+        .check(&req.new_password, &previous)
+        .await?;
+    let new_hash = hash_password(&state, &req.new_password)?;
+
+    let revoked_sessions: i32 = sqlx::query_scalar("SELECT lab_fun_change_password($1,$2,$3)")
+        .bind(user_id)
+        .bind(new_hash)
+        .bind(claims.jti)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let meta = serde_json::json!({ "revoked_sessions": revoked_sessions });
     audit(
         &state,
+        &ctx,
         Some(user_id),
-        "password_reset",
-        Some(&req.email),
+        "password_change",
         None,
+        Some(meta),
     )
     .await;
-    Ok(Json(PasswordResetRes { user_id }))
+
+    let notify_state = state.clone();
+    tokio::spawn(async move {
+        let res = notify_user(
+            &notify_state,
+            user_id,
+            "Password diubah",
+            "Password akun Anda baru saja diubah. Jika bukan Anda, segera hubungi kami.",
+            None,
+        )
+        .await;
+        if let Err((_, e)) = res {
+            tracing::warn!("password change notification failed: {}", e);
+        }
+    });
+
+    Ok(Json(ChangePasswordRes { revoked_sessions }))
 }
 
 pub async fn check_email(
@@ -300,24 +607,338 @@ pub async fn check_email(
 
 pub async fn logout(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Path(token_id): Path<Uuid>,
     axum::Extension(claims): axum::Extension<Claims>,
 ) -> ApiResult<axum::http::StatusCode> {
-    let _ = sqlx::query_scalar!("SELECT lab_fun_revoke_refresh_token($1) AS ok", token_id)
+    let uid =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    // sesi caller sendiri → access token ini juga langsung masuk cache revoked
+    let is_current: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM lab_refresh_tokens WHERE token_id = $1 AND access_jti = $2)",
+    )
+    .bind(token_id)
+    .bind(claims.jti)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(ApiError::from)?;
+
+    // token_id harus milik caller; jti pasangannya ikut dicabut di DB
+    let ok: bool = sqlx::query_scalar("SELECT lab_fun_revoke_user_session($1,$2)")
+        .bind(uid)
+        .bind(token_id)
         .fetch_one(&state.pool)
-        .await;
+        .await
+        .map_err(ApiError::from)?;
+    if !ok {
+        return Err(ApiError::NotFound("session not found".into()).into());
+    }
 
-    let exp_ts = chrono::DateTime::<chrono::Utc>::from_timestamp(claims.exp as i64, 0)
-        .unwrap_or_else(|| chrono::Utc::now() + chrono::Duration::minutes(15));
-    let _ = sqlx::query("SELECT lab_fun_revoke_access_token($1,$2)")
-        .bind(claims.jti)
-        .bind(exp_ts)
-        .execute(&state.pool)
-        .await;
+    if is_current {
+        revoke_current_access_token(&state, &claims).await?;
+    }
 
-    let uid = Uuid::parse_str(&claims.sub).unwrap_or_else(|_| Uuid::nil());
     let meta = serde_json::json!({ "token_id": token_id });
-    audit(&state, Some(uid), "logout", None, Some(meta)).await;
+    audit(&state, &ctx, Some(uid), "logout", None, Some(meta)).await;
 
     Ok(axum::http::StatusCode::OK)
 }
+
+/// GET /.well-known/jwks.json — kunci publik untuk verifikasi access token oleh service lain
+pub async fn jwks(
+    State(state): State<SharedState>,
+) -> (
+    [(axum::http::HeaderName, &'static str); 1],
+    Json<serde_json::Value>,
+) {
+    (
+        [(axum::http::header::CACHE_CONTROL, "public, max-age=300")],
+        Json(state.jwt_keys.jwks()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{extract::State, http::StatusCode, Json};
+    use ring::{rand::SystemRandom, signature::Ed25519KeyPair};
+    use sqlx::postgres::PgPoolOptions;
+    use uuid::Uuid;
+
+    use super::{issue_tokens, refresh, RefreshReq};
+    use crate::{
+        app_state::{AppState, DigiflazzConfig, SharedState},
+        delivery::DeliveryChannel,
+        jwt_keys::JwtKeys,
+        middleware::{request_context::RequestContext, request_signature::SigningClients},
+        models::{perm, Claims, InvitationClaims, MfaPendingClaims},
+        password_policy::PasswordPolicy,
+        revoked_jti::RevokedJtiCache,
+        routes::step_up::StepUpPolicy,
+    };
+
+    const TEST_KID: &str = "test-ed25519";
+
+    /// Keyring Ed25519 sementara di temp dir
+    fn test_jwt_keys() -> JwtKeys {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).expect("keygen");
+        let dir = std::env::temp_dir().join(format!("labapi-jwt-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("temp dir");
+        let pem = pem::encode(&pem::Pem::new("PRIVATE KEY", pkcs8.as_ref().to_vec()));
+        std::fs::write(dir.join(format!("{}.pem", TEST_KID)), pem).expect("write key");
+        let keys = JwtKeys::load_dir(&dir, TEST_KID).expect("load keys");
+        std::fs::remove_dir_all(&dir).ok();
+        keys
+    }
+
+    /// State terhubung ke `DATABASE_URL` (schema db/schema.sql sudah dimuat). Test yang memakainya
+    /// ditandai `#[ignore]`; jalankan dengan `DATABASE_URL=... cargo test -- --ignored`.
+    async fn test_state() -> SharedState {
+        let db_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set for DB tests");
+        let pool = PgPoolOptions::new()
+            .max_connections(2)
+            .connect(&db_url)
+            .await
+            .expect("connect DATABASE_URL");
+        Arc::new(AppState {
+            pool: pool.clone(),
+            pool2: pool,
+            jwt_keys: Arc::new(test_jwt_keys()),
+            firebase: None,
+            digiflazz: DigiflazzConfig {
+                username: String::new(),
+                dev_key: String::new(),
+                prod_key: String::new(),
+                use_production: false,
+            },
+            delivery: DeliveryChannel::from_env_value("log"),
+            trusted_proxies: Arc::new(Vec::new()),
+            signing_clients: Arc::new(SigningClients::default()),
+            password_policy: Arc::new(PasswordPolicy::from_env()),
+            revoked_jtis: Arc::new(RevokedJtiCache::default()),
+            step_up_policy: Arc::new(StepUpPolicy::from_env()),
+        })
+    }
+
+    fn test_ctx() -> RequestContext {
+        RequestContext {
+            ip: "127.0.0.1".into(),
+            user_agent: "labapi-test".into(),
+            request_id: Uuid::new_v4(),
+        }
+    }
+
+    async fn create_user(state: &SharedState, role: &str) -> Uuid {
+        sqlx::query_scalar(
+            "INSERT INTO lab_users(email, password_hash, role) VALUES ($1, 'x', $2) RETURNING id",
+        )
+        .bind(format!("refresh-test-{}@example.test", Uuid::new_v4()))
+        .bind(role)
+        .fetch_one(&state.pool)
+        .await
+        .expect("insert user")
+    }
+
+    async fn active_refresh_tokens(state: &SharedState, user_id: Uuid) -> i64 {
+        sqlx::query_scalar(
+            "SELECT count(*) FROM lab_refresh_tokens WHERE user_id = $1 AND revoked = false",
+        )
+        .bind(user_id)
+        .fetch_one(&state.pool)
+        .await
+        .expect("count refresh tokens")
+    }
+
+    fn refresh_req(value: serde_json::Value) -> Json<RefreshReq> {
+        Json(serde_json::from_value(value).expect("refresh request"))
+    }
+
+    fn access_claims_of(state: &SharedState, body: &serde_json::Value) -> Claims {
+        let token = body["access_token"].as_str().expect("access_token");
+        state
+            .jwt_keys
+            .verify::<Claims>(token)
+            .expect("valid access token")
+            .claims
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL with db/schema.sql loaded"]
+    async fn admin_refresh_keeps_role_and_perms() {
+        let state = test_state().await;
+        let admin_id = create_user(&state, "admin").await;
+        let tokens = issue_tokens(&state, &test_ctx(), admin_id, "admin".into())
+            .await
+            .expect("issue tokens");
+        let before = access_claims_of(
+            &state,
+            &serde_json::json!({ "access_token": tokens.access_token }),
+        );
+
+        let Json(body) = refresh(
+            State(state.clone()),
+            test_ctx(),
+            refresh_req(serde_json::json!({ "refresh_token": tokens.refresh_token })),
+        )
+        .await
+        .expect("refresh");
+
+        let after = access_claims_of(&state, &body);
+        assert_eq!(after.sub, admin_id.to_string());
+        assert_eq!(after.role, "admin");
+        assert!(after.has_perm(perm::AUDIT_READ));
+        assert_eq!(after.perms, before.perms);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL with db/schema.sql loaded"]
+    async fn disabled_user_refresh_refused_and_family_revoked() {
+        let state = test_state().await;
+        let user_id = create_user(&state, "user").await;
+        let tokens = issue_tokens(&state, &test_ctx(), user_id, "user".into())
+            .await
+            .expect("issue tokens");
+        sqlx::query("UPDATE lab_users SET is_active = false WHERE id = $1")
+            .bind(user_id)
+            .execute(&state.pool)
+            .await
+            .expect("disable user");
+
+        let err = refresh(
+            State(state.clone()),
+            test_ctx(),
+            refresh_req(serde_json::json!({ "refresh_token": tokens.refresh_token })),
+        )
+        .await
+        .expect_err("disabled user must be refused");
+        assert_eq!(err.0, StatusCode::FORBIDDEN);
+        assert_eq!(active_refresh_tokens(&state, user_id).await, 0);
+        let access = access_claims_of(
+            &state,
+            &serde_json::json!({ "access_token": tokens.access_token }),
+        );
+        let blacklisted: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM lab_revoked_access_tokens WHERE jti = $1)",
+        )
+        .bind(access.jti)
+        .fetch_one(&state.pool)
+        .await
+        .expect("revoked access tokens");
+        assert!(blacklisted, "live access token must be blacklisted");
+
+        // diaktifkan lagi pun token lama tidak bisa dipakai
+        sqlx::query("UPDATE lab_users SET is_active = true WHERE id = $1")
+            .bind(user_id)
+            .execute(&state.pool)
+            .await
+            .expect("enable user");
+        let err = refresh(
+            State(state.clone()),
+            test_ctx(),
+            refresh_req(serde_json::json!({ "refresh_token": tokens.refresh_token })),
+        )
+        .await
+        .expect_err("revoked family must stay revoked");
+        assert_eq!(err.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL with db/schema.sql loaded"]
+    async fn refresh_ignores_user_id_in_body() {
+        let state = test_state().await;
+        let user_id = create_user(&state, "user").await;
+        let admin_id = create_user(&state, "admin").await;
+        let tokens = issue_tokens(&state, &test_ctx(), user_id, "user".into())
+            .await
+            .expect("issue tokens");
+
+        let Json(body) = refresh(
+            State(state.clone()),
+            test_ctx(),
+            refresh_req(serde_json::json!({
+                "refresh_token": tokens.refresh_token,
+                "user_id": admin_id,
+            })),
+        )
+        .await
+        .expect("refresh");
+
+        let claims = access_claims_of(&state, &body);
+        assert_eq!(claims.sub, user_id.to_string());
+        assert_eq!(claims.role, "user");
+        assert!(!claims.has_perm(perm::AUDIT_READ));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires DATABASE_URL with db/schema.sql loaded"]
+    async fn refresh_tokens_record_request_id() {
+        let state = test_state().await;
+        let user_id = create_user(&state, "user").await;
+        let login_ctx = test_ctx();
+        let tokens = issue_tokens(&state, &login_ctx, user_id, "user".into())
+            .await
+            .expect("issue tokens");
+        let refresh_ctx = test_ctx();
+        let Json(_) = refresh(
+            State(state.clone()),
+            refresh_ctx.clone(),
+            refresh_req(serde_json::json!({ "refresh_token": tokens.refresh_token })),
+        )
+        .await
+        .expect("refresh");
+
+        let request_ids: Vec<Option<Uuid>> = sqlx::query_scalar(
+            "SELECT request_id FROM lab_refresh_tokens WHERE user_id = $1 ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(&state.pool)
+        .await
+        .expect("refresh token rows");
+        assert_eq!(
+            request_ids,
+            vec![Some(login_ctx.request_id), Some(refresh_ctx.request_id)]
+        );
+    }
+
+    #[test]
+    fn token_kinds_are_not_interchangeable() {
+        let keys = test_jwt_keys();
+        let exp = (chrono::Utc::now() + chrono::Duration::minutes(5)).timestamp() as usize;
+        let access = keys
+            .sign(&Claims {
+                sub: Uuid::new_v4().to_string(),
+                role: "admin".into(),
+                exp,
+                jti: Uuid::new_v4(),
+                perms: vec![perm::AUDIT_READ.into()],
+            })
+            .expect("sign access");
+        let mfa = keys
+            .sign(&MfaPendingClaims {
+                sub: Uuid::new_v4().to_string(),
+                purpose: "mfa_pending".into(),
+                exp,
+                jti: Uuid::new_v4(),
+            })
+            .expect("sign mfa");
+        let invite = keys
+            .sign(&InvitationClaims {
+                sub: "staff@example.test".into(),
+                purpose: "invitation".into(),
+                exp,
+                jti: Uuid::new_v4(),
+            })
+            .expect("sign invitation");
+
+        assert!(keys.verify::<Claims>(&access).is_ok());
+        assert!(keys.verify::<MfaPendingClaims>(&mfa).is_ok());
+        assert!(keys.verify::<InvitationClaims>(&invite).is_ok());
+
+        assert!(keys.verify::<Claims>(&mfa).is_err());
+        assert!(keys.verify::<Claims>(&invite).is_err());
+        assert!(keys.verify::<MfaPendingClaims>(&invite).is_err());
+        assert!(keys.verify::<InvitationClaims>(&mfa).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, Row};
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
+    models::Claims,
+    money::Money,
+    routes::accounts::{ensure_lookup_allowed, mask_name, LookupCaller},
+    utils::audit,
+};
+
+const ALIAS_MAX_CHARS: usize = 50;
+
+#[derive(Deserialize)]
+pub struct BeneficiaryCreateReq {
+    pub account_no: String,
+    pub alias: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BeneficiaryRes {
+    pub id: Uuid,
+    pub account_no: String,
+    pub alias: Option<String>,
+    /// Nama pemilik hasil verifikasi saat disimpan, tersamar
+    pub verified_name: String,
+    pub verified_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    /// Terisi selama masa tenggang penerima baru; total transfer dibatasi `cooling_off_remaining`
+    pub cooling_off_until: Option<DateTime<Utc>>,
+    pub cooling_off_remaining: Option<Money>,
+}
+
+#[derive(Serialize)]
+pub struct BeneficiariesListRes {
+    pub items: Vec<BeneficiaryRes>,
+}
+
+fn beneficiary_from_row(row: &PgRow) -> Result<BeneficiaryRes, ApiError> {
+    let verified_name: String = row.try_get("verified_name").map_err(ApiError::from)?;
+    Ok(BeneficiaryRes {
+        id: row.try_get("id").map_err(ApiError::from)?,
+        account_no: row.try_get("account_no").map_err(ApiError::from)?,
+        alias: row.try_get("alias").map_err(ApiError::from)?,
+        verified_name: mask_name(&verified_name),
+        verified_at: row.try_get("verified_at").map_err(ApiError::from)?,
+        created_at: row.try_get("created_at").map_err(ApiError::from)?,
+        cooling_off_until: row.try_get("cooling_off_until").map_err(ApiError::from)?,
+        cooling_off_remaining: row
+            .try_get("cooling_off_remaining")
+            .map_err(ApiError::from)?,
+    })
+}
+
+async fn list_for_user(
+    state: &SharedState,
+    user_id: Uuid,
+) -> Result<Vec<BeneficiaryRes>, ApiError> {
+    let rows = sqlx::query(
+        r#"SELECT id, account_no, alias, verified_name, verified_at, created_at,
+                  cooling_off_until, cooling_off_remaining
+           FROM lab_fun_beneficiaries($1)"#,
+    )
+    .bind(user_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::from)?;
+
+    rows.iter().map(beneficiary_from_row).collect()
+}
+
+/// Nomor rekening tujuan dari beneficiary milik user (untuk transfer via `beneficiary_id`)
+pub(crate) async fn beneficiary_account_no(
+    state: &SharedState,
+    user_id: Uuid,
+    beneficiary_id: Uuid,
+) -> Result<String, ApiError> {
+    sqlx::query_scalar("SELECT account_no FROM lab_beneficiaries WHERE id = $1 AND user_id = $2")
+        .bind(beneficiary_id)
+        .bind(user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or_else(|| ApiError::NotFound("beneficiary not found".into()))
+}
+
+/// POST /beneficiaries — simpan rekening tujuan; nama pemilik diverifikasi & di-cache
+pub async fn create_beneficiary(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<BeneficiaryCreateReq>,
+) -> ApiResult<Json<BeneficiaryRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let account_no = req.account_no.trim();
+    if account_no.is_empty() {
+        return Err(ApiError::BadRequest("account_no is required".into()).into());
+    }
+    if req
+        .alias
+        .as_deref()
+        .is_some_and(|alias| alias.trim().chars().count() > ALIAS_MAX_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "alias must be at most {} characters",
+            ALIAS_MAX_CHARS
+        ))
+        .into());
+    }
+
+    // verifikasi nomor rekening dihitung ke kuota lookup yang sama dengan /accounts/verify
+    ensure_lookup_allowed(
+        &state,
+        &ctx,
+        &LookupCaller::User(user_id),
+        "beneficiary_add",
+    )
+    .await?;
+
+    let receiver =
+        sqlx::query("SELECT to_account_no, nama_lengkap FROM lab_fun_transfer_receivers($1)")
+            .bind(account_no)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(ApiError::from)?
+            .ok_or_else(|| ApiError::NotFound("account not found".into()))?;
+    let verified_name: String = receiver.try_get("nama_lengkap").unwrap_or_default();
+
+    let id: Uuid = sqlx::query_scalar("SELECT lab_fun_beneficiary_add($1,$2,$3,$4)")
+        .bind(user_id)
+        .bind(account_no)
+        .bind(req.alias.as_deref())
+        .bind(&verified_name)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| {
+            let msg = e.to_string();
+            if msg.contains("BENEFICIARY_EXISTS") {
+                ApiError::BadRequest("beneficiary already saved".into())
+            } else if msg.contains("BENEFICIARY_OWN_ACCOUNT") {
+                ApiError::BadRequest("cannot save own account as beneficiary".into())
+            } else {
+                ApiError::Internal(msg)
+            }
+        })?;
+
+    let res = list_for_user(&state, user_id)
+        .await?
+        .into_iter()
+        .find(|b| b.id == id)
+        .ok_or_else(|| ApiError::Internal("beneficiary not found after insert".into()))?;
+
+    let meta = serde_json::json!({
+        "account_no": res.account_no,
+        "alias": res.alias,
+        "cooling_off_until": res.cooling_off_until,
+    });
+    audit(
+        &state,
+        &ctx,
+        Some(user_id),
+        "beneficiary_add",
+        Some(&id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    Ok(Json(res))
+}
+
+/// GET /beneficiaries — daftar rekening tujuan tersimpan milik caller
+pub async fn list_beneficiaries(
+    State(state): State<SharedState>,
+    Extension(claims): Extension<Claims>,
+) -> ApiResult<Json<BeneficiariesListRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let items = list_for_user(&state, user_id).await?;
+    Ok(Json(BeneficiariesListRes { items }))
+}
+
+/// DELETE /beneficiaries/:beneficiary_id — hapus dari daftar (kuota masa tenggang tidak direset)
+pub async fn delete_beneficiary(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(beneficiary_id): Path<Uuid>,
+) -> ApiResult<axum::http::StatusCode> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let account_no: Option<String> = sqlx::query_scalar("SELECT lab_fun_beneficiary_delete($1,$2)")
+        .bind(user_id)
+        .bind(beneficiary_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    let Some(account_no) = account_no else {
+        return Err(ApiError::NotFound("beneficiary not found".into()).into());
+    };
+
+    let meta = serde_json::json!({ "account_no": account_no });
+    audit(
+        &state,
+        &ctx,
+        Some(user_id),
+        "beneficiary_delete",
+        Some(&beneficiary_id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    Ok(axum::http::StatusCode::OK)
+}
@@ -10,7 +10,12 @@ use uuid::Uuid;
 use crate::{
     app_state::SharedState,
     errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
     models::Claims,
+    money::{Money, MoneyError},
+    routes::account_lifecycle::ledger_error,
+    routes::limits::LimitTxnType,
+    routes::step_up::{ensure_step_up, StepUpOperation, StepUpProof},
     utils::{audit, verify_account_pin},
 };
 
@@ -18,27 +23,28 @@ use crate::{
 #[derive(Deserialize)]
 pub struct DepositReq {
     pub account_id: Uuid,
-    pub amount: f64,
+    pub amount: Money,
     pub description: Option<String>,
     pub pin: String,
     pub akun: String,
 }
 
-/// Request untuk tarik tunai (wajib PIN)
+/// Request untuk tarik tunai (wajib PIN; step-up di atas ambang)
 #[derive(Deserialize)]
 pub struct WithdrawReq {
     pub account_id: Uuid,
-    pub amount: f64,
+    pub amount: Money,
     pub description: Option<String>,
     pub pin: String,
     pub akun: String,
+    pub step_up: Option<StepUpProof>,
 }
 
 #[derive(Serialize)]
 pub struct CashRes {
     pub journal_id: Uuid,
     pub account_id: Uuid,
-    pub balance_after: f64,
+    pub balance_after: Money,
     pub trx_time: DateTime<Utc>,
     pub description: String,
 }
@@ -56,12 +62,12 @@ pub struct WidhrawRes {
     pub role_name: String,
     pub branch_id: i32,
     pub branch_name: String,
-    pub debit: String,
+    pub debit: Money,
 }
 
 #[derive(Serialize)]
 pub struct EodRes {
-    pub amount: f64,
+    pub amount: Money,
 }
 
 #[derive(Deserialize)]
@@ -69,8 +75,8 @@ pub struct UpdateWidhrawJournalReq {
     pub code: i64,
     pub jornal_id: String,
     pub account_no: i64,
-    pub debit: String,
-    pub credit: String,
+    pub debit: Money,
+    pub credit: Money,
     pub journal_date: String,
     pub deskripsi: String,
     pub nama_lengkap: String,
@@ -83,10 +89,11 @@ pub struct UpdateWidhrawJournalRes {
 
 pub async fn cash_deposit(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Json(req): Json<DepositReq>,
 ) -> ApiResult<Json<CashRes>> {
-    if req.amount <= 0.0 {
+    if !req.amount.is_positive() {
         return Err(ApiError::BadRequest("amount must be > 0".into()).into());
     }
     if req.pin.len() != 6 || !req.pin.chars().all(|c| c.is_ascii_digit()) {
@@ -97,7 +104,7 @@ pub async fn cash_deposit(
         Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
 
     // ✅ Validasi PIN di Rust sebelum panggil DB
-    verify_account_pin(&state, user_id, req.account_id, &req.pin).await?;
+    verify_account_pin(&state, &ctx, user_id, req.account_id, &req.pin).await?;
 
     let row = sqlx::query(
         r#"
@@ -107,18 +114,18 @@ pub async fn cash_deposit(
     )
     .bind(user_id)
     .bind(req.account_id)
-    .bind(req.amount)
+    .bind(&req.amount)
     .bind(req.description.as_deref())
     .bind(&req.akun)
     .fetch_one(&state.pool)
     .await
-    .map_err(ApiError::from)?;
+    .map_err(ledger_error)?;
 
     let res = CashRes {
         journal_id: row.try_get("journal_id").map_err(ApiError::from)?,
         account_id: row.try_get("account_id").map_err(ApiError::from)?,
         balance_after: row
-            .try_get::<f64, _>("balance_after")
+            .try_get::<Money, _>("balance_after")
             .map_err(ApiError::from)?,
         trx_time: row.try_get("trx_time").map_err(ApiError::from)?,
         description: row
@@ -129,6 +136,7 @@ pub async fn cash_deposit(
 
     audit(
         &state,
+        &ctx,
         Some(user_id),
         "deposit",
         Some(&res.journal_id.to_string()),
@@ -140,38 +148,83 @@ pub async fn cash_deposit(
 
 pub async fn cash_withdraw(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Json(req): Json<WithdrawReq>,
 ) -> ApiResult<Json<CashRes>> {
-    if req.amount <= 0.0 {
+    if !req.amount.is_positive() {
         return Err(ApiError::BadRequest("amount must be > 0".into()).into());
     }
     let user_id =
         Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
 
     // ✅ Validasi PIN sebelum tarik tunai
-    verify_account_pin(&state, user_id, req.account_id, &req.pin).await?;
+    verify_account_pin(&state, &ctx, user_id, req.account_id, &req.pin).await?;
 
+    let op = StepUpOperation::CashWithdraw {
+        account_id: req.account_id,
+        amount: req.amount.clone(),
+    };
+    ensure_step_up(
+        &state,
+        &ctx,
+        user_id,
+        &op,
+        &req.amount,
+        false,
+        req.step_up.as_ref(),
+    )
+    .await?;
+
+    let debit = Debit {
+        account_id: req.account_id,
+        amount: &req.amount,
+        description: req.description.as_deref(),
+        akun: &req.akun,
+        txn_type: LimitTxnType::CashWithdraw,
+    };
+    let res = withdraw_funds(&state, &ctx, user_id, debit).await?;
+    Ok(Json(res))
+}
+
+/// Rincian debit untuk `withdraw_funds`; `txn_type` menentukan kuota limit yang dipakai
+pub(crate) struct Debit<'a> {
+    pub account_id: Uuid,
+    pub amount: &'a Money,
+    pub description: Option<&'a str>,
+    pub akun: &'a str,
+    pub txn_type: LimitTxnType,
+}
+
+/// Debit saldo + jurnal tarik tunai; PIN/step-up sudah diverifikasi caller
+/// (cash_withdraw, pay_pasca_digiflazz, topup_digiflazz)
+pub(crate) async fn withdraw_funds(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+    debit: Debit<'_>,
+) -> Result<CashRes, ApiError> {
     let row = sqlx::query(
         r#"
         SELECT journal_id, account_id, balance_after, trx_time, description
-        FROM lab_fun_withdraw($1,$2,$3,$4,$5)
+        FROM lab_fun_withdraw($1,$2,$3,$4,$5,$6)
         "#,
     )
     .bind(user_id)
-    .bind(req.account_id)
-    .bind(req.amount)
-    .bind(req.description.as_deref())
-    .bind(&req.akun)
+    .bind(debit.account_id)
+    .bind(debit.amount)
+    .bind(debit.description)
+    .bind(debit.akun)
+    .bind(debit.txn_type.as_str())
     .fetch_one(&state.pool)
     .await
-    .map_err(ApiError::from)?;
+    .map_err(ledger_error)?;
 
     let res = CashRes {
         journal_id: row.try_get("journal_id").map_err(ApiError::from)?,
         account_id: row.try_get("account_id").map_err(ApiError::from)?,
         balance_after: row
-            .try_get::<f64, _>("balance_after")
+            .try_get::<Money, _>("balance_after")
             .map_err(ApiError::from)?,
         trx_time: row.try_get("trx_time").map_err(ApiError::from)?,
         description: row
@@ -181,14 +234,15 @@ pub async fn cash_withdraw(
     };
 
     audit(
-        &state,
+        state,
+        ctx,
         Some(user_id),
         "withdraw",
         Some(&res.journal_id.to_string()),
         None,
     )
     .await;
-    Ok(Json(res))
+    Ok(res)
 }
 
 pub async fn check_widhraw(
@@ -211,7 +265,11 @@ pub async fn check_widhraw(
             role_name: row.try_get(3).map_err(ApiError::from)?,
             branch_id: row.try_get::<i32, _>(4).map_err(ApiError::from)?,
             branch_name: row.try_get(5).map_err(ApiError::from)?,
-            debit: row.try_get(6).map_err(ApiError::from)?,
+            debit: row
+                .try_get::<String, _>(6)
+                .map_err(ApiError::from)?
+                .parse()
+                .map_err(|e: MoneyError| ApiError::Internal(format!("invalid debit: {}", e)))?,
         });
     }
 
@@ -223,7 +281,7 @@ pub async fn get_eod(
     Extension(_claims): Extension<Claims>,
     Query(req): Query<WidhrawQuery>,
 ) -> ApiResult<Json<EodRes>> {
-    let amount = sqlx::query_scalar("SELECT (corp_sp_get_amount_eod($1))::float8")
+    let amount = sqlx::query_scalar("SELECT (corp_sp_get_amount_eod($1))::numeric")
         .bind(req.id)
         .fetch_one(&state.pool2)
         .await
@@ -240,8 +298,8 @@ pub async fn update_widhraw_journal(
         .bind(req.code)
         .bind(req.jornal_id)
         .bind(req.account_no)
-        .bind(req.debit)
-        .bind(req.credit)
+        .bind(req.debit.to_string())
+        .bind(req.credit.to_string())
         .bind(req.journal_date)
         .bind(req.deskripsi)
         .bind(req.nama_lengkap)
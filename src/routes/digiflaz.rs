@@ -2,18 +2,21 @@ use axum::{
     extract::{Path, Query, State},
     Extension, Json,
 };
-use chrono::NaiveDateTime;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::Row;
 use tokio::time::{sleep, Duration};
 
-use crate::routes::cash::cash_withdraw;
+use crate::routes::cash::{withdraw_funds, Debit};
 use crate::{
     app_state::SharedState,
     errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
     models::Claims,
-    routes::cash::cash_deposit,
-    utils::verify_account_pin,
+    money::Money,
+    routes::limits::LimitTxnType,
+    routes::step_up::{ensure_step_up, StepUpOperation, StepUpProof},
+    utils::{audit, verify_account_pin},
 };
 use sqlx::types::BigDecimal;
 use uuid::Uuid;
@@ -87,7 +90,7 @@ pub struct DigiflazzSaldoResponse {
 
 #[derive(Serialize, Deserialize)]
 pub struct DigiflazzSaldoData {
-    pub deposit: f64,
+    pub deposit: Money,
 }
 
 #[derive(Deserialize)]
@@ -211,6 +214,8 @@ pub struct DigiflazzPascaPayReq {
     pub description: Option<String>,
     pub year: Option<i32>,
     pub testing: Option<bool>,
+    /// wajib jika tagihan di atas ambang step-up `pay_pasca`
+    pub step_up: Option<StepUpProof>,
 }
 
 pub async fn list_digiflazz_products(
@@ -723,6 +728,7 @@ pub async fn status_pasca_digiflazz(
 
 pub async fn pay_pasca_digiflazz(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Json(req): Json<DigiflazzPascaPayReq>,
 ) -> ApiResult<Json<DigiflazzTransactionResponse>> {
@@ -741,7 +747,7 @@ pub async fn pay_pasca_digiflazz(
 
     let user_id =
         Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
-    verify_account_pin(&state, user_id, req.account_id, &pin).await?;
+    verify_account_pin(&state, &ctx, user_id, req.account_id, &pin).await?;
 
     let tx_row = sqlx::query(
         "SELECT buyer_sku_code, customer_no, amount, price FROM sp_get_digiflazz_transaction_by_ref_id($1)",
@@ -751,55 +757,68 @@ pub async fn pay_pasca_digiflazz(
     .await
     .map_err(ApiError::from)?;
     let (buyer_sku_code, customer_no, amount_nominal, amount_to_charge) = match tx_row {
-        Some(row) => {
-            let nominal_str = row
-                .try_get::<BigDecimal, _>("amount")
-                .map_err(ApiError::from)?;
-            let price_str = row
-                .try_get::<BigDecimal, _>("price")
-                .map_err(ApiError::from)?;
-            let nominal_val = nominal_str.to_string().parse::<i64>().unwrap_or(0);
-            let amount_val = price_str.to_string().parse::<i64>().unwrap_or(0);
-            (
-                row.try_get::<String, _>("buyer_sku_code")
-                    .map_err(ApiError::from)?,
-                row.try_get::<String, _>("customer_no")
-                    .map_err(ApiError::from)?,
-                nominal_val,
-                amount_val,
-            )
-        }
+        Some(row) => (
+            row.try_get::<String, _>("buyer_sku_code")
+                .map_err(ApiError::from)?,
+            row.try_get::<String, _>("customer_no")
+                .map_err(ApiError::from)?,
+            row.try_get::<Money, _>("amount").map_err(ApiError::from)?,
+            row.try_get::<Money, _>("price").map_err(ApiError::from)?,
+        ),
         None => {
             return Err(ApiError::NotFound("transaction not found".into()).into());
         }
     };
     let is_emoney = is_emoney_sku(&state, &buyer_sku_code).await?;
-    if amount_to_charge <= 0 {
+    if !amount_to_charge.is_positive() {
         return Err(ApiError::BadRequest("amount not found from inquiry".into()).into());
     }
-    if is_emoney && amount_nominal <= 0 {
+    if is_emoney && !amount_nominal.is_positive() {
         return Err(ApiError::BadRequest("amount not found from inquiry".into()).into());
     }
+    // field amount di request digiflazz berupa rupiah utuh
+    let amount_payload = if is_emoney {
+        let nominal = amount_nominal
+            .to_whole_rupiah()
+            .ok_or_else(|| ApiError::BadRequest("inquiry amount must be whole rupiah".into()))?;
+        Some(nominal)
+    } else {
+        None
+    };
 
-    let _ = cash_withdraw(
-        State(state.clone()),
-        Extension(claims.clone()),
-        Json(crate::routes::cash::WithdrawReq {
-            account_id: req.account_id,
-            amount: amount_to_charge as f64,
-            description: req.description.clone(),
-            pin: pin.clone(),
-            akun: req.akun.clone(),
-        }),
+    // nominal tagihan berasal dari inquiry, jadi cukup ref_id yang diikat ke challenge
+    let op = StepUpOperation::PayPasca {
+        account_id: req.account_id,
+        ref_id: ref_id.clone(),
+    };
+    ensure_step_up(
+        &state,
+        &ctx,
+        user_id,
+        &op,
+        &amount_to_charge,
+        false,
+        req.step_up.as_ref(),
     )
     .await?;
 
-    let amount_str = amount_to_charge.to_string();
-    let amount_payload = if is_emoney {
-        Some(amount_nominal)
-    } else {
-        None
+    let debit = Debit {
+        account_id: req.account_id,
+        amount: &amount_to_charge,
+        description: req.description.as_deref(),
+        akun: &req.akun,
+        txn_type: LimitTxnType::Ppob,
+    };
+    let charged = withdraw_funds(&state, &ctx, user_id, debit).await?;
+    let charge = PpobCharge {
+        journal_id: charged.journal_id,
+        account_id: req.account_id,
+        amount: amount_to_charge.clone(),
+        charged_at: charged.trx_time,
+        ref_id: ref_id.clone(),
     };
+
+    let amount_str = amount_to_charge.to_string();
     let raw_request = serde_json::json!({
         "buyer_sku_code": buyer_sku_code,
         "customer_no": customer_no,
@@ -890,19 +909,7 @@ pub async fn pay_pasca_digiflazz(
             ),
         };
         if is_failed_status(&status_txt) {
-            let description = format!("Reversal dana Sejumlah {} Berhasil", amount_to_charge);
-            let _ = cash_deposit(
-                State(state.clone()),
-                Extension(claims.clone()),
-                Json(crate::routes::cash::DepositReq {
-                    account_id: req.account_id,
-                    amount: amount_to_charge as f64,
-                    description: Some(description),
-                    pin: pin.clone(),
-                    akun: "REVERSAL DANA".to_string(),
-                }),
-            )
-            .await;
+            reverse_ppob_charge(&state, &ctx, &claims, &charge).await;
         }
         let _ = sqlx::query("SELECT sp_update_digiflazz_transaction_status($1,$2,$3,$4,$5,$6)")
             .bind(tx_id)
@@ -939,19 +946,7 @@ pub async fn pay_pasca_digiflazz(
             .map(|s| s.to_string()),
     );
     if is_failed_status(status_txt) {
-        let description = format!("Reversal dana Sejumlah {} Berhasil", amount_to_charge);
-        let _ = cash_deposit(
-            State(state.clone()),
-            Extension(claims.clone()),
-            Json(crate::routes::cash::DepositReq {
-                account_id: req.account_id,
-                amount: amount_to_charge as f64,
-                description: Some(description),
-                pin: pin.clone(),
-                akun: "REVERSAL DANA".to_string(),
-            }),
-        )
-        .await;
+        reverse_ppob_charge(&state, &ctx, &claims, &charge).await;
     }
     let _ = sqlx::query("SELECT sp_update_digiflazz_transaction_status($1,$2,$3,$4,$5,$6)")
         .bind(tx_id)
@@ -966,40 +961,115 @@ pub async fn pay_pasca_digiflazz(
     Ok(Json(DigiflazzTransactionResponse { data: status_body }))
 }
 
+/// Debit PPOB yang sudah terjadi, untuk reversal bila transaksi gagal
+pub(crate) struct PpobCharge {
+    /// jurnal debit asal; satu debit hanya bisa di-reversal sekali
+    pub journal_id: Uuid,
+    pub account_id: Uuid,
+    pub amount: Money,
+    /// waktu debit; kuota limit dilepas dari periode ini
+    pub charged_at: DateTime<Utc>,
+    pub ref_id: String,
+}
+
+/// Kredit balik debit PPOB + lepas kuota limit `ppob`-nya dalam satu transaksi DB;
+/// mengembalikan jurnal reversal & saldo akhir
+pub(crate) async fn apply_ppob_reversal(
+    state: &SharedState,
+    user_id: Uuid,
+    charge: &PpobCharge,
+) -> Result<(Uuid, Money), sqlx::Error> {
+    let row = sqlx::query(
+        "SELECT journal_id, balance_after FROM lab_fun_ppob_reverse($1,$2,$3,$4,$5,$6)",
+    )
+    .bind(charge.journal_id)
+    .bind(user_id)
+    .bind(charge.account_id)
+    .bind(&charge.amount)
+    .bind(charge.charged_at)
+    .bind(&charge.ref_id)
+    .fetch_one(&state.pool)
+    .await?;
+    Ok((row.try_get("journal_id")?, row.try_get("balance_after")?))
+}
+
+/// Catat reversal yang gagal sebagai `pending` di `lab_ppob_reversals` untuk dicoba ulang
+pub(crate) async fn record_failed_ppob_reversal(
+    state: &SharedState,
+    user_id: Uuid,
+    charge: &PpobCharge,
+    error: &str,
+) {
+    tracing::error!(
+        "ppob reversal failed for journal {} (ref {}): {}",
+        charge.journal_id,
+        charge.ref_id,
+        error
+    );
+    if let Err(e) = sqlx::query("SELECT lab_fun_ppob_reversal_failed($1,$2,$3,$4,$5,$6,$7)")
+        .bind(charge.journal_id)
+        .bind(user_id)
+        .bind(charge.account_id)
+        .bind(&charge.amount)
+        .bind(charge.charged_at)
+        .bind(&charge.ref_id)
+        .bind(error)
+        .execute(&state.pool)
+        .await
+    {
+        tracing::error!(
+            "ppob reversal for journal {} not recorded as pending: {}",
+            charge.journal_id,
+            e
+        );
+    }
+}
+
+/// Reversal debit PPOB yang gagal di Digiflazz; jika gagal, reversal menunggu dicoba ulang
+/// lewat `POST /admin/ppob/reversals/:journal_id/retry`
+async fn reverse_ppob_charge(
+    state: &SharedState,
+    ctx: &RequestContext,
+    claims: &Claims,
+    charge: &PpobCharge,
+) {
+    let Ok(user_id) = Uuid::parse_str(&claims.sub) else {
+        tracing::error!("ppob reversal skipped: bad subject {}", claims.sub);
+        return;
+    };
+    match apply_ppob_reversal(state, user_id, charge).await {
+        Ok((journal_id, _)) => {
+            audit(
+                state,
+                ctx,
+                Some(user_id),
+                "ppob_reversal",
+                Some(&charge.journal_id.to_string()),
+                Some(serde_json::json!({
+                    "ref_id": charge.ref_id,
+                    "reversal_journal_id": journal_id,
+                })),
+            )
+            .await;
+        }
+        Err(e) => record_failed_ppob_reversal(state, user_id, charge, &e.to_string()).await,
+    }
+}
+
 async fn handle_digiflazz_status(
     state: SharedState,
+    ctx: RequestContext,
     claims: Claims,
     tx_id: i64,
     ref_id: &str,
     buyer_sku_code: &str,
     customer_no: &str,
-    account_id: Uuid,
-    amount: f64,
-    pin: String,
+    charge: PpobCharge,
 ) -> ApiResult<serde_json::Value> {
     let is_failed_status = |status_txt: &str| {
         let s = status_txt.trim().to_ascii_lowercase();
         s == "failed" || s == "gagal"
     };
-    let reversal = |state: SharedState,
-                    claims: Claims,
-                    account_id: Uuid,
-                    amount: f64,
-                    pin: String| async move {
-        let description = format!("Reversal dana Sejumlah {} Berhasil", amount);
-        let _ = cash_deposit(
-            State(state),
-            Extension(claims),
-            Json(crate::routes::cash::DepositReq {
-                account_id,
-                amount,
-                description: Some(description),
-                pin,
-                akun: "REVERSAL DANA".to_string(),
-            }),
-        )
-        .await;
-    };
 
     let cfg = &state.digiflazz;
     let api_key = if cfg.use_production {
@@ -1059,14 +1129,7 @@ async fn handle_digiflazz_status(
             ),
         };
         if is_failed_status(&status_txt) {
-            reversal(
-                state.clone(),
-                claims.clone(),
-                account_id,
-                amount,
-                pin.clone(),
-            )
-            .await;
+            reverse_ppob_charge(&state, &ctx, &claims, &charge).await;
         }
         let _ = sqlx::query("SELECT sp_update_digiflazz_transaction_status($1,$2,$3,$4,$5,$6)")
             .bind(tx_id)
@@ -1099,14 +1162,7 @@ async fn handle_digiflazz_status(
             .map(|s| s.to_string()),
     );
     if is_failed_status(status_txt) {
-        reversal(
-            state.clone(),
-            claims.clone(),
-            account_id,
-            amount,
-            pin.clone(),
-        )
-        .await;
+        reverse_ppob_charge(&state, &ctx, &claims, &charge).await;
     }
     let _ = sqlx::query("SELECT sp_update_digiflazz_transaction_status($1,$2,$3,$4,$5,$6)")
         .bind(tx_id)
@@ -1123,6 +1179,7 @@ async fn handle_digiflazz_status(
 
 pub async fn topup_digiflazz(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Json(req): Json<DigiflazzTopupReq>,
 ) -> ApiResult<Json<DigiflazzTransactionResponse>> {
@@ -1137,7 +1194,7 @@ pub async fn topup_digiflazz(
 
     let user_id =
         Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
-    verify_account_pin(&state, user_id, req.account_id, &pin).await?;
+    verify_account_pin(&state, &ctx, user_id, req.account_id, &pin).await?;
 
     let product_row = sqlx::query(
         r#"
@@ -1170,25 +1227,27 @@ pub async fn topup_digiflazz(
         .0
         .data
         .deposit;
-    if saldo < product.price as f64 {
+    if saldo < Money::from(i64::from(product.price)) {
         return Err(ApiError::BadRequest("digiflazz saldo tidak cukup".into()).into());
     }
 
     let amount_str = product.price.to_string();
-    let _ = cash_withdraw(
-        State(state.clone()),
-        Extension(claims.clone()),
-        Json(crate::routes::cash::WithdrawReq {
-            account_id: req.account_id,
-            amount: product.price as f64,
-            description: req.description.clone(),
-            pin: pin.clone(),
-            akun: req.akun.clone(),
-        }),
-    )
-    .await?;
-
+    let debit = Debit {
+        account_id: req.account_id,
+        amount: &Money::from(i64::from(product.price)),
+        description: req.description.as_deref(),
+        akun: &req.akun,
+        txn_type: LimitTxnType::Ppob,
+    };
+    let charged = withdraw_funds(&state, &ctx, user_id, debit).await?;
     let ref_id = Uuid::new_v4().to_string();
+    let charge = PpobCharge {
+        journal_id: charged.journal_id,
+        account_id: req.account_id,
+        amount: Money::from(i64::from(product.price)),
+        charged_at: charged.trx_time,
+        ref_id: ref_id.clone(),
+    };
     let raw_request = serde_json::json!({
         "buyer_sku_code": req.buyer_sku_code,
         "customer_no": req.customer_no,
@@ -1270,21 +1329,7 @@ pub async fn topup_digiflazz(
             ),
         };
         if is_failed_status(&status_txt) {
-            let _ = cash_deposit(
-                State(state.clone()),
-                Extension(claims.clone()),
-                Json(crate::routes::cash::DepositReq {
-                    account_id: req.account_id,
-                    amount: product.price as f64,
-                    description: Some(format!(
-                        "Reversal dana Sejumlah {} Berhasil",
-                        product.price as f64
-                    )),
-                    pin: pin.clone(),
-                    akun: "REVERSAL DANA".to_string(),
-                }),
-            )
-            .await;
+            reverse_ppob_charge(&state, &ctx, &claims, &charge).await;
         }
         let _ = sqlx::query("SELECT sp_update_digiflazz_transaction_status($1,$2,$3,$4,$5,$6)")
             .bind(tx_id)
@@ -1303,14 +1348,13 @@ pub async fn topup_digiflazz(
     sleep(Duration::from_secs(1)).await;
     let status_body = handle_digiflazz_status(
         state.clone(),
+        ctx.clone(),
         claims.clone(),
         tx_id,
         &ref_id,
         &req.buyer_sku_code,
         &req.customer_no,
-        req.account_id,
-        product.price as f64,
-        pin.clone(),
+        charge,
     )
     .await?;
 
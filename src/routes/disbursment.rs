@@ -7,6 +7,7 @@ use uuid::Uuid;
 use crate::{
     app_state::SharedState,
     errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
     models::Claims,
     utils::audit,
 };
@@ -48,6 +49,7 @@ pub struct SelectedBankAccountRes {
 
 pub async fn create_bank_account(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Json(req): Json<CreateBankAccountReq>,
 ) -> ApiResult<Json<BankAccountRes>> {
@@ -141,6 +143,7 @@ pub async fn create_bank_account(
     });
     audit(
         &state,
+        &ctx,
         Some(user_id),
         "bank_account_create",
         Some(&res.id.to_string()),
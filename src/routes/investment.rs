@@ -10,6 +10,7 @@ use crate::{
     app_state::SharedState,
     errors::{ApiError, ApiResult},
     models::Claims,
+    money::Money,
 };
 
 #[derive(Deserialize)]
@@ -23,7 +24,7 @@ pub struct MasterSahamRes {
     pub nik: String,
     pub cmp_desc: String,
     pub name_store: String,
-    pub amount: f64,
+    pub amount: Money,
     pub nama_lengkap: String,
     pub addres: String,
     pub from_date: Option<NaiveDate>,
@@ -46,7 +47,7 @@ pub struct DevidenDetailRes {
     pub nik: String,
     pub nama_lengkap: String,
     pub lokasi: String,
-    pub amount_dev: f64,
+    pub amount_dev: Money,
 }
 
 #[derive(Deserialize)]
@@ -63,12 +64,12 @@ pub struct DashboardDevidenRes {
     pub o_group_type: String,
     pub o_group_key: String,
     pub o_bulan: String,
-    pub o_total_amount: f64,
+    pub o_total_amount: Money,
 }
 
 #[derive(Serialize)]
 pub struct DashboardDevidenSummaryRes {
-    pub total_amount: f64,
+    pub total_amount: Money,
     pub items: Vec<DashboardDevidenRes>,
 }
 
@@ -154,7 +155,7 @@ pub async fn get_master_saham(
             nik,
             cmp_desc,
             name_store,
-            amount::float8 AS amount,
+            round(amount::numeric, 2) AS amount,
             nama_lengkap,
             addres,
             from_date,
@@ -179,9 +180,9 @@ pub async fn get_master_saham(
             cmp_desc: row.try_get("cmp_desc").unwrap_or_default(),
             name_store: row.try_get("name_store").unwrap_or_default(),
             amount: row
-                .try_get::<Option<f64>, _>("amount")
+                .try_get::<Option<Money>, _>("amount")
                 .map_err(ApiError::from)?
-                .unwrap_or(0.0),
+                .unwrap_or_else(Money::zero),
             nama_lengkap: row.try_get("nama_lengkap").unwrap_or_default(),
             addres: row.try_get("addres").unwrap_or_default(),
             from_date: row.try_get("from_date").ok(),
@@ -302,7 +303,7 @@ pub async fn get_deviden_detail(
             nik,
             nama_lengkap,
             lokasi,
-            amount_dev::float8 AS amount_dev
+            round(amount_dev::numeric, 2) AS amount_dev
         FROM public.corp_sp_get_list_deviden($1::integer, $2::varchar)
         "#,
     )
@@ -323,9 +324,9 @@ pub async fn get_deviden_detail(
             nama_lengkap: row.try_get("nama_lengkap").unwrap_or_default(),
             lokasi: row.try_get("lokasi").unwrap_or_default(),
             amount_dev: row
-                .try_get::<Option<f64>, _>("amount_dev")
+                .try_get::<Option<Money>, _>("amount_dev")
                 .map_err(ApiError::from)?
-                .unwrap_or(0.0),
+                .unwrap_or_else(Money::zero),
         });
     }
 
@@ -343,7 +344,7 @@ pub async fn get_dashboard_deviden(
             o_group_type,
             o_group_key,
             o_bulan,
-            o_total_amount::float8 AS o_total_amount
+            round(o_total_amount::numeric, 2) AS o_total_amount
         FROM public.corp_sp_sum_deviden_group($1::varchar, $2::varchar, $3::varchar, $4::varchar, $5::varchar)
         "#,
     )
@@ -363,13 +364,13 @@ pub async fn get_dashboard_deviden(
             o_group_key: row.try_get("o_group_key").unwrap_or_default(),
             o_bulan: row.try_get("o_bulan").unwrap_or_default(),
             o_total_amount: row
-                .try_get::<Option<f64>, _>("o_total_amount")
+                .try_get::<Option<Money>, _>("o_total_amount")
                 .map_err(ApiError::from)?
-                .unwrap_or(0.0),
+                .unwrap_or_else(Money::zero),
         });
     }
 
-    let total_amount = items.iter().map(|v| v.o_total_amount).sum();
+    let total_amount = items.iter().map(|v| v.o_total_amount.clone()).sum();
 
     Ok(Json(DashboardDevidenSummaryRes {
         total_amount,
@@ -0,0 +1,213 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
+    models::{Claims, InvitationClaims},
+    routes::auth::{hash_password, RegisterRes},
+    utils::audit,
+};
+
+const INVITATION_PURPOSE: &str = "invitation";
+const INVITATION_TTL_HOURS: i64 = 72;
+
+#[derive(Deserialize)]
+pub struct CreateInvitationReq {
+    pub email: String,
+    /// default `admin`; bisa juga role staf lain (teller, branch_supervisor, auditor)
+    pub role: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CreateInvitationRes {
+    pub invitation_id: Uuid,
+    pub email: String,
+    pub role: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct AcceptInvitationReq {
+    pub token: String,
+    pub password: String,
+}
+
+/// POST /admin/invitations — terbitkan undangan bertanda tangan, dikirim lewat delivery channel
+pub async fn create_invitation(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<CreateInvitationReq>,
+) -> ApiResult<Json<CreateInvitationRes>> {
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    let email = req.email.trim().to_string();
+    if email.is_empty() {
+        return Err(ApiError::BadRequest("email is required".into()).into());
+    }
+    let role = req.role.unwrap_or_else(|| "admin".to_string());
+    if role == "user" {
+        return Err(ApiError::BadRequest("plain users register themselves".into()).into());
+    }
+
+    let expires_at = Utc::now() + Duration::hours(INVITATION_TTL_HOURS);
+    let invitation_id: Uuid = sqlx::query_scalar("SELECT lab_fun_create_invitation($1,$2,$3,$4)")
+        .bind(&email)
+        .bind(&role)
+        .bind(admin_id)
+        .bind(expires_at)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(|e| {
+            let msg = e.to_string();
+            if msg.contains("EMAIL_EXISTS") {
+                ApiError::BadRequest("email already registered".into())
+            } else if msg.contains("ROLE_UNKNOWN") {
+                ApiError::BadRequest("unknown role".into())
+            } else {
+                ApiError::Internal(msg)
+            }
+        })?;
+
+    let token = state.jwt_keys.sign(&InvitationClaims {
+        sub: email.clone(),
+        purpose: INVITATION_PURPOSE.into(),
+        exp: expires_at.timestamp() as usize,
+        jti: invitation_id,
+    })?;
+    let body = format!(
+        "Anda diundang sebagai {}. Token undangan (berlaku {} jam): {}",
+        role, INVITATION_TTL_HOURS, token
+    );
+    state.delivery.send(&email, "Undangan akun", &body).await?;
+
+    let meta = serde_json::json!({ "invitation_id": invitation_id, "role": role });
+    audit(
+        &state,
+        &ctx,
+        Some(admin_id),
+        "invitation_create",
+        Some(&email),
+        Some(meta),
+    )
+    .await;
+
+    Ok(Json(CreateInvitationRes {
+        invitation_id,
+        email,
+        role,
+        expires_at,
+    }))
+}
+
+/// DELETE /admin/invitations/:invitation_id — cabut undangan yang belum dipakai
+pub async fn revoke_invitation(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(invitation_id): Path<Uuid>,
+) -> ApiResult<axum::http::StatusCode> {
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let ok: bool = sqlx::query_scalar("SELECT lab_fun_revoke_invitation($1)")
+        .bind(invitation_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if !ok {
+        return Err(ApiError::NotFound("pending invitation not found".into()).into());
+    }
+
+    audit(
+        &state,
+        &ctx,
+        Some(admin_id),
+        "invitation_revoke",
+        Some(&invitation_id.to_string()),
+        None,
+    )
+    .await;
+
+    Ok(axum::http::StatusCode::OK)
+}
+
+/// POST /auth/invitations/accept — invitee menukar token undangan + password menjadi akun
+pub async fn accept_invitation(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Json(req): Json<AcceptInvitationReq>,
+) -> ApiResult<Json<RegisterRes>> {
+    let invalid = || ApiError::BadRequest("invalid or expired invitation".into());
+
+    let data = state
+        .jwt_keys
+        .verify::<InvitationClaims>(req.token.trim())
+        .map_err(|_| invalid())?;
+    if data.claims.purpose != INVITATION_PURPOSE {
+        return Err(invalid().into());
+    }
+    let invitation_id = data.claims.jti;
+
+    state.password_policy.check(&req.password, &[]).await?;
+    let password_hash = hash_password(&state, &req.password)?;
+    let row =
+        sqlx::query("SELECT user_id, role, invited_by FROM lab_fun_redeem_invitation($1,$2,$3)")
+            .bind(invitation_id)
+            .bind(&data.claims.sub)
+            .bind(password_hash)
+            .fetch_one(&state.pool)
+            .await;
+    let row = match row {
+        Ok(row) => row,
+        Err(e) => {
+            let msg = e.to_string();
+            let meta = serde_json::json!({ "invitation_id": invitation_id });
+            audit(
+                &state,
+                &ctx,
+                None,
+                "invitation_redeem_failed",
+                Some(&data.claims.sub),
+                Some(meta),
+            )
+            .await;
+            return Err(if msg.contains("INVITATION_INVALID") {
+                invalid()
+            } else if msg.contains("EMAIL_EXISTS") {
+                ApiError::BadRequest("email already registered".into())
+            } else {
+                ApiError::Internal(msg)
+            }
+            .into());
+        }
+    };
+
+    let user_id: Uuid = row.get("user_id");
+    let role: String = row.get("role");
+    let invited_by: Uuid = row.get("invited_by");
+    let meta = serde_json::json!({
+        "invitation_id": invitation_id,
+        "role": role,
+        "invited_by": invited_by,
+    });
+    audit(
+        &state,
+        &ctx,
+        Some(user_id),
+        "invitation_redeemed",
+        Some(&data.claims.sub),
+        Some(meta),
+    )
+    .await;
+
+    Ok(Json(RegisterRes { user_id }))
+}
@@ -10,15 +10,18 @@ use uuid::Uuid;
 use crate::{
     app_state::SharedState,
     errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
     models::Claims,
+    money::Money,
+    routes::account_lifecycle::ledger_error,
     utils::audit,
 };
 
 #[derive(Deserialize)]
 pub struct JournalPostReq {
     pub account_id: Uuid,
-    pub debit: Option<f64>,
-    pub credit: Option<f64>,
+    pub debit: Option<Money>,
+    pub credit: Option<Money>,
     pub description: Option<String>,
 }
 
@@ -26,10 +29,10 @@ pub struct JournalPostReq {
 pub struct JournalRes {
     pub id: Uuid,
     pub trx_time: DateTime<Utc>,
-    pub debit: f64,
-    pub credit: f64,
+    pub debit: Money,
+    pub credit: Money,
     pub description: Option<String>,
-    pub balance_after: f64,
+    pub balance_after: Money,
     pub nama_lengkap: Option<String>,
 }
 
@@ -37,9 +40,9 @@ pub struct JournalRes {
 pub struct JournalPublicRes {
     pub journal_id: Uuid,
     pub account_no: String,
-    pub debit: f64,
-    pub credit: f64,
-    pub balance_after: f64,
+    pub debit: Money,
+    pub credit: Money,
+    pub balance_after: Money,
     pub trx_time: DateTime<Utc>,
     pub description: String,
     pub nama_lengkap: String,
@@ -59,13 +62,16 @@ pub struct JournalsQuery {
 
 pub async fn post_journal(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Json(req): Json<JournalPostReq>,
 ) -> ApiResult<Json<JournalRes>> {
-    if req.debit.unwrap_or(0.0) < 0.0 || req.credit.unwrap_or(0.0) < 0.0 {
+    let debit = req.debit.clone().unwrap_or_else(Money::zero);
+    let credit = req.credit.clone().unwrap_or_else(Money::zero);
+    if debit.is_negative() || credit.is_negative() {
         return Err(ApiError::BadRequest("debit/credit must be >= 0".into()).into());
     }
-    if req.debit.unwrap_or(0.0) == 0.0 && req.credit.unwrap_or(0.0) == 0.0 {
+    if !debit.is_positive() && !credit.is_positive() {
         return Err(ApiError::BadRequest("either debit or credit must be > 0".into()).into());
     }
     let user_id =
@@ -78,21 +84,21 @@ pub async fn post_journal(
     )
     .bind(user_id)
     .bind(req.account_id)
-    .bind(req.debit.unwrap_or(0.0_f64))
-    .bind(req.credit.unwrap_or(0.0_f64))
+    .bind(&debit)
+    .bind(&credit)
     .bind(req.description.clone())
     .fetch_one(&state.pool)
     .await
-    .map_err(ApiError::from)?;
+    .map_err(ledger_error)?;
     let journal_id: Uuid = row.get("journal_id");
 
     let row = sqlx::query(
         r#"
         SELECT id, trx_time,
-               debit::float8  AS debit,
-               credit::float8 AS credit,
+               debit,
+               credit,
                description,
-               balance_after::float8 AS balance_after
+               balance_after
         FROM lab_journals
         WHERE id = $1
         "#,
@@ -105,20 +111,21 @@ pub async fn post_journal(
     let res = Json(JournalRes {
         id: row.get("id"),
         trx_time: row.get("trx_time"),
-        debit: row.get::<f64, _>("debit"),
-        credit: row.get::<f64, _>("credit"),
+        debit: row.get::<Money, _>("debit"),
+        credit: row.get::<Money, _>("credit"),
         description: row.try_get("description").ok(),
-        balance_after: row.get::<f64, _>("balance_after"),
+        balance_after: row.get::<Money, _>("balance_after"),
         nama_lengkap: None,
     });
 
     let meta = serde_json::json!({
         "account_id": req.account_id,
-        "debit": req.debit.unwrap_or(0.0_f64),
-        "credit": req.credit.unwrap_or(0.0_f64)
+        "debit": debit,
+        "credit": credit
     });
     audit(
         &state,
+        &ctx,
         Some(user_id),
         "journal_post",
         Some(&journal_id.to_string()),
@@ -142,10 +149,10 @@ pub async fn list_journals(
     let rows = sqlx::query(
         r#"
         SELECT id, trx_time,
-               debit::float8  AS debit,
-               credit::float8 AS credit,
+               debit,
+               credit,
                description,
-               balance_after::float8 AS balance_after,
+               balance_after,
                nama_lengkap
         FROM lab_fun_list_journal($1,$2,$3,$4)
         "#,
@@ -163,10 +170,10 @@ pub async fn list_journals(
         items.push(JournalRes {
             id: r.get("id"),
             trx_time: r.get("trx_time"),
-            debit: r.get::<f64, _>("debit"),
-            credit: r.get::<f64, _>("credit"),
+            debit: r.get::<Money, _>("debit"),
+            credit: r.get::<Money, _>("credit"),
             description: r.try_get("description").ok(),
-            balance_after: r.get::<f64, _>("balance_after"),
+            balance_after: r.get::<Money, _>("balance_after"),
             nama_lengkap: r
                 .try_get::<Option<String>, _>("nama_lengkap")
                 .ok()
@@ -186,9 +193,9 @@ pub async fn get_journal_public(
         r#"
         SELECT journal_id,
                account_no,
-               debit,
-               credit,
-               balance_after,
+               round(debit::numeric, 2)         AS debit,
+               round(credit::numeric, 2)        AS credit,
+               round(balance_after::numeric, 2) AS balance_after,
                trx_time,
                description,
                nama_lengkap
@@ -209,10 +216,10 @@ pub async fn get_journal_public(
     let res = JournalPublicRes {
         journal_id: row.try_get("journal_id").map_err(ApiError::from)?,
         account_no: row.try_get("account_no").map_err(ApiError::from)?,
-        debit: row.try_get::<f64, _>("debit").map_err(ApiError::from)?,
-        credit: row.try_get::<f64, _>("credit").map_err(ApiError::from)?,
+        debit: row.try_get::<Money, _>("debit").map_err(ApiError::from)?,
+        credit: row.try_get::<Money, _>("credit").map_err(ApiError::from)?,
         balance_after: row
-            .try_get::<f64, _>("balance_after")
+            .try_get::<Money, _>("balance_after")
             .map_err(ApiError::from)?,
         trx_time: row.try_get("trx_time").map_err(ApiError::from)?,
         description: row
@@ -241,10 +248,10 @@ pub struct JournalListAllRes {
     pub nama_lengkap: Option<String>,
     pub akun: Option<String>,
     pub rekening: Option<String>,
-    pub debit: f64,
-    pub credit: f64,
+    pub debit: Money,
+    pub credit: Money,
     pub description: String,
-    pub balance_after: f64,
+    pub balance_after: Money,
     pub trx_time: DateTime<Utc>,
 }
 
@@ -266,9 +273,9 @@ pub async fn list_journals_public(
         r#"
         SELECT journal_id,
                account_no,
-               debit,
-               credit,
-               balance_after,
+               round(debit::numeric, 2)         AS debit,
+               round(credit::numeric, 2)        AS credit,
+               round(balance_after::numeric, 2) AS balance_after,
                trx_time,
                description,
                nama_lengkap
@@ -287,9 +294,9 @@ pub async fn list_journals_public(
         .map(|row| JournalPublicRes {
             journal_id: row.get("journal_id"),
             account_no: row.get("account_no"),
-            debit: row.get::<f64, _>("debit"),
-            credit: row.get::<f64, _>("credit"),
-            balance_after: row.get::<f64, _>("balance_after"),
+            debit: row.get::<Money, _>("debit"),
+            credit: row.get::<Money, _>("credit"),
+            balance_after: row.get::<Money, _>("balance_after"),
             trx_time: row.get("trx_time"),
             description: row
                 .try_get::<Option<String>, _>("description")
@@ -321,10 +328,10 @@ pub async fn list_journals_list_all(
                nama_lengkap,
                akun,
                rekening,
-               debit::float8  AS debit,
-               credit::float8 AS credit,
+               round(debit::numeric, 2)         AS debit,
+               round(credit::numeric, 2)        AS credit,
                description,
-               balance_after::float8 AS balance_after,
+               round(balance_after::numeric, 2) AS balance_after,
                trx_time
         FROM public.lab_sp_get_journals_paged($1,$2,$3,$4,$5)
         "#,
@@ -348,14 +355,14 @@ pub async fn list_journals_list_all(
                 .flatten(),
             akun: row.try_get::<Option<String>, _>("akun").ok().flatten(),
             rekening: row.try_get::<Option<String>, _>("rekening").ok().flatten(),
-            debit: row.get::<f64, _>("debit"),
-            credit: row.get::<f64, _>("credit"),
+            debit: row.get::<Money, _>("debit"),
+            credit: row.get::<Money, _>("credit"),
             description: row
                 .try_get::<Option<String>, _>("description")
                 .ok()
                 .flatten()
                 .unwrap_or_default(),
-            balance_after: row.get::<f64, _>("balance_after"),
+            balance_after: row.get::<Money, _>("balance_after"),
             trx_time: row.get("trx_time"),
         })
         .collect();
@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use serde::Serialize;
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::{ApiError, ApiResult},
+    models::Claims,
+    money::Money,
+};
+
+/// Jenis debit lab_fun_withdraw yang dihitung limitnya (`transfer_out` dihitung langsung
+/// oleh fungsi transfer)
+#[derive(Clone, Copy)]
+pub enum LimitTxnType {
+    CashWithdraw,
+    Ppob,
+}
+
+impl LimitTxnType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LimitTxnType::CashWithdraw => "cash_withdraw",
+            LimitTxnType::Ppob => "ppob",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct LimitUsageRes {
+    pub txn_type: String,
+    /// "user" = gabungan semua rekening user, "account" = rekening ini saja
+    pub scope: String,
+    pub per_txn_max: Option<Money>,
+    pub daily_max: Option<Money>,
+    pub daily_used: Money,
+    pub daily_remaining: Option<Money>,
+    pub monthly_max: Option<Money>,
+    pub monthly_used: Money,
+    pub monthly_remaining: Option<Money>,
+}
+
+#[derive(Serialize)]
+pub struct AccountLimitsRes {
+    pub account_id: Uuid,
+    pub tier: String,
+    pub limits: Vec<LimitUsageRes>,
+}
+
+/// Terjemahkan RAISE limit dari lab_fun_limit_consume / lab_fun_payee_cooling_consume
+pub(crate) fn limit_error(msg: &str) -> Option<ApiError> {
+    if msg.contains("LIMIT_PER_TXN") {
+        Some(ApiError::Forbidden(
+            "amount exceeds per-transaction limit".into(),
+        ))
+    } else if msg.contains("LIMIT_DAILY") {
+        Some(ApiError::Forbidden("daily limit exceeded".into()))
+    } else if msg.contains("LIMIT_MONTHLY") {
+        Some(ApiError::Forbidden("monthly limit exceeded".into()))
+    } else if msg.contains("LIMIT_COOLING_OFF") {
+        Some(ApiError::Forbidden(
+            "new beneficiary cooling-off limit exceeded".into(),
+        ))
+    } else {
+        None
+    }
+}
+
+/// GET /accounts/:account_id/limits — limit tier user & sisa kuota hari/bulan berjalan
+pub async fn get_account_limits(
+    State(state): State<SharedState>,
+    Extension(claims): Extension<Claims>,
+    Path(account_id): Path<Uuid>,
+) -> ApiResult<Json<AccountLimitsRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let rows = sqlx::query(
+        r#"SELECT txn_type, scope, per_txn_max,
+                  daily_max, daily_used, daily_max - LEAST(daily_used, daily_max) AS daily_remaining,
+                  monthly_max, monthly_used, monthly_max - LEAST(monthly_used, monthly_max) AS monthly_remaining
+           FROM lab_fun_account_limits($1, $2)"#,
+    )
+    .bind(user_id)
+    .bind(account_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|e| {
+        let msg = e.to_string();
+        if msg.contains("ACCOUNT_NOT_OWNED") {
+            ApiError::Forbidden("account not owned".into())
+        } else {
+            ApiError::Internal(msg)
+        }
+    })?;
+
+    let tier: String = sqlx::query_scalar("SELECT tier FROM lab_users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    let limits = rows
+        .into_iter()
+        .map(|r| LimitUsageRes {
+            txn_type: r.get("txn_type"),
+            scope: r.get("scope"),
+            per_txn_max: r.get("per_txn_max"),
+            daily_max: r.get("daily_max"),
+            daily_used: r.get("daily_used"),
+            daily_remaining: r.get("daily_remaining"),
+            monthly_max: r.get("monthly_max"),
+            monthly_used: r.get("monthly_used"),
+            monthly_remaining: r.get("monthly_remaining"),
+        })
+        .collect();
+
+    Ok(Json(AccountLimitsRes {
+        account_id,
+        tier,
+        limits,
+    }))
+}
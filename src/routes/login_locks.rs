@@ -0,0 +1,180 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
+    models::Claims,
+    utils::audit,
+};
+
+/// Gagal tanpa jeda sebelum backoff eksponensial dimulai, per email / per IP
+/// (batas IP lebih longgar karena banyak user bisa berbagi IP lewat NAT)
+const LOGIN_FREE_ATTEMPTS_EMAIL: i32 = 3;
+const LOGIN_FREE_ATTEMPTS_IP: i32 = 20;
+/// Lockout penuh setelah sekian kegagalan per email / per IP
+const LOGIN_LOCK_AFTER_EMAIL: i32 = 10;
+const LOGIN_LOCK_AFTER_IP: i32 = 50;
+const LOGIN_LOCK_SECS: i32 = 60 * 15;
+const LOGIN_BACKOFF_CAP_SECS: i32 = 60 * 5;
+
+const LOGIN_THROTTLED_MSG: &str = "too many login attempts, try again later";
+const THROTTLE_PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+#[derive(Serialize)]
+pub struct LoginLockRes {
+    pub scope: String,
+    pub key: String,
+    pub failures: i32,
+    pub last_failure_at: Option<DateTime<Utc>>,
+    pub blocked_until: Option<DateTime<Utc>>,
+    pub locked: bool,
+}
+
+/// Tolak percobaan login selama email atau IP masih dalam backoff/lockout
+pub(crate) async fn ensure_login_allowed(
+    state: &SharedState,
+    ctx: &RequestContext,
+    email: &str,
+) -> Result<(), ApiError> {
+    let blocked_until: Option<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT lab_fun_login_blocked_until($1,$2)")
+            .bind(email)
+            .bind(&ctx.ip)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(ApiError::from)?;
+
+    if let Some(until) = blocked_until {
+        let meta = serde_json::json!({ "blocked_until": until });
+        audit(state, ctx, None, "login_blocked", Some(email), Some(meta)).await;
+        return Err(ApiError::TooManyRequests(LOGIN_THROTTLED_MSG.into()));
+    }
+    Ok(())
+}
+
+/// Catat kegagalan login (email terdaftar atau tidak, perlakuannya sama)
+pub(crate) async fn record_login_failure(
+    state: &SharedState,
+    ctx: &RequestContext,
+    email: &str,
+    user_id: Option<Uuid>,
+    reason: &str,
+) -> Result<(), ApiError> {
+    let blocked_until: Option<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT lab_fun_login_failure($1,$2,$3,$4,$5,$6,$7,$8)")
+            .bind(email)
+            .bind(&ctx.ip)
+            .bind(LOGIN_FREE_ATTEMPTS_EMAIL)
+            .bind(LOGIN_FREE_ATTEMPTS_IP)
+            .bind(LOGIN_LOCK_AFTER_EMAIL)
+            .bind(LOGIN_LOCK_AFTER_IP)
+            .bind(LOGIN_LOCK_SECS)
+            .bind(LOGIN_BACKOFF_CAP_SECS)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(ApiError::from)?;
+
+    let meta = serde_json::json!({ "reason": reason, "blocked_until": blocked_until });
+    audit(state, ctx, user_id, "login_failed", Some(email), Some(meta)).await;
+    Ok(())
+}
+
+pub(crate) async fn record_login_success(state: &SharedState, email: &str) -> Result<(), ApiError> {
+    sqlx::query("SELECT lab_fun_login_success($1)")
+        .bind(email)
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(())
+}
+
+/// Job berkala: hapus baris `lab_login_throttle` yang backoff/lockout-nya sudah kedaluwarsa
+pub fn spawn_throttle_purge_job(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(THROTTLE_PURGE_INTERVAL);
+        loop {
+            tick.tick().await;
+            let res: Result<i32, _> = sqlx::query_scalar("SELECT lab_fun_login_throttle_purge()")
+                .fetch_one(&pool)
+                .await;
+            match res {
+                Ok(0) => {}
+                Ok(n) => tracing::info!("{} expired login throttle rows purged", n),
+                Err(e) => tracing::warn!("login throttle purge failed: {}", e),
+            }
+        }
+    });
+}
+
+/// GET /admin/login-locks — email/IP/user 2FA yang sedang backoff atau terkunci
+pub async fn list_login_locks(
+    State(state): State<SharedState>,
+    Extension(_claims): Extension<Claims>,
+) -> ApiResult<Json<Vec<LoginLockRes>>> {
+    let rows = sqlx::query(
+        r#"SELECT scope, key, failures, last_failure_at, blocked_until, locked
+           FROM lab_fun_list_login_locks()"#,
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::from)?;
+
+    let mut items = Vec::with_capacity(rows.len());
+    for row in rows {
+        items.push(LoginLockRes {
+            scope: row.try_get("scope").map_err(ApiError::from)?,
+            key: row.try_get("key").map_err(ApiError::from)?,
+            failures: row.try_get("failures").map_err(ApiError::from)?,
+            last_failure_at: row.try_get("last_failure_at").map_err(ApiError::from)?,
+            blocked_until: row.try_get("blocked_until").map_err(ApiError::from)?,
+            locked: row.try_get("locked").map_err(ApiError::from)?,
+        });
+    }
+
+    Ok(Json(items))
+}
+
+/// DELETE /admin/login-locks/:scope/:key — lepas lockout (scope: email | ip | totp, key totp = user_id)
+pub async fn release_login_lock(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path((scope, key)): Path<(String, String)>,
+) -> ApiResult<axum::http::StatusCode> {
+    let admin_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    if !matches!(scope.as_str(), "email" | "ip" | "totp") {
+        return Err(ApiError::BadRequest("scope must be email|ip|totp".into()).into());
+    }
+
+    let ok: bool = sqlx::query_scalar("SELECT lab_fun_release_login_lock($1,$2)")
+        .bind(&scope)
+        .bind(&key)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if !ok {
+        return Err(ApiError::NotFound("lock not found".into()).into());
+    }
+
+    let meta = serde_json::json!({ "scope": scope });
+    audit(
+        &state,
+        &ctx,
+        Some(admin_id),
+        "login_lock_release",
+        Some(&key),
+        Some(meta),
+    )
+    .await;
+
+    Ok(axum::http::StatusCode::OK)
+}
@@ -0,0 +1,358 @@
+use axum::{extract::State, Extension, Json};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
+    models::{Claims, MfaPendingClaims},
+    routes::auth::{issue_tokens, TokenRes},
+    routes::login_locks::record_login_success,
+    totp,
+    utils::{audit, sha256_bytes},
+};
+
+const MFA_ISSUER: &str = "labapi";
+const MFA_PENDING_PURPOSE: &str = "mfa_pending";
+const MFA_PENDING_TTL_SECS: i64 = 60 * 5;
+const RECOVERY_CODE_COUNT: usize = 8;
+/// Kode 2FA (TOTP / recovery) salah per user: jeda backoff setelah beberapa kali gagal,
+/// lockout penuh setelah `TOTP_LOCK_AFTER` kali
+const TOTP_FREE_ATTEMPTS: i32 = 3;
+const TOTP_LOCK_AFTER: i32 = 5;
+const TOTP_LOCK_SECS: i32 = 60 * 30;
+const TOTP_BACKOFF_CAP_SECS: i32 = 60 * 5;
+
+const TOTP_THROTTLED_MSG: &str = "too many mfa attempts, try again later";
+
+#[derive(Serialize)]
+pub struct MfaEnrollRes {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+#[derive(Deserialize)]
+pub struct MfaConfirmReq {
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct MfaConfirmRes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct MfaPendingRes {
+    pub mfa_required: bool,
+    pub mfa_token: String,
+    pub expires_in: i64,
+}
+
+#[derive(Deserialize)]
+pub struct LoginMfaReq {
+    pub mfa_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+/// Cek apakah user sudah mengaktifkan 2FA (enrollment terkonfirmasi)
+pub(crate) async fn mfa_enabled(state: &SharedState, user_id: Uuid) -> Result<bool, ApiError> {
+    let enabled: Option<bool> = sqlx::query_scalar("SELECT enabled FROM lab_fun_mfa_get($1)")
+        .bind(user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(enabled.unwrap_or(false))
+}
+
+/// Token "mfa pending" berumur pendek, ditukar di /auth/login/mfa
+pub(crate) fn issue_mfa_pending_token(
+    state: &SharedState,
+    user_id: Uuid,
+) -> Result<MfaPendingRes, ApiError> {
+    let exp = (Utc::now() + Duration::seconds(MFA_PENDING_TTL_SECS)).timestamp() as usize;
+    let claims = MfaPendingClaims {
+        sub: user_id.to_string(),
+        purpose: MFA_PENDING_PURPOSE.into(),
+        exp,
+        jti: Uuid::new_v4(),
+    };
+    let mfa_token = state.jwt_keys.sign(&claims)?;
+
+    Ok(MfaPendingRes {
+        mfa_required: true,
+        mfa_token,
+        expires_in: MFA_PENDING_TTL_SECS,
+    })
+}
+
+/// Tolak percobaan kode 2FA selama user masih dalam backoff/lockout
+async fn ensure_totp_allowed(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+) -> Result<(), ApiError> {
+    let blocked_until: Option<chrono::DateTime<Utc>> =
+        sqlx::query_scalar("SELECT lab_fun_totp_blocked_until($1)")
+            .bind(user_id)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(ApiError::from)?;
+
+    if let Some(until) = blocked_until {
+        let meta = serde_json::json!({ "blocked_until": until });
+        audit(state, ctx, Some(user_id), "mfa_blocked", None, Some(meta)).await;
+        return Err(ApiError::TooManyRequests(TOTP_THROTTLED_MSG.into()));
+    }
+    Ok(())
+}
+
+/// Catat kode 2FA salah; counter tidak di-reset oleh login password yang sukses
+async fn record_totp_failure(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+) -> Result<(), ApiError> {
+    let row = sqlx::query("SELECT blocked_until, locked FROM lab_fun_totp_failure($1,$2,$3,$4,$5)")
+        .bind(user_id)
+        .bind(TOTP_FREE_ATTEMPTS)
+        .bind(TOTP_LOCK_AFTER)
+        .bind(TOTP_LOCK_SECS)
+        .bind(TOTP_BACKOFF_CAP_SECS)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    if row.get::<bool, _>("locked") {
+        let until: Option<chrono::DateTime<Utc>> = row.get("blocked_until");
+        let meta = serde_json::json!({ "blocked_until": until });
+        audit(state, ctx, Some(user_id), "mfa_locked", None, Some(meta)).await;
+    }
+    Ok(())
+}
+
+async fn record_totp_success(state: &SharedState, user_id: Uuid) -> Result<(), ApiError> {
+    sqlx::query("SELECT lab_fun_totp_success($1)")
+        .bind(user_id)
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    Ok(())
+}
+
+/// Verifikasi kode TOTP user yang 2FA-nya aktif (dengan proteksi replay per time step).
+/// Kode salah dihitung ke throttle 2FA per user; saat terkunci → 429.
+pub(crate) async fn verify_user_totp(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+    code: &str,
+) -> Result<(), ApiError> {
+    ensure_totp_allowed(state, ctx, user_id).await?;
+
+    let row = sqlx::query("SELECT secret, enabled FROM lab_fun_mfa_get($1)")
+        .bind(user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or_else(|| ApiError::BadRequest("mfa not enrolled".into()))?;
+
+    let secret: String = row.get("secret");
+    let enabled: bool = row.get("enabled");
+    if !enabled {
+        return Err(ApiError::BadRequest("mfa not enabled".into()));
+    }
+
+    let Some(step) = totp::verify(&secret, code, Utc::now().timestamp()) else {
+        record_totp_failure(state, ctx, user_id).await?;
+        return Err(ApiError::Unauthorized("invalid mfa code".into()));
+    };
+    mark_step(state, user_id, step).await?;
+    record_totp_success(state, user_id).await
+}
+
+async fn mark_step(state: &SharedState, user_id: Uuid, step: i64) -> Result<(), ApiError> {
+    let fresh: bool = sqlx::query_scalar("SELECT lab_fun_mfa_mark_step($1,$2)")
+        .bind(user_id)
+        .bind(step)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if fresh {
+        Ok(())
+    } else {
+        Err(ApiError::Unauthorized("mfa code already used".into()))
+    }
+}
+
+/// POST /auth/mfa/enroll — buat secret baru (belum aktif sampai dikonfirmasi)
+pub async fn enroll(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+) -> ApiResult<Json<MfaEnrollRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let email: String = sqlx::query_scalar("SELECT email FROM lab_fun_get_user_by_id($1)")
+        .bind(user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or_else(|| ApiError::NotFound("user not found".into()))?;
+
+    let secret = totp::generate_secret();
+    sqlx::query("SELECT lab_fun_mfa_enroll($1,$2)")
+        .bind(user_id)
+        .bind(&secret)
+        .execute(&state.pool)
+        .await
+        .map_err(|e| {
+            let msg = e.to_string();
+            if msg.contains("MFA_ALREADY_ENABLED") {
+                ApiError::BadRequest("mfa already enabled".into())
+            } else {
+                ApiError::Internal(msg)
+            }
+        })?;
+
+    audit(&state, &ctx, Some(user_id), "mfa_enroll", None, None).await;
+
+    Ok(Json(MfaEnrollRes {
+        provisioning_uri: totp::provisioning_uri(&secret, &email, MFA_ISSUER),
+        secret,
+    }))
+}
+
+/// POST /auth/mfa/confirm — aktifkan 2FA dengan kode pertama, kembalikan recovery codes
+pub async fn confirm(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Json(req): Json<MfaConfirmReq>,
+) -> ApiResult<Json<MfaConfirmRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let row = sqlx::query("SELECT secret, enabled FROM lab_fun_mfa_get($1)")
+        .bind(user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or_else(|| ApiError::BadRequest("mfa not enrolled".into()))?;
+    let secret: String = row.get("secret");
+    if row.get::<bool, _>("enabled") {
+        return Err(ApiError::BadRequest("mfa already enabled".into()).into());
+    }
+
+    ensure_totp_allowed(&state, &ctx, user_id).await?;
+    let Some(step) = totp::verify(&secret, &req.code, Utc::now().timestamp()) else {
+        record_totp_failure(&state, &ctx, user_id).await?;
+        return Err(ApiError::Unauthorized("invalid mfa code".into()).into());
+    };
+    mark_step(&state, user_id, step).await?;
+    record_totp_success(&state, user_id).await?;
+
+    let recovery_codes = totp::generate_recovery_codes(RECOVERY_CODE_COUNT);
+    let hashes: Vec<Vec<u8>> = recovery_codes
+        .iter()
+        .map(|c| sha256_bytes(&totp::normalize_recovery_code(c)))
+        .collect();
+
+    let ok: bool = sqlx::query_scalar("SELECT lab_fun_mfa_confirm($1,$2)")
+        .bind(user_id)
+        .bind(hashes)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if !ok {
+        return Err(ApiError::BadRequest("mfa already enabled".into()).into());
+    }
+
+    audit(&state, &ctx, Some(user_id), "mfa_enable", None, None).await;
+
+    Ok(Json(MfaConfirmRes { recovery_codes }))
+}
+
+/// POST /auth/login/mfa — langkah kedua login: tukar mfa_token + kode TOTP/recovery
+pub async fn login_mfa(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Json(req): Json<LoginMfaReq>,
+) -> ApiResult<Json<TokenRes>> {
+    let data = state
+        .jwt_keys
+        .verify::<MfaPendingClaims>(&req.mfa_token)
+        .map_err(|_| ApiError::Unauthorized("invalid or expired mfa token".into()))?;
+    if data.claims.purpose != MFA_PENDING_PURPOSE {
+        return Err(ApiError::Unauthorized("invalid or expired mfa token".into()).into());
+    }
+    let user_id = Uuid::parse_str(&data.claims.sub)
+        .map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    // satu mfa_token = satu percobaan kode; gagal → login password lagi
+    let exp_ts = chrono::DateTime::<Utc>::from_timestamp(data.claims.exp as i64, 0)
+        .unwrap_or_else(|| Utc::now() + Duration::seconds(MFA_PENDING_TTL_SECS));
+    let fresh: bool = sqlx::query_scalar("SELECT lab_fun_mfa_pending_consume($1,$2)")
+        .bind(data.claims.jti)
+        .bind(exp_ts)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if !fresh {
+        return Err(ApiError::Unauthorized("invalid or expired mfa token".into()).into());
+    }
+
+    let method = match (req.code.as_deref(), req.recovery_code.as_deref()) {
+        (Some(code), _) => {
+            if let Err(e) = verify_user_totp(&state, &ctx, user_id, code).await {
+                audit(&state, &ctx, Some(user_id), "mfa_login_failed", None, None).await;
+                return Err(e.into());
+            }
+            "totp"
+        }
+        (None, Some(recovery)) => {
+            ensure_totp_allowed(&state, &ctx, user_id).await?;
+            let sha = sha256_bytes(&totp::normalize_recovery_code(recovery));
+            let used: bool = sqlx::query_scalar("SELECT lab_fun_mfa_use_recovery_code($1,$2)")
+                .bind(user_id)
+                .bind(sha)
+                .fetch_one(&state.pool)
+                .await
+                .map_err(ApiError::from)?;
+            if !used {
+                record_totp_failure(&state, &ctx, user_id).await?;
+                audit(&state, &ctx, Some(user_id), "mfa_login_failed", None, None).await;
+                return Err(ApiError::Unauthorized("invalid recovery code".into()).into());
+            }
+            record_totp_success(&state, user_id).await?;
+            audit(&state, &ctx, Some(user_id), "mfa_recovery_used", None, None).await;
+            "recovery_code"
+        }
+        (None, None) => {
+            return Err(ApiError::BadRequest("code or recovery_code is required".into()).into());
+        }
+    };
+
+    let user = sqlx::query("SELECT email, role, is_active FROM lab_fun_get_user_by_id($1)")
+        .bind(user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::from)?
+        .ok_or_else(|| ApiError::Unauthorized("user not found".into()))?;
+    if !user.get::<bool, _>("is_active") {
+        return Err(ApiError::Forbidden("account disabled".into()).into());
+    }
+    record_login_success(&state, user.get::<&str, _>("email")).await?;
+    let role: String = user.get("role");
+
+    let tokens = issue_tokens(&state, &ctx, user_id, role.clone()).await?;
+
+    let meta = serde_json::json!({ "token_id": tokens.token_id, "role": role, "mfa": method });
+    audit(&state, &ctx, Some(user_id), "login", None, Some(meta)).await;
+
+    Ok(Json(tokens))
+}
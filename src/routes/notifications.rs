@@ -11,6 +11,7 @@ use jsonwebtoken::{Algorithm, EncodingKey, Header};
 use serde::{Deserialize, Serialize};
 use sqlx::Row;
 use tokio::{sync::Semaphore, task::JoinSet};
+use uuid::Uuid;
 
 const FCM_SEND_CONCURRENCY: usize = 8;
 
@@ -190,6 +191,47 @@ async fn send_notification_inner(
     Ok(name)
 }
 
+/// Push notifikasi ke device milik satu user (mis. notifikasi keamanan akun).
+/// Firebase belum dikonfigurasi / user belum punya FCM token => dilewati.
+pub(crate) async fn notify_user(
+    state: &SharedState,
+    user_id: Uuid,
+    title: &str,
+    body: &str,
+    data: Option<&HashMap<String, String>>,
+) -> ApiResult<()> {
+    let Some(firebase) = state.firebase.as_ref() else {
+        return Ok(());
+    };
+    let token: Option<String> = sqlx::query_scalar("SELECT fcm_token FROM lab_users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::from)?
+        .flatten();
+    let Some(token) = token.filter(|t| !t.trim().is_empty()) else {
+        return Ok(());
+    };
+
+    let access_token = fetch_access_token(firebase).await?;
+    let client = reqwest::Client::new();
+    match send_fcm_message_raw(
+        &client,
+        firebase,
+        &access_token,
+        token.trim(),
+        title,
+        body,
+        data,
+    )
+    .await
+    {
+        Ok(_) => Ok(()),
+        Err(err) if err.is_unregistered() => Ok(()),
+        Err(err) => Err(ApiError::Internal(format!("fcm send error: {}", err.body)).into()),
+    }
+}
+
 async fn fetch_notif_payload(
     state: &SharedState,
     id_store: i32,
@@ -6,6 +6,7 @@ use uuid::Uuid;
 use crate::{
     app_state::SharedState,
     errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
     models::Claims,
     utils::audit,
 };
@@ -95,6 +96,7 @@ pub async fn get_profile(
 
 pub async fn upsert_profile(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Json(p): Json<ProfileUpsertReq>,
 ) -> ApiResult<axum::http::StatusCode> {
@@ -118,12 +120,13 @@ pub async fn upsert_profile(
     .await
     .map_err(ApiError::from)?;
 
-    audit(&state, Some(user_id), "profile_upsert", None, None).await;
+    audit(&state, &ctx, Some(user_id), "profile_upsert", None, None).await;
     Ok(axum::http::StatusCode::OK)
 }
 
 pub async fn update_fcm_token(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
     Json(p): Json<FcmTokenUpdateReq>,
 ) -> ApiResult<axum::http::StatusCode> {
@@ -137,6 +140,14 @@ pub async fn update_fcm_token(
         .await
         .map_err(ApiError::from)?;
 
-    audit(&state, Some(user_id), "fcm_token_notification", None, None).await;
+    audit(
+        &state,
+        &ctx,
+        Some(user_id),
+        "fcm_token_notification",
+        None,
+        None,
+    )
+    .await;
     Ok(axum::http::StatusCode::OK)
 }
@@ -0,0 +1,154 @@
+use axum::{
+    extract::{Path, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
+    models::Claims,
+    utils::audit,
+};
+
+#[derive(Serialize)]
+pub struct SessionRes {
+    pub token_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_rotated_at: Option<DateTime<Utc>>,
+    pub user_agent: Option<String>,
+    pub ip_addr: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub current: bool,
+}
+
+#[derive(Serialize)]
+pub struct SessionsListRes {
+    pub items: Vec<SessionRes>,
+}
+
+#[derive(Serialize)]
+pub struct RevokeAllSessionsRes {
+    pub revoked: i32,
+}
+
+/// Cabut access token yang sedang dipakai request ini
+pub(crate) async fn revoke_current_access_token(
+    state: &SharedState,
+    claims: &Claims,
+) -> Result<(), ApiError> {
+    let exp_ts = DateTime::<Utc>::from_timestamp(claims.exp as i64, 0)
+        .unwrap_or_else(|| Utc::now() + chrono::Duration::minutes(15));
+    sqlx::query("SELECT lab_fun_revoke_access_token($1,$2)")
+        .bind(claims.jti)
+        .bind(exp_ts)
+        .execute(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    state.revoked_jtis.insert(claims.jti, exp_ts.timestamp());
+    Ok(())
+}
+
+/// GET /auth/sessions — daftar sesi (refresh token aktif) milik caller
+pub async fn list_sessions(
+    State(state): State<SharedState>,
+    Extension(claims): Extension<Claims>,
+) -> ApiResult<Json<SessionsListRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let rows = sqlx::query(
+        r#"SELECT token_id, session_started_at, last_rotated_at, user_agent, ip_addr,
+                  expires_at, access_jti
+           FROM lab_fun_list_sessions($1)"#,
+    )
+    .bind(user_id)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::from)?;
+
+    let mut items = Vec::with_capacity(rows.len());
+    for row in rows {
+        let access_jti: Option<Uuid> = row.try_get("access_jti").map_err(ApiError::from)?;
+        items.push(SessionRes {
+            token_id: row.try_get("token_id").map_err(ApiError::from)?,
+            created_at: row.try_get("session_started_at").map_err(ApiError::from)?,
+            last_rotated_at: row.try_get("last_rotated_at").map_err(ApiError::from)?,
+            user_agent: row.try_get("user_agent").map_err(ApiError::from)?,
+            ip_addr: row.try_get("ip_addr").map_err(ApiError::from)?,
+            expires_at: row.try_get("expires_at").map_err(ApiError::from)?,
+            current: access_jti == Some(claims.jti),
+        });
+    }
+
+    Ok(Json(SessionsListRes { items }))
+}
+
+/// DELETE /auth/sessions/:token_id — cabut satu sesi milik caller
+pub async fn revoke_session(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(token_id): Path<Uuid>,
+) -> ApiResult<axum::http::StatusCode> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let ok: bool = sqlx::query_scalar("SELECT lab_fun_revoke_user_session($1,$2)")
+        .bind(user_id)
+        .bind(token_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if !ok {
+        return Err(ApiError::NotFound("session not found".into()).into());
+    }
+
+    let meta = serde_json::json!({ "token_id": token_id });
+    audit(
+        &state,
+        &ctx,
+        Some(user_id),
+        "session_revoke",
+        Some(&token_id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    Ok(axum::http::StatusCode::OK)
+}
+
+/// DELETE /auth/sessions — logout di semua device (termasuk sesi ini)
+pub async fn revoke_all_sessions(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+) -> ApiResult<Json<RevokeAllSessionsRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let revoked: i32 = sqlx::query_scalar("SELECT lab_fun_revoke_all_sessions($1,$2)")
+        .bind(user_id)
+        .bind(Option::<Uuid>::None)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    revoke_current_access_token(&state, &claims).await?;
+
+    let meta = serde_json::json!({ "revoked": revoked });
+    audit(
+        &state,
+        &ctx,
+        Some(user_id),
+        "session_revoke_all",
+        None,
+        Some(meta),
+    )
+    .await;
+
+    Ok(Json(RevokeAllSessionsRes { revoked }))
+}
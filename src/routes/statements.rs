@@ -0,0 +1,435 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, Utc};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
+    models::Claims,
+    money::Money,
+    pdf::{PdfLine, TextPdf},
+    utils::audit,
+};
+
+/// Rentang maksimum satu statement
+const STATEMENT_MAX_DAYS: i64 = 366;
+const STATEMENT_FONT_SIZE: f32 = 7.5;
+/// Lebar kolom tabel PDF (karakter): tanggal, keterangan, masuk, keluar, saldo
+const COL_DATE: usize = 16;
+const COL_DESC: usize = 38;
+const COL_AMOUNT: usize = 17;
+const COL_BALANCE: usize = 19;
+
+#[derive(Deserialize)]
+pub struct StatementQuery {
+    /// default: tanggal 1 bulan berjalan (WIB)
+    pub from: Option<NaiveDate>,
+    /// default: hari ini (WIB)
+    pub to: Option<NaiveDate>,
+    /// pdf (default) | csv
+    pub format: Option<String>,
+}
+
+struct StatementLine {
+    journal_id: Uuid,
+    trx_time: DateTime<Utc>,
+    description: String,
+    debit: Money,
+    credit: Money,
+    balance: Money,
+}
+
+struct Statement {
+    id: Uuid,
+    account_no: String,
+    holder_name: String,
+    from: NaiveDate,
+    to: NaiveDate,
+    opening: Money,
+    closing: Money,
+    lines: Vec<StatementLine>,
+    sha256: String,
+    issued_at: DateTime<Utc>,
+}
+
+fn wib() -> FixedOffset {
+    FixedOffset::east_opt(7 * 3600).expect("valid offset")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Hash verifikasi atas isi statement (bukan atas file), sama untuk PDF & CSV
+fn statement_digest(
+    account_no: &str,
+    holder_name: &str,
+    from: NaiveDate,
+    to: NaiveDate,
+    opening: &Money,
+    lines: &[StatementLine],
+) -> Vec<u8> {
+    let mut hasher = Sha256::new();
+    hasher.update(format!(
+        "labapi-statement-v1\n{}\n{}\n{}\n{}\n{}\n",
+        account_no, holder_name, from, to, opening
+    ));
+    for l in lines {
+        hasher.update(format!(
+            "{}|{}|{}|{}|{}\n",
+            l.journal_id,
+            l.trx_time.to_rfc3339(),
+            l.debit,
+            l.credit,
+            l.balance
+        ));
+    }
+    hasher.finalize().to_vec()
+}
+
+/// "1234567.50" → "1.234.567,50"
+fn format_idr(amount: &Money) -> String {
+    let raw = amount.to_string();
+    let (sign, raw) = match raw.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", raw.as_str()),
+    };
+    let (int, frac) = raw.split_once('.').unwrap_or((raw, "00"));
+    let mut grouped = String::with_capacity(int.len() + int.len() / 3);
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            grouped.push('.');
+        }
+        grouped.push(c);
+    }
+    format!("{}{},{}", sign, grouped, frac)
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_string()
+    } else {
+        let mut s: String = text.chars().take(width - 1).collect();
+        s.push('~');
+        s
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn render_csv(st: &Statement) -> Vec<u8> {
+    let mut out = String::new();
+    out.push_str("account_no,holder_name,period_from,period_to,opening_balance,closing_balance,statement_id,verification_sha256\n");
+    out.push_str(&format!(
+        "{},{},{},{},{},{},{},{}\n\n",
+        csv_field(&st.account_no),
+        csv_field(&st.holder_name),
+        st.from,
+        st.to,
+        st.opening,
+        st.closing,
+        st.id,
+        st.sha256
+    ));
+    out.push_str("trx_time,journal_id,description,debit,credit,balance\n");
+    for l in &st.lines {
+        out.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            l.trx_time.with_timezone(&wib()).to_rfc3339(),
+            l.journal_id,
+            csv_field(&l.description),
+            l.debit,
+            l.credit,
+            l.balance
+        ));
+    }
+    out.into_bytes()
+}
+
+fn render_pdf(st: &Statement) -> Vec<u8> {
+    let pdf = TextPdf::new(STATEMENT_FONT_SIZE);
+    let width = pdf.chars_per_line();
+    let rule = "-".repeat(width);
+    let row = |date: &str, desc: &str, masuk: &str, keluar: &str, saldo: &str| {
+        format!(
+            "{:<dw$} {:<cw$} {:>aw$} {:>aw$} {:>bw$}",
+            date,
+            truncate(desc, COL_DESC),
+            masuk,
+            keluar,
+            saldo,
+            dw = COL_DATE,
+            cw = COL_DESC,
+            aw = COL_AMOUNT,
+            bw = COL_BALANCE
+        )
+    };
+
+    let header = |page: usize, pages: usize| {
+        vec![
+            PdfLine::bold("REKENING KORAN"),
+            PdfLine::plain(""),
+            PdfLine::plain(format!("Nama          : {}", st.holder_name)),
+            PdfLine::plain(format!("No. Rekening  : {}", st.account_no)),
+            PdfLine::plain(format!(
+                "Periode       : {} s/d {}",
+                st.from.format("%d-%m-%Y"),
+                st.to.format("%d-%m-%Y")
+            )),
+            PdfLine::plain(format!(
+                "Dicetak       : {} WIB",
+                st.issued_at.with_timezone(&wib()).format("%d-%m-%Y %H:%M")
+            )),
+            PdfLine::plain(format!("Halaman       : {} / {}", page, pages)),
+            PdfLine::plain(""),
+            PdfLine::bold(row("Tanggal", "Keterangan", "Masuk", "Keluar", "Saldo")),
+            PdfLine::plain(rule.clone()),
+        ]
+    };
+    let footer = vec![
+        PdfLine::plain(rule.clone()),
+        PdfLine::bold(row("", "Saldo akhir", "", "", &format_idr(&st.closing))),
+        PdfLine::plain(""),
+        PdfLine::plain(format!("ID statement     : {}", st.id)),
+        PdfLine::plain(format!("Kode verifikasi  : {}", st.sha256)),
+        PdfLine::plain(
+            "Dokumen ini dicetak otomatis dan sah tanpa tanda tangan. Kode verifikasi dapat",
+        ),
+        PdfLine::plain("dicocokkan ke layanan nasabah untuk memastikan keaslian isi dokumen."),
+    ];
+
+    let mut body = vec![PdfLine::plain(row(
+        "",
+        "Saldo awal",
+        "",
+        "",
+        &format_idr(&st.opening),
+    ))];
+    body.extend(st.lines.iter().map(|l| {
+        let amount = |m: &Money| {
+            if m.is_positive() {
+                format_idr(m)
+            } else {
+                String::new()
+            }
+        };
+        PdfLine::plain(row(
+            &l.trx_time
+                .with_timezone(&wib())
+                .format("%d-%m-%Y %H:%M")
+                .to_string(),
+            &l.description,
+            &amount(&l.debit),
+            &amount(&l.credit),
+            &format_idr(&l.balance),
+        ))
+    }));
+
+    // header di tiap halaman; footer harus muat utuh di halaman terakhir
+    let per_page = pdf.lines_per_page() - header(1, 1).len();
+    let mut chunks: Vec<Vec<PdfLine>> = Vec::new();
+    let mut body = body.into_iter().peekable();
+    while body.peek().is_some() {
+        chunks.push(body.by_ref().take(per_page).collect());
+    }
+    // body minimal berisi baris saldo awal, jadi chunks tidak pernah kosong
+    if chunks
+        .last()
+        .is_some_and(|c| c.len() + footer.len() > per_page)
+    {
+        chunks.push(Vec::new());
+    }
+    if let Some(last) = chunks.last_mut() {
+        last.extend(footer);
+    }
+
+    let total = chunks.len();
+    let pages: Vec<Vec<PdfLine>> = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut page = header(i + 1, total);
+            page.extend(chunk);
+            page
+        })
+        .collect();
+    pdf.render(&format!("Rekening koran {}", st.account_no), &pages)
+}
+
+/// GET /accounts/:account_id/statement?from=&to=&format=pdf|csv — rekening koran dari jurnal
+pub async fn get_account_statement(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Path(account_id): Path<Uuid>,
+    Query(q): Query<StatementQuery>,
+) -> ApiResult<Response> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let format = q.format.as_deref().unwrap_or("pdf").to_ascii_lowercase();
+    if format != "pdf" && format != "csv" {
+        return Err(ApiError::BadRequest("format must be pdf or csv".into()).into());
+    }
+    let today = Utc::now().with_timezone(&wib()).date_naive();
+    let to = q.to.unwrap_or(today);
+    let from = q
+        .from
+        .unwrap_or_else(|| to.with_day(1).expect("day 1 always valid"));
+    if from > to {
+        return Err(ApiError::BadRequest("from must be <= to".into()).into());
+    }
+    if to - from >= Duration::days(STATEMENT_MAX_DAYS) {
+        return Err(ApiError::BadRequest(format!(
+            "statement period must be at most {} days",
+            STATEMENT_MAX_DAYS
+        ))
+        .into());
+    }
+
+    let head = sqlx::query(
+        r#"SELECT account_no, holder_name, opening_balance, current_balance
+           FROM lab_fun_account_statement_header($1,$2,$3)"#,
+    )
+    .bind(user_id)
+    .bind(account_id)
+    .bind(from)
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|e| {
+        let msg = e.to_string();
+        if msg.contains("ACCOUNT_NOT_OWNED") {
+            ApiError::NotFound("account not found".into())
+        } else {
+            ApiError::Internal(msg)
+        }
+    })?;
+    let account_no: String = head.get("account_no");
+    let holder_name = head
+        .get::<Option<String>, _>("holder_name")
+        .unwrap_or_else(|| "-".into());
+    let opening: Money = head.get("opening_balance");
+    let current_balance: Money = head.get("current_balance");
+
+    let lines: Vec<StatementLine> = sqlx::query(
+        r#"SELECT journal_id, trx_time, description, debit, credit, balance
+           FROM lab_fun_account_statement_lines($1,$2,$3,$4)"#,
+    )
+    .bind(account_id)
+    .bind(from)
+    .bind(to)
+    .bind(&opening)
+    .fetch_all(&state.pool)
+    .await
+    .map_err(ApiError::from)?
+    .into_iter()
+    .map(|r| StatementLine {
+        journal_id: r.get("journal_id"),
+        trx_time: r.get("trx_time"),
+        description: r
+            .get::<Option<String>, _>("description")
+            .unwrap_or_default(),
+        debit: r.get("debit"),
+        credit: r.get("credit"),
+        balance: r.get("balance"),
+    })
+    .collect();
+    let closing = lines
+        .last()
+        .map(|l| l.balance.clone())
+        .unwrap_or_else(|| opening.clone());
+    // periode sampai hari ini: saldo akhir wajib sama dengan saldo rekening (mis. ada mutasi
+    // masuk di antara query header & baris) — jangan terbitkan statement yang tidak cocok
+    if to >= today && closing != current_balance {
+        tracing::error!(
+            "statement balance mismatch for account {}: closing {} != saldo {}",
+            account_id,
+            closing,
+            current_balance
+        );
+        return Err(ApiError::Internal("statement balance mismatch, please retry".into()).into());
+    }
+
+    let digest = statement_digest(&account_no, &holder_name, from, to, &opening, &lines);
+    let id: Uuid =
+        sqlx::query_scalar("SELECT lab_fun_account_statement_issue($1,$2,$3,$4,$5,$6,$7,$8,$9)")
+            .bind(user_id)
+            .bind(account_id)
+            .bind(from)
+            .bind(to)
+            .bind(&format)
+            .bind(&opening)
+            .bind(&closing)
+            .bind(lines.len() as i32)
+            .bind(&digest)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(ApiError::from)?;
+
+    let st = Statement {
+        id,
+        account_no,
+        holder_name,
+        from,
+        to,
+        opening,
+        closing,
+        lines,
+        sha256: hex(&digest),
+        issued_at: Utc::now(),
+    };
+
+    let meta = serde_json::json!({
+        "statement_id": st.id,
+        "from": st.from,
+        "to": st.to,
+        "format": format,
+        "sha256": st.sha256,
+    });
+    audit(
+        &state,
+        &ctx,
+        Some(user_id),
+        "account_statement",
+        Some(&account_id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    let (content_type, body) = if format == "csv" {
+        ("text/csv; charset=utf-8", render_csv(&st))
+    } else {
+        ("application/pdf", render_pdf(&st))
+    };
+    let disposition = format!(
+        "attachment; filename=\"statement-{}-{}-{}.{}\"",
+        st.account_no, st.from, st.to, format
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+            (
+                header::HeaderName::from_static("x-statement-sha256"),
+                st.sha256.clone(),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
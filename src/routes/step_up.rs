@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use axum::{extract::State, Extension, Json};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+use crate::{
+    app_state::SharedState,
+    errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
+    models::Claims,
+    money::Money,
+    routes::mfa::{mfa_enabled, verify_user_totp},
+    utils::{audit, sha256_bytes},
+};
+
+const STEP_UP_TTL_SECS: i64 = 60 * 5;
+const STEP_UP_MAX_ATTEMPTS: i32 = 5;
+const STEP_UP_REQUIRED_MSG: &str = "step_up_required";
+
+/// Ambang step-up per operasi, dari env
+/// `STEP_UP_THRESHOLDS=transfer:5000000,cash_withdraw:5000000,pay_pasca:2000000,account_funding:5000000`
+/// dan `STEP_UP_NEW_BENEFICIARY=true|false` (transfer ke rekening yang belum pernah dituju).
+pub struct StepUpPolicy {
+    thresholds: HashMap<String, Money>,
+    new_beneficiary: bool,
+}
+
+impl StepUpPolicy {
+    pub fn from_env() -> Self {
+        let mut thresholds: HashMap<String, Money> = [
+            ("transfer", 5_000_000),
+            ("cash_withdraw", 5_000_000),
+            ("pay_pasca", 2_000_000),
+            ("account_funding", 5_000_000),
+        ]
+        .into_iter()
+        .map(|(op, amount)| (op.to_string(), Money::from(amount)))
+        .collect();
+
+        let raw = std::env::var("STEP_UP_THRESHOLDS").unwrap_or_default();
+        for entry in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            match entry
+                .split_once(':')
+                .and_then(|(op, amount)| Some((op.trim(), amount.trim().parse::<Money>().ok()?)))
+            {
+                Some((op, amount)) if thresholds.contains_key(op) => {
+                    thresholds.insert(op.to_string(), amount);
+                }
+                _ => tracing::warn!(
+                    "STEP_UP_THRESHOLDS entry ignored (expected operation:amount): {}",
+                    entry
+                ),
+            }
+        }
+
+        let new_beneficiary = std::env::var("STEP_UP_NEW_BENEFICIARY")
+            .map(|v| !matches!(v.trim(), "0" | "false" | "no"))
+            .unwrap_or(true);
+
+        StepUpPolicy {
+            thresholds,
+            new_beneficiary,
+        }
+    }
+
+    fn requires(&self, operation: &str, amount: &Money, new_beneficiary: bool) -> bool {
+        (new_beneficiary && self.new_beneficiary)
+            || self
+                .thresholds
+                .get(operation)
+                .is_some_and(|threshold| amount > threshold)
+    }
+}
+
+/// Payload transaksi yang diikat ke challenge. Client mengirim payload yang sama
+/// persis ke /auth/step_up/challenge dan ke endpoint transaksinya.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum StepUpOperation {
+    Transfer {
+        from_account_no: String,
+        to_account_no: String,
+        amount: Money,
+    },
+    CashWithdraw {
+        account_id: Uuid,
+        amount: Money,
+    },
+    PayPasca {
+        account_id: Uuid,
+        ref_id: String,
+    },
+    /// Setoran awal rekening baru dari rekening sendiri
+    AccountFunding {
+        from_account_id: Uuid,
+        amount: Money,
+    },
+    /// Ganti PIN tanpa PIN lama ("lupa PIN")
+    PinReset {
+        account_id: Uuid,
+    },
+}
+
+impl StepUpOperation {
+    fn name(&self) -> &'static str {
+        match self {
+            StepUpOperation::Transfer { .. } => "transfer",
+            StepUpOperation::CashWithdraw { .. } => "cash_withdraw",
+            StepUpOperation::PayPasca { .. } => "pay_pasca",
+            StepUpOperation::AccountFunding { .. } => "account_funding",
+            StepUpOperation::PinReset { .. } => "pin_reset",
+        }
+    }
+
+    fn digest(&self) -> Result<Vec<u8>, ApiError> {
+        let canonical =
+            serde_json::to_string(self).map_err(|e| ApiError::Internal(e.to_string()))?;
+        Ok(sha256_bytes(&canonical))
+    }
+}
+
+/// Bukti step-up yang dilampirkan pada request transaksi
+#[derive(Deserialize)]
+pub struct StepUpProof {
+    pub challenge_id: Uuid,
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct StepUpChallengeRes {
+    pub challenge_id: Uuid,
+    /// `totp` (aplikasi authenticator) atau `otp` (kode dikirim lewat delivery channel)
+    pub method: String,
+    pub expires_in: i64,
+}
+
+/// POST /auth/step_up/challenge — buat challenge untuk satu payload transaksi
+pub async fn create_challenge(
+    State(state): State<SharedState>,
+    ctx: RequestContext,
+    Extension(claims): Extension<Claims>,
+    Json(op): Json<StepUpOperation>,
+) -> ApiResult<Json<StepUpChallengeRes>> {
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    // 2FA aktif => TOTP; selain itu OTP sekali pakai ke email user
+    let otp = if mfa_enabled(&state, user_id).await? {
+        None
+    } else {
+        use rand_core::{OsRng, RngCore};
+        Some(format!("{:06}", OsRng.next_u32() % 1_000_000))
+    };
+    let method = if otp.is_some() { "otp" } else { "totp" };
+
+    let challenge_id: Uuid = sqlx::query_scalar("SELECT lab_fun_step_up_create($1,$2,$3,$4,$5,$6)")
+        .bind(user_id)
+        .bind(op.name())
+        .bind(op.digest()?)
+        .bind(method)
+        .bind(otp.as_deref().map(sha256_bytes))
+        .bind(Utc::now() + Duration::seconds(STEP_UP_TTL_SECS))
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+
+    if let Some(otp) = otp {
+        let email: String = sqlx::query_scalar("SELECT email FROM lab_fun_get_user_by_id($1)")
+            .bind(user_id)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(ApiError::from)?
+            .ok_or_else(|| ApiError::NotFound("user not found".into()))?;
+        let body = format!(
+            "Kode verifikasi transaksi {} Anda: {} (berlaku {} menit). Jangan berikan kode ini kepada siapa pun.",
+            op.name(),
+            otp,
+            STEP_UP_TTL_SECS / 60
+        );
+        state
+            .delivery
+            .send(&email, "Verifikasi transaksi", &body)
+            .await?;
+    }
+
+    let meta = serde_json::json!({ "operation": op.name(), "method": method });
+    audit(
+        &state,
+        &ctx,
+        Some(user_id),
+        "step_up_challenge",
+        Some(&challenge_id.to_string()),
+        Some(meta),
+    )
+    .await;
+
+    Ok(Json(StepUpChallengeRes {
+        challenge_id,
+        method: method.into(),
+        expires_in: STEP_UP_TTL_SECS,
+    }))
+}
+
+/// Wajibkan bukti step-up jika nominal melewati ambang operasi atau tujuan transfer baru.
+/// Tanpa bukti → 403 `step_up_required`; client membuat challenge lalu mengulang request.
+pub(crate) async fn ensure_step_up(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+    op: &StepUpOperation,
+    amount: &Money,
+    new_beneficiary: bool,
+    proof: Option<&StepUpProof>,
+) -> Result<(), ApiError> {
+    if !state
+        .step_up_policy
+        .requires(op.name(), amount, new_beneficiary)
+    {
+        return Ok(());
+    }
+    let meta = serde_json::json!({
+        "operation": op.name(),
+        "amount": amount,
+        "new_beneficiary": new_beneficiary,
+    });
+    check_proof(state, ctx, user_id, op, proof, meta).await
+}
+
+/// Step-up wajib tanpa melihat ambang (operasi non-nominal, mis. reset PIN)
+pub(crate) async fn require_step_up(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+    op: &StepUpOperation,
+    proof: Option<&StepUpProof>,
+) -> Result<(), ApiError> {
+    let meta = serde_json::json!({ "operation": op.name() });
+    check_proof(state, ctx, user_id, op, proof, meta).await
+}
+
+async fn check_proof(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+    op: &StepUpOperation,
+    proof: Option<&StepUpProof>,
+    meta: serde_json::Value,
+) -> Result<(), ApiError> {
+    let proof = proof.ok_or_else(|| ApiError::Forbidden(STEP_UP_REQUIRED_MSG.into()))?;
+
+    let result = verify_proof(state, ctx, user_id, op, proof).await;
+    let action = if result.is_ok() {
+        "step_up_verified"
+    } else {
+        "step_up_failed"
+    };
+    audit(
+        state,
+        ctx,
+        Some(user_id),
+        action,
+        Some(&proof.challenge_id.to_string()),
+        Some(meta),
+    )
+    .await;
+    result
+}
+
+async fn verify_proof(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+    op: &StepUpOperation,
+    proof: &StepUpProof,
+) -> Result<(), ApiError> {
+    let invalid = || ApiError::Forbidden("invalid or expired step-up proof".into());
+
+    let row =
+        sqlx::query("SELECT method, code_sha256 FROM lab_fun_step_up_attempt($1,$2,$3,$4,$5)")
+            .bind(proof.challenge_id)
+            .bind(user_id)
+            .bind(op.name())
+            .bind(op.digest()?)
+            .bind(STEP_UP_MAX_ATTEMPTS)
+            .fetch_optional(&state.pool)
+            .await
+            .map_err(ApiError::from)?
+            .ok_or_else(invalid)?;
+
+    let method: String = row.get("method");
+    let code = proof.code.trim();
+    if method == "totp" {
+        // 2FA terkunci tetap 429; kesalahan lain disamarkan sebagai bukti tidak valid
+        verify_user_totp(state, ctx, user_id, code)
+            .await
+            .map_err(|e| match e {
+                ApiError::TooManyRequests(_) => e,
+                _ => invalid(),
+            })?;
+    } else {
+        let expected: Option<Vec<u8>> = row.get("code_sha256");
+        if expected.as_deref() != Some(sha256_bytes(code).as_slice()) {
+            return Err(invalid());
+        }
+    }
+
+    let consumed: bool = sqlx::query_scalar("SELECT lab_fun_step_up_consume($1)")
+        .bind(proof.challenge_id)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    if !consumed {
+        return Err(invalid());
+    }
+    Ok(())
+}
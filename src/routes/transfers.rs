@@ -6,18 +6,28 @@ use uuid::Uuid;
 use crate::{
     app_state::SharedState,
     errors::{ApiError, ApiResult},
+    middleware::request_context::RequestContext,
     models::Claims,
-    utils::audit,
+    money::Money,
+    routes::account_lifecycle::account_status_error,
+    routes::beneficiaries::beneficiary_account_no,
+    routes::limits::limit_error,
+    routes::step_up::{ensure_step_up, StepUpOperation, StepUpProof},
+    utils::{audit, verify_account_pin_by_no},
 };
 
 #[derive(Deserialize)]
 pub struct TransferReq {
     pub from_account_no: String,
-    pub to_account_no: String,
-    pub amount: f64,
+    /// isi salah satu: `to_account_no` atau `beneficiary_id` (rekening tujuan tersimpan)
+    pub to_account_no: Option<String>,
+    pub beneficiary_id: Option<Uuid>,
+    pub amount: Money,
     pub description: Option<String>,
     pub pin: String,
     pub akun: String,
+    /// wajib di atas ambang step-up atau untuk rekening tujuan baru
+    pub step_up: Option<StepUpProof>,
 }
 
 #[derive(Serialize)]
@@ -30,46 +40,60 @@ pub struct TransferRes {
 
 pub async fn transfer(
     State(state): State<SharedState>,
+    ctx: RequestContext,
     Extension(claims): Extension<Claims>,
-    Json(req): Json<TransferReq>,
+    Json(mut req): Json<TransferReq>,
 ) -> ApiResult<Json<TransferRes>> {
     // Validasi dasar
-    if req.amount <= 0.0 {
+    if !req.amount.is_positive() {
         return Err(ApiError::BadRequest("amount must be > 0".into()).into());
     }
-    if req.from_account_no.trim() == req.to_account_no.trim() {
+
+    let user_id =
+        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+
+    let to_account_no = match (req.to_account_no.take(), req.beneficiary_id) {
+        (Some(no), None) => no,
+        (None, Some(beneficiary_id)) => {
+            beneficiary_account_no(&state, user_id, beneficiary_id).await?
+        }
+        _ => {
+            return Err(ApiError::BadRequest(
+                "exactly one of to_account_no or beneficiary_id is required".into(),
+            )
+            .into())
+        }
+    };
+    if req.from_account_no.trim() == to_account_no.trim() {
         return Err(ApiError::BadRequest(
             "from_account_no and to_account_no must be different".into(),
         )
         .into());
     }
 
-    let user_id =
-        Uuid::parse_str(&claims.sub).map_err(|_| ApiError::Unauthorized("bad subject".into()))?;
+    verify_account_pin_by_no(&state, &ctx, user_id, &req.from_account_no, &req.pin).await?;
 
-    // =========================
-    // Validasi PIN by account_no
-    // =========================
-    // Kita resolve account_no -> id di subquery (tanpa ubah utils)
-    let pin_ok: Option<bool> = sqlx::query_scalar(
-        r#"
-        SELECT lab_fun_verify_account_pin(
-            $1,
-            (SELECT id FROM lab_accounts WHERE account_no = $2),
-            $3
-        ) AS ok
-        "#,
+    let new_beneficiary: bool = sqlx::query_scalar("SELECT lab_fun_is_new_counterparty($1,$2)")
+        .bind(user_id)
+        .bind(&to_account_no)
+        .fetch_one(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    let op = StepUpOperation::Transfer {
+        from_account_no: req.from_account_no.clone(),
+        to_account_no: to_account_no.clone(),
+        amount: req.amount.clone(),
+    };
+    ensure_step_up(
+        &state,
+        &ctx,
+        user_id,
+        &op,
+        &req.amount,
+        new_beneficiary,
+        req.step_up.as_ref(),
     )
-    .bind(user_id)
-    .bind(&req.from_account_no)
-    .bind(&req.pin)
-    .fetch_optional(&state.pool)
-    .await
-    .map_err(ApiError::from)?;
-
-    if !pin_ok.unwrap_or(false) {
-        return Err(ApiError::Unauthorized("invalid PIN".into()).into());
-    }
+    .await?;
 
     // =========================
     // Eksekusi transfer by account_no
@@ -82,15 +106,17 @@ pub async fn transfer(
     )
     .bind(user_id)
     .bind(&req.from_account_no)
-    .bind(&req.to_account_no)
-    .bind(req.amount)
+    .bind(&to_account_no)
+    .bind(&req.amount)
     .bind(req.description.clone())
     .bind(&req.akun)
     .fetch_one(&state.pool)
     .await
     .map_err(|e| {
         let msg = e.to_string();
-        if msg.contains("ACCOUNT_NOT_OWNED") {
+        if let Some(err) = account_status_error(&msg).or_else(|| limit_error(&msg)) {
+            err
+        } else if msg.contains("ACCOUNT_NOT_OWNED") {
             ApiError::Forbidden("account not owned".into())
         } else if msg.contains("INSUFFICIENT_FUNDS") {
             ApiError::BadRequest("insufficient funds".into())
@@ -116,11 +142,12 @@ pub async fn transfer(
     // Audit
     let meta = serde_json::json!({
         "from_account_no": req.from_account_no,
-        "to_account_no": req.to_account_no,
+        "to_account_no": to_account_no,
+        "beneficiary_id": req.beneficiary_id,
         "amount": req.amount,
         "desc": req.description
     });
-    audit(&state, Some(user_id), "transfer", None, Some(meta)).await;
+    audit(&state, &ctx, Some(user_id), "transfer", None, Some(meta)).await;
 
     Ok(Json(res))
 }
@@ -0,0 +1,81 @@
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+/// Langkah waktu TOTP (detik) dan jumlah digit (RFC 6238 default)
+pub const TOTP_STEP: i64 = 30;
+pub const TOTP_DIGITS: u32 = 6;
+/// Toleransi clock skew: 1 langkah sebelum & sesudah
+const TOTP_SKEW: i64 = 1;
+
+const ALPHABET: base32::Alphabet = base32::Alphabet::Rfc4648 { padding: false };
+
+/// Secret acak 160-bit, di-encode base32 (format standar aplikasi authenticator)
+pub fn generate_secret() -> String {
+    use rand_core::{OsRng, RngCore};
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    base32::encode(ALPHABET, &bytes)
+}
+
+/// URI `otpauth://` untuk QR code enrollment
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    let mut url = reqwest::Url::parse("otpauth://totp/").expect("static otpauth url");
+    url.set_path(&format!("{}:{}", issuer, account));
+    url.query_pairs_mut()
+        .append_pair("secret", secret)
+        .append_pair("issuer", issuer)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &TOTP_DIGITS.to_string())
+        .append_pair("period", &TOTP_STEP.to_string());
+    url.to_string()
+}
+
+/// Hitung kode HOTP untuk counter tertentu (RFC 4226)
+fn hotp(key: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("hmac accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let bin = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    bin % 10u32.pow(TOTP_DIGITS)
+}
+
+/// Verifikasi kode TOTP. Mengembalikan nomor langkah (time step) yang cocok,
+/// supaya pemanggil bisa menolak pemakaian ulang kode yang sama.
+pub fn verify(secret: &str, code: &str, unix_time: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let expected: u32 = code.parse().ok()?;
+    let key = base32::decode(ALPHABET, secret)?;
+
+    let current = unix_time / TOTP_STEP;
+    (current - TOTP_SKEW..=current + TOTP_SKEW)
+        .filter(|step| *step >= 0)
+        .find(|step| hotp(&key, *step as u64) == expected)
+}
+
+/// Recovery code sekali pakai, format `XXXXX-XXXXX`
+pub fn generate_recovery_codes(count: usize) -> Vec<String> {
+    use rand_core::{OsRng, RngCore};
+    (0..count)
+        .map(|_| {
+            let mut bytes = [0u8; 7];
+            OsRng.fill_bytes(&mut bytes);
+            let raw = base32::encode(ALPHABET, &bytes);
+            format!("{}-{}", &raw[..5], &raw[5..10])
+        })
+        .collect()
+}
+
+/// Normalisasi input recovery code (huruf besar, tanpa spasi) sebelum di-hash
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.trim().to_ascii_uppercase().replace(' ', "")
+}
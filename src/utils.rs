@@ -1,10 +1,16 @@
+use chrono::{DateTime, Utc};
+use sqlx::Row;
 use uuid::Uuid;
 
-use crate::{app_state::SharedState, errors::ApiError};
+use crate::{
+    app_state::SharedState, errors::ApiError, middleware::request_context::RequestContext,
+    password_policy::verify_password,
+};
 
 /// Helper audit (non-blocking log; error di-log saja)
 pub async fn audit(
     state: &SharedState,
+    ctx: &RequestContext,
     user_id: Option<Uuid>,
     action: &str,
     target: Option<&str>,
@@ -12,20 +18,19 @@ pub async fn audit(
 ) {
     let tgt = target.unwrap_or_default();
     let m = meta.unwrap_or(serde_json::Value::Null);
-    let ip = "unknown";
-    let ua = "unknown";
 
-    if let Err(e) = sqlx::query("SELECT lab_fun_audit($1,$2,$3,$4,$5,$6)")
+    if let Err(e) = sqlx::query("SELECT lab_fun_audit($1,$2,$3,$4,$5,$6,$7)")
         .bind(user_id)
         .bind(action)
         .bind(tgt)
         .bind(m)
-        .bind(ip)
-        .bind(ua)
+        .bind(&ctx.ip)
+        .bind(&ctx.user_agent)
+        .bind(ctx.request_id)
         .execute(&state.pool)
         .await
     {
-        tracing::warn!("audit failed ({}): {}", action, e);
+        tracing::warn!("audit failed ({}, req {}): {}", action, ctx.request_id, e);
     }
 }
 
@@ -37,9 +42,48 @@ pub fn sha256_bytes(input: &str) -> Vec<u8> {
     hasher.finalize().to_vec()
 }
 
-/// Verifikasi PIN akun terhadap fungsi DB: lab_fun_verify_account_pin(user_id, account_id, pin)
+/// Token acak 256-bit (base64 url-safe), untuk token sekali pakai yang dikirim ke user
+pub fn random_token() -> String {
+    use base64::Engine;
+    use rand_core::{OsRng, RngCore};
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Validasi PIN baru: 6 digit, bukan digit berulang (111111, 121212) atau urutan (123456, 987654)
+pub fn validate_new_pin(pin: &str) -> Result<(), ApiError> {
+    if pin.len() != 6 || !pin.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ApiError::BadRequest("pin must be 6 digits".into()));
+    }
+    let digits: Vec<i8> = pin.bytes().map(|b| (b - b'0') as i8).collect();
+    let mut distinct = digits.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    let step = digits[1] - digits[0];
+    let sequential = step.abs() == 1 && digits.windows(2).all(|w| w[1] - w[0] == step);
+    if distinct.len() <= 2 || sequential {
+        return Err(ApiError::BadRequest(
+            "pin too weak: avoid repeated or sequential digits".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Salah PIN beruntun sebelum PIN rekening dikunci sementara
+const PIN_LOCK_AFTER: i32 = 5;
+const PIN_LOCK_SECS: i32 = 60 * 30;
+
+const PIN_LOCKED_MSG: &str = "PIN locked after too many failed attempts, try again later";
+
+const PIN_RESET_MSG: &str = "PIN must be reset, set a new PIN with step_up";
+
+/// Verifikasi PIN rekening milik user (hash Argon2id; hash bcrypt lama di-upgrade saat cocok,
+/// PIN yang sudah dihapus `spawn_legacy_pin_expiry_job` wajib diatur ulang).
+/// Salah PIN dihitung per rekening; setelah `PIN_LOCK_AFTER` kali PIN dikunci `PIN_LOCK_SECS` detik.
 pub async fn verify_account_pin(
     state: &SharedState,
+    ctx: &RequestContext,
     user_id: Uuid,
     account_id: Uuid,
     pin: &str,
@@ -48,18 +92,115 @@ pub async fn verify_account_pin(
         return Err(ApiError::BadRequest("pin must be 6 digits".into()));
     }
 
-    let ok: Option<bool> =
-        sqlx::query_scalar(r#"SELECT lab_fun_verify_account_pin($1,$2,$3) AS ok"#)
-            .bind(user_id)
+    let row = sqlx::query("SELECT pin_hash, locked_until FROM lab_fun_account_pin_state($1,$2)")
+        .bind(user_id)
+        .bind(account_id)
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(ApiError::from)?;
+    let Some(row) = row else {
+        return Err(ApiError::Unauthorized("invalid PIN".into()));
+    };
+    if row
+        .get::<Option<DateTime<Utc>>, _>("locked_until")
+        .is_some()
+    {
+        return Err(ApiError::TooManyRequests(PIN_LOCKED_MSG.into()));
+    }
+
+    let Some(hash) = row.get::<Option<String>, _>("pin_hash") else {
+        return Err(ApiError::Forbidden(PIN_RESET_MSG.into()));
+    };
+    let legacy = !hash.starts_with("$argon2");
+    let valid = if legacy {
+        // hash bcrypt dari pgcrypto (sebelum PIN di-hash di aplikasi)
+        bcrypt::verify(pin, &hash).unwrap_or(false)
+    } else {
+        verify_password(&hash, pin)
+    };
+
+    if valid {
+        let rehash = if legacy {
+            Some(state.password_policy.hash(pin)?)
+        } else {
+            None
+        };
+        sqlx::query("SELECT lab_fun_account_pin_success($1,$2)")
+            .bind(account_id)
+            .bind(rehash)
+            .execute(&state.pool)
+            .await
+            .map_err(ApiError::from)?;
+        return Ok(());
+    }
+
+    let locked_until: Option<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT lab_fun_account_pin_failure($1,$2,$3)")
             .bind(account_id)
-            .bind(pin)
+            .bind(PIN_LOCK_AFTER)
+            .bind(PIN_LOCK_SECS)
+            .fetch_one(&state.pool)
+            .await
+            .map_err(ApiError::from)?;
+    if let Some(until) = locked_until {
+        let meta = serde_json::json!({ "locked_until": until });
+        audit(
+            state,
+            ctx,
+            Some(user_id),
+            "account_pin_locked",
+            Some(&account_id.to_string()),
+            Some(meta),
+        )
+        .await;
+        return Err(ApiError::TooManyRequests(PIN_LOCKED_MSG.into()));
+    }
+    Err(ApiError::Unauthorized("invalid PIN".into()))
+}
+
+/// Sama dengan `verify_account_pin`, rekening dicari dari nomor rekening milik user
+pub async fn verify_account_pin_by_no(
+    state: &SharedState,
+    ctx: &RequestContext,
+    user_id: Uuid,
+    account_no: &str,
+    pin: &str,
+) -> Result<(), ApiError> {
+    let account_id: Option<Uuid> =
+        sqlx::query_scalar("SELECT id FROM lab_accounts WHERE account_no = $1 AND user_id = $2")
+            .bind(account_no.trim())
+            .bind(user_id)
             .fetch_optional(&state.pool)
             .await
             .map_err(ApiError::from)?;
+    match account_id {
+        Some(account_id) => verify_account_pin(state, ctx, user_id, account_id, pin).await,
+        None => Err(ApiError::Unauthorized("invalid PIN".into())),
+    }
+}
 
-    if ok.unwrap_or(false) {
-        Ok(())
-    } else {
-        Err(ApiError::Unauthorized("invalid PIN".into()))
+#[cfg(test)]
+mod tests {
+    use super::validate_new_pin;
+
+    #[test]
+    fn new_pin_must_be_six_digits() {
+        for pin in ["", "12345", "1234567", "12a456", " 13579", "１３５７９０"] {
+            assert!(validate_new_pin(pin).is_err(), "{:?} accepted", pin);
+        }
+    }
+
+    #[test]
+    fn new_pin_rejects_repeated_and_sequential_digits() {
+        for pin in ["111111", "121212", "000000", "123456", "987654", "456789"] {
+            assert!(validate_new_pin(pin).is_err(), "{:?} accepted", pin);
+        }
+    }
+
+    #[test]
+    fn new_pin_accepts_ordinary_pins() {
+        for pin in ["135790", "246813", "902817", "123457"] {
+            assert!(validate_new_pin(pin).is_ok(), "{:?} rejected", pin);
+        }
     }
 }